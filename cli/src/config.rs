@@ -0,0 +1,141 @@
+//! REPL settings, read from `~/.huckrc`. Each line is a `key = value` pair,
+//! and lines starting with `#` are comments:
+//! ```text
+//! edit-mode = vi
+//! auto-add-history = true
+//! completion = list
+//! ```
+
+use std::path::PathBuf;
+
+use rustyline::{config::Configurer, CompletionType, Config, EditMode};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReplConfig {
+    pub edit_mode: EditMode,
+    pub auto_add_history: bool,
+    pub completion_type: CompletionType,
+}
+
+impl Default for ReplConfig {
+    fn default() -> Self {
+        ReplConfig {
+            edit_mode: EditMode::Emacs,
+            auto_add_history: true,
+            completion_type: CompletionType::Circular,
+        }
+    }
+}
+
+impl ReplConfig {
+    /// The settings in `~/.huckrc`, or the defaults if there is none. Invalid
+    /// lines are reported and skipped.
+    pub fn load() -> ReplConfig {
+        let mut config = ReplConfig::default();
+        let text = match path().and_then(|path| std::fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return config,
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => Err(format!("Expected key = value, got: {}", line)),
+            };
+            if let Err(err) = result {
+                println!("~/.huckrc:{}: {}", number + 1, err);
+            }
+        }
+        config
+    }
+
+    /// Saves `key = value` to `~/.huckrc`, replacing the line that sets `key`
+    /// or adding one at the end. Comments and other lines are kept as they are.
+    pub fn save(key: &str, value: &str) -> Result<(), String> {
+        let path = path().ok_or("HOME is not set")?;
+        let text = std::fs::read_to_string(&path).unwrap_or_default();
+        std::fs::write(path, with_setting(&text, key, value)).map_err(|err| err.to_string())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value for {}: {}", key, value);
+        match key {
+            "edit-mode" => {
+                self.edit_mode = match value {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    _ => return Err(invalid()),
+                }
+            }
+            "auto-add-history" => self.auto_add_history = value.parse().map_err(|_| invalid())?,
+            "completion" => {
+                self.completion_type = match value {
+                    "circular" => CompletionType::Circular,
+                    "list" => CompletionType::List,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(format!("Unknown setting: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// The line editor configuration for these settings. History is added
+    /// by the REPL, so a pasted block is kept as one entry.
+    pub fn editor_config(&self) -> Config {
+        Config::builder()
+            .edit_mode(self.edit_mode)
+            .completion_type(self.completion_type)
+            .build()
+    }
+
+    /// Applies these settings to a running editor.
+    pub fn apply(&self, editor: &mut impl Configurer) {
+        editor.set_edit_mode(self.edit_mode);
+        editor.set_completion_type(self.completion_type);
+    }
+}
+
+impl std::fmt::Display for ReplConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let edit_mode = match self.edit_mode {
+            EditMode::Vi => "vi",
+            _ => "emacs",
+        };
+        let completion = match self.completion_type {
+            CompletionType::List => "list",
+            _ => "circular",
+        };
+        writeln!(f, "edit-mode = {}", edit_mode)?;
+        writeln!(f, "auto-add-history = {}", self.auto_add_history)?;
+        writeln!(f, "completion = {}", completion)
+    }
+}
+
+/// `text` with each line setting `key` replaced by `key = value`, or with
+/// the line added if none does.
+fn with_setting(text: &str, key: &str, value: &str) -> String {
+    let setting = format!("{} = {}", key, value);
+    let mut found = false;
+    let mut lines: Vec<&str> = text
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name, _)) if !line.trim_start().starts_with('#') && name.trim() == key => {
+                found = true;
+                setting.as_str()
+            }
+            _ => line,
+        })
+        .collect();
+    if !found {
+        lines.push(&setting);
+    }
+    lines.join("\n") + "\n"
+}
+
+fn path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".huckrc"))
+}
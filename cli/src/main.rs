@@ -1,45 +1,508 @@
-use huckleberry_lib::{env::Env, evaluator::eval};
+#![allow(clippy::result_large_err)]
+
+use config::ReplConfig;
+use huckleberry_lib::{
+    analysis::{check, check_tail_calls, check_unused_defns},
+    cassette::Cassette,
+    docs::{extract, to_markdown},
+    env::{Env, EnvRef, EnvSnapshot},
+    error::HError,
+    evaluator::eval,
+    expr::{Expr, PrintLimits},
+    interpreter::{Interpreter, Options},
+    kernel::Kernel,
+    manifest::Manifest,
+    modules::{
+        doc::method_doc,
+        huckleberry::PRELUDE_DIR,
+        inspect::inspect,
+        markdown::to_html,
+        process::{is_trapped, listen, raise, Signal},
+        require::FileLoader,
+    },
+    parser::{parse, parse_with_spans},
+    stats::memory_stats,
+    trace::backtrace,
+};
 use rustyline::error::ReadlineError;
 use rustyline::{Editor, Result};
 
+mod config;
+
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
 
-    if args.len() > 2 {
-        println!("Usage: huck [script]");
-        std::process::exit(64);
-    } else if args.len() == 2 {
-        run_file(&args[1]);
-    } else {
-        repl();
+    match &args[1..] {
+        [] => repl(false, ReplConfig::load()),
+        [flag] if flag == "--watch" => repl(true, ReplConfig::load()),
+        [flag, mode] if flag == "--edit-mode" => {
+            let mut config = ReplConfig::load();
+            match config.set("edit-mode", mode) {
+                Ok(()) => repl(false, config),
+                Err(err) => {
+                    println!("{}", err);
+                    std::process::exit(64);
+                }
+            }
+        }
+        [command] if command == "deps" => fetch_deps(),
+        [command, flag, path] if command == "doc" && flag == "--html" => print_docs(path, true),
+        [command, path] if command == "doc" => print_docs(path, false),
+        [command] if command == "kernel" => {
+            kernel().serve(std::io::stdin().lock(), std::io::stdout())?;
+        }
+        [command, port @ ..] if command == "serve-repl" => {
+            let port = match port {
+                [] => "7777",
+                [flag, port] if flag == "--port" => port,
+                _ => {
+                    println!("Usage: huck serve-repl [--port port]");
+                    std::process::exit(64);
+                }
+            };
+            let listener = std::net::TcpListener::bind(format!("127.0.0.1:{}", port))?;
+            println!("Listening on {}", listener.local_addr()?);
+            kernel().serve_tcp(listener)?;
+        }
+        [command, flag, paths @ ..] if command == "test" && flag == "--coverage" => {
+            if run_tests(paths, true) > 0 {
+                std::process::exit(1);
+            }
+        }
+        [command, paths @ ..] if command == "test" => {
+            if run_tests(paths, false) > 0 {
+                std::process::exit(1);
+            }
+        }
+        [command, flag, path] if command == "check" && flag == "--unused" => {
+            if report_warnings(path, false, true) > 0 {
+                std::process::exit(1);
+            }
+        }
+        [command, path] if command == "check" => {
+            if report_warnings(path, false, false) > 0 {
+                std::process::exit(1);
+            }
+        }
+        [flag, path] if flag == "--warn" => {
+            report_warnings(path, false, true);
+            run_file(path, &[], Options::default());
+        }
+        [flag, path] if flag == "--warn-tail" => {
+            report_warnings(path, true, true);
+            run_file(path, &[], Options::default());
+        }
+        [flag, path, script_args @ ..] if flag == "--break-on-error" => run_file(
+            path,
+            script_args,
+            Options {
+                break_on_error: Some(std::rc::Rc::new(debug_error)),
+                ..Options::default()
+            },
+        ),
+        [flag, path, script_args @ ..] if flag == "--deterministic" => run_file(
+            path,
+            script_args,
+            Options {
+                deterministic: true,
+                ..Options::default()
+            },
+        ),
+        [flag, path, script_args @ ..] if flag == "--allow-redefine" => run_file(
+            path,
+            script_args,
+            Options {
+                allow_redefine: true,
+                ..Options::default()
+            },
+        ),
+        [flag, cassette, path, script_args @ ..] if flag == "--record" || flag == "--replay" => {
+            let cassette = std::path::Path::new(cassette);
+            let cassette = if flag == "--record" {
+                Cassette::record(cassette)
+            } else {
+                Cassette::replay(cassette)
+            };
+            let cassette = match cassette {
+                Ok(cassette) => cassette,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+            let options = Options {
+                cassette: Some(cassette),
+                ..Options::default()
+            };
+            run_file(path, script_args, options)
+        }
+        [flag, path, script_args @ ..] if flag == "--mem-report" => {
+            run_file(path, script_args, Options::default());
+            // The interpreter is gone, so anything still live was leaked.
+            let stats = memory_stats();
+            eprintln!(
+                "Still live after the script: {} envs, {} fns",
+                stats.envs, stats.fns
+            );
+        }
+        [flag, ..] if flag.starts_with('-') => {
+            println!("Usage: huck [--watch | --edit-mode vi|emacs | deps | kernel | serve-repl [--port port] | check script | test [--coverage] scripts... | --warn script | --warn-tail script | [--break-on-error | --deterministic | --allow-redefine | --mem-report | --record file | --replay file] script [args...]]");
+            std::process::exit(64);
+        }
+        [path, script_args @ ..] => run_file(path, script_args, Options::default()),
     }
 
     Ok(())
 }
 
-fn run_file(path: &str) {
-    let contents = std::fs::read_to_string(path).expect(&format!("Unable to read file: {}", path));
-    let env = Env::with_core_module().into_ref();
-    eval(&contents, env).unwrap();
+fn read_file(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|_| panic!("Unable to read file: {}", path))
+}
+
+/// A kernel finding required files in the working directory.
+fn kernel() -> Kernel {
+    Kernel::new(Options {
+        loader: Some(std::rc::Rc::new(FileLoader::from_env(
+            std::path::PathBuf::new(),
+        ))),
+        ..Options::default()
+    })
+    .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// An interpreter for the script at `path`, finding required files next to it.
+fn script_interpreter(path: &str, options: Options) -> Interpreter {
+    let root = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    Interpreter::with_options(Options {
+        loader: Some(std::rc::Rc::new(FileLoader::from_env(root.to_path_buf()))),
+        ..options
+    })
+    .unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Runs a script under `options`.
+fn run_file(path: &str, args: &[String], options: Options) {
+    trap_signals();
+    let contents = read_file(path);
+    let interpreter = script_interpreter(path, options);
+    let args: Vec<Expr> = args.iter().map(|arg| Expr::string(arg)).collect();
+    interpreter.env().def("*args*", Expr::Vector(args)).unwrap();
+    // Like an event loop, pending timers run once the script itself is done.
+    let result = interpreter
+        .eval_source(&contents, path)
+        .and_then(|_| interpreter.run_scheduled());
+    // Taken first, since running the exit handlers starts a new trace.
+    let frames = result.as_ref().err().map(backtrace).unwrap_or_default();
+    if let Err(err) = interpreter.run_exit_handlers() {
+        eprintln!("{}", err);
+    }
+    match result {
+        Ok(_) => (),
+        Err(HError::Interrupted) => std::process::exit(130),
+        Err(err) => {
+            eprintln!("{}", err);
+            for frame in frames {
+                eprintln!("  {}", frame);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs each test script, which passes if it finishes without an error.
+/// With `coverage`, also prints the lines each evaluated file ran and writes
+/// them to `lcov.info`. Returns the number of failures.
+fn run_tests(paths: &[String], coverage: bool) -> usize {
+    if coverage {
+        huckleberry_lib::coverage::start();
+    }
+    let mut failures = 0;
+    for path in paths {
+        let interpreter = script_interpreter(path, Options::default());
+        let result = interpreter
+            .eval_source(&read_file(path), path)
+            .and_then(|_| interpreter.run_scheduled());
+        match result {
+            Ok(_) => println!("ok {}", path),
+            Err(err) => {
+                failures += 1;
+                println!("FAIL {}: {}", path, err);
+                for frame in backtrace(&err) {
+                    println!("  {}", frame);
+                }
+            }
+        }
+    }
+    println!("{} passed, {} failed", paths.len() - failures, failures);
+
+    if coverage {
+        let files = match huckleberry_lib::coverage::finish() {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("Unable to compute coverage: {}", err);
+                return failures.max(1);
+            }
+        };
+        let mut lcov = String::new();
+        for file in &files {
+            let (hit, found) = file.covered();
+            let percent = if found == 0 {
+                100.
+            } else {
+                hit as f64 * 100. / found as f64
+            };
+            println!("{}: {}/{} lines ({:.1}%)", file.name, hit, found, percent);
+            let missed = file.missed();
+            if !missed.is_empty() {
+                let missed: Vec<String> = missed.iter().map(|line| line.to_string()).collect();
+                println!("  not run: {}", missed.join(", "));
+            }
+            lcov.push_str(&file.lcov());
+        }
+        if let Err(err) = std::fs::write("lcov.info", lcov) {
+            eprintln!("Unable to write lcov.info: {}", err);
+        }
+    }
+    failures
+}
+
+/// A REPL in the env an error escaped from. Returns the value the failing
+/// call should return instead, or `None` to let the error propagate.
+fn debug_error(err: &HError, env: EnvRef) -> Option<Expr> {
+    println!("{}", err);
+    println!("Debugging the failing call. :locals lists its bindings, :continue [expr] returns expr from it, and :abort stops.");
+    let print = |expr: &Expr| {
+        PrintLimits::from_env(&env)
+            .readably()
+            .scope(|| println!("{}", expr))
+    };
+    let mut rl = Editor::<()>::with_config(ReplConfig::load().editor_config());
+    loop {
+        let line = match rl.readline("debug>> ") {
+            Ok(line) => line,
+            Err(_) => return None,
+        };
+        rl.add_history_entry(line.as_str());
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match name {
+            ":locals" => match env.locals() {
+                Ok(locals) => {
+                    for (name, value) in locals {
+                        print!("{} = ", name);
+                        print(&value);
+                    }
+                }
+                Err(err) => println!("{}", err),
+            },
+            ":continue" if rest.trim().is_empty() => return Some(Expr::Nil),
+            ":continue" => match eval(rest, env.clone_ref()) {
+                Ok(value) => return Some(value),
+                Err(err) => println!("{}", err),
+            },
+            ":abort" => return None,
+            _ => match eval(line, env.clone_ref()) {
+                Ok(value) => print(&value),
+                Err(err) => println!("{}", err),
+            },
+        }
+    }
+}
+
+/// Fetches the dependencies listed in `huck.toml` in the working directory.
+fn fetch_deps() {
+    let manifest = Manifest::parse(&read_file("huck.toml")).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    match manifest.fetch(std::path::Path::new("")) {
+        Ok(dirs) => {
+            for dir in dirs {
+                println!("Fetched {}", dir.display());
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints API docs for the `.huck` files at `path`, a file or a directory
+/// searched recursively, as Markdown or as an HTML page.
+fn print_docs(path: &str, html: bool) {
+    let mut files = vec![];
+    for file in huck_files(std::path::Path::new(path)) {
+        let name = file.display().to_string();
+        match extract(&read_file(&name)) {
+            Ok(entries) => files.push((name, entries)),
+            Err(err) => {
+                eprintln!("{}: {}", name, err);
+                std::process::exit(1);
+            }
+        }
+    }
+    let title = path.trim_end_matches('/');
+    let markdown = to_markdown(title, &files);
+    if html {
+        println!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n{}\n</body>\n</html>",
+            title,
+            to_html(&markdown)
+        );
+    } else {
+        print!("{}", markdown);
+    }
 }
 
-fn repl() {
-    let env = Env::with_core_module().into_ref();
+/// `path` if it is a file, or the `.huck` files under it, in name order.
+fn huck_files(path: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return vec![path.to_path_buf()],
+    };
+    let mut paths: Vec<std::path::PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .flat_map(|path| match path.is_dir() {
+            true => huck_files(&path),
+            false if path.extension().is_some_and(|ext| ext == "huck") => vec![path],
+            false => vec![],
+        })
+        .collect()
+}
 
-    let mut rl = Editor::<()>::new();
+/// Turns SIGINT and SIGTERM into signals the interpreter checks between
+/// steps, for scripts that trap them with `on-signal`. Untrapped signals keep
+/// their default action, so they still stop the script at once.
+#[cfg(unix)]
+fn trap_signals() {
+    extern "C" fn handle(sig: libc::c_int) {
+        let signal = match sig {
+            libc::SIGINT => Signal::Interrupt,
+            _ => Signal::Terminate,
+        };
+        if is_trapped(signal) {
+            raise(signal);
+        } else {
+            // SAFETY: signal and raise are async-signal-safe.
+            unsafe {
+                libc::signal(sig, libc::SIG_DFL);
+                libc::raise(sig);
+            }
+        }
+    }
+    listen();
+    // SAFETY: the handler only touches atomics or restores the default action.
+    // Without SA_RESTART, a blocking call returns early with EINTR, so a native
+    // waiting on input doesn't hold back a trapped signal.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handle as *const () as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut());
+    }
+}
+
+#[cfg(not(unix))]
+fn trap_signals() {}
+
+/// Prints static analysis warnings for the script to stderr, returning how many were found.
+/// `tail` also reports recursive calls that are not in tail position, and
+/// `unused` defns nothing else in the file calls, which only makes sense for
+/// whole programs rather than libraries.
+fn report_warnings(path: &str, tail: bool, unused: bool) -> usize {
+    let source = read_file(path);
+    let nodes = match parse_with_spans(&source) {
+        Ok(nodes) => nodes,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return 1;
+        }
+    };
+    let mut warnings = check(&nodes, &Env::with_core_module().unwrap().into_ref());
+    if unused {
+        warnings.extend(check_unused_defns(&nodes));
+    }
+    if tail {
+        warnings.extend(check_tail_calls(&nodes));
+    }
+    warnings.sort_by_key(|(span, _)| span.start);
+    for (span, warning) in &warnings {
+        let (line, column) = span.line_col(&source);
+        eprintln!("{}:{}:{}: warning: {}", path, line, column, warning);
+    }
+    warnings.len()
+}
+
+fn repl(watch: bool, mut config: ReplConfig) {
+    let mut repl = Repl {
+        interpreter: Interpreter::new().unwrap(),
+        last: Expr::Nil,
+        checkpoint: None,
+        watch,
+    };
+
+    let mut rl = Editor::<()>::with_config(config.editor_config());
     loop {
         let readline = rl.readline(">> ");
         match readline {
-            Ok(line) => match eval(&line, env.clone_ref()) {
-                Ok(expr) => {
-                    println!("{}", expr);
-                    rl.add_history_entry(line);
+            Ok(line) if line.trim() == ":paste" => {
+                println!("Paste mode. End with a line containing only \".\".");
+                match read_paste(&mut rl) {
+                    Some(buffer) => {
+                        if config.auto_add_history {
+                            rl.add_history_entry(buffer.as_str());
+                        }
+                        repl.eval(&buffer);
+                    }
+                    None => println!("Paste cancelled"),
                 }
-                Err(err) => {
-                    println!("{:?}", err);
-                    rl.add_history_entry(line);
+            }
+            Ok(line) if line.trim() == ":set" || line.trim_start().starts_with(":set ") => {
+                let setting = line.trim()[4..].trim();
+                match setting.split_once(' ') {
+                    Some((key, value)) => match config.set(key, value.trim()) {
+                        Ok(()) => {
+                            config.apply(&mut rl);
+                            if let Err(err) = ReplConfig::save(key, value.trim()) {
+                                println!("Unable to save ~/.huckrc: {}", err);
+                            }
+                        }
+                        Err(err) => println!("{}", err),
+                    },
+                    None => print!("{}", config),
                 }
-            },
+            }
+            Ok(line)
+                if line.trim() == ":reload-core"
+                    || line.trim_start().starts_with(":reload-core ") =>
+            {
+                let dir = match line.trim()[12..].trim() {
+                    "" => PRELUDE_DIR,
+                    dir => dir,
+                };
+                match repl.interpreter.reload_prelude(std::path::Path::new(dir)) {
+                    Ok(files) if files.is_empty() => println!("Reloaded the built-in prelude"),
+                    Ok(files) => println!("Reloaded {}", files.join(", ")),
+                    Err(err) => println!("{}", err),
+                }
+                config = ReplConfig::load();
+                config.apply(&mut rl);
+            }
+            Ok(line) => {
+                if config.auto_add_history {
+                    rl.add_history_entry(line.as_str());
+                }
+                if !repl.command(&line) {
+                    repl.eval(&line);
+                }
+            }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
                 break;
@@ -55,3 +518,77 @@ fn repl() {
         }
     }
 }
+
+/// Reads lines until one containing only `.`, returning them as one input
+/// so blank lines inside a pasted definition don't end it early. Returns
+/// `None` if input ends or is interrupted first.
+fn read_paste(rl: &mut Editor<()>) -> Option<String> {
+    let mut lines = vec![];
+    loop {
+        match rl.readline("") {
+            Ok(line) if line.trim() == "." => return Some(lines.join("\n")),
+            Ok(line) => lines.push(line),
+            Err(_) => return None,
+        }
+    }
+}
+
+struct Repl {
+    interpreter: Interpreter,
+    // The most recent result, used by `:inspect` to drill into large values.
+    last: Expr,
+    checkpoint: Option<EnvSnapshot>,
+    // Reload required files that changed on disk before each line.
+    watch: bool,
+}
+
+impl Repl {
+    /// Runs a `:command` line, returning false if the line is not a command.
+    fn command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let env = self.interpreter.env();
+        match name {
+            ":inspect" => match parse(rest).and_then(|path| inspect(&self.last, &path)) {
+                Ok(text) => println!("{}", text),
+                Err(err) => println!("{}", err),
+            },
+            ":doc" => match method_doc(rest.trim(), env) {
+                Ok(text) => println!("{}", text),
+                Err(err) => println!("{}", err),
+            },
+            ":checkpoint" => match env.snapshot() {
+                Ok(snapshot) => self.checkpoint = Some(snapshot),
+                Err(err) => println!("{}", err),
+            },
+            ":rollback" => match &self.checkpoint {
+                Some(snapshot) => {
+                    if let Err(err) = env.restore(snapshot.clone()) {
+                        println!("{}", err);
+                    }
+                }
+                None => println!("No checkpoint to roll back to"),
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn eval(&mut self, line: &str) {
+        if self.watch {
+            match self.interpreter.reload_changed() {
+                Ok(paths) => paths.iter().for_each(|path| println!("Reloaded {}", path)),
+                Err(err) => println!("{}", err),
+            }
+        }
+        match self.interpreter.eval(line) {
+            Ok(expr) => {
+                PrintLimits::from_env(&self.interpreter.env())
+                    .readably()
+                    .scope(|| println!("{}", expr));
+                self.last = expr;
+            }
+            Err(err) => println!("{}", err),
+        }
+    }
+}
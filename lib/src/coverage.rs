@@ -0,0 +1,133 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+};
+
+use crate::{error::HError, expr::Expr, parser::list_lines};
+
+/// How often each line of a file ran. Only lines where a call starts are
+/// listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub name: String,
+    pub lines: BTreeMap<usize, usize>, // Line number to times run
+}
+
+// Exprs carry no source positions, so a call is matched to its line by
+// value. Identical calls on different lines share their count.
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static HITS: RefCell<BTreeMap<Expr, usize>> = const { RefCell::new(BTreeMap::new()) };
+    // Files evaluated while coverage was on, by name.
+    static SOURCES: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Starts recording which calls are evaluated, forgetting earlier results.
+pub fn start() {
+    HITS.with(|hits| hits.borrow_mut().clear());
+    SOURCES.with(|sources| sources.borrow_mut().clear());
+    ENABLED.with(|enabled| enabled.set(true));
+}
+
+/// Stops recording and returns the coverage of each file evaluated since
+/// `start`, by `Interpreter::eval_source` or `require`.
+pub fn finish() -> Result<Vec<FileCoverage>, HError> {
+    ENABLED.with(|enabled| enabled.set(false));
+    let hits = HITS.with(|hits| hits.take());
+    SOURCES
+        .with(|sources| sources.take())
+        .into_iter()
+        .map(|(name, source)| {
+            let mut lines = BTreeMap::new();
+            for (line, list) in list_lines(&source)? {
+                let count = hits.get(&list).copied().unwrap_or(0);
+                let hit = lines.entry(line).or_insert(0);
+                *hit = count.max(*hit);
+            }
+            Ok(FileCoverage { name, lines })
+        })
+        .collect()
+}
+
+pub(crate) fn record_source(name: &str, source: &str) {
+    if ENABLED.with(|enabled| enabled.get()) {
+        SOURCES.with(|sources| {
+            sources
+                .borrow_mut()
+                .insert(name.to_string(), source.to_string())
+        });
+    }
+}
+
+/// Counts one evaluation of the call `list`.
+pub(crate) fn record(list: &Expr) {
+    if ENABLED.with(|enabled| enabled.get()) {
+        HITS.with(|hits| {
+            let mut hits = hits.borrow_mut();
+            match hits.get_mut(list) {
+                Some(count) => *count += 1,
+                None => {
+                    hits.insert(list.clone(), 1);
+                }
+            }
+        });
+    }
+}
+
+impl FileCoverage {
+    /// The number of lines that ran, out of the lines that could.
+    pub fn covered(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|count| **count > 0).count();
+        (hit, self.lines.len())
+    }
+
+    /// Lines that never ran.
+    pub fn missed(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(line, _)| *line)
+            .collect()
+    }
+
+    /// This file's record in the lcov tracefile format.
+    pub fn lcov(&self) -> String {
+        let mut record = format!("SF:{}\n", self.name);
+        for (line, count) in &self.lines {
+            record.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        let (hit, found) = self.covered();
+        record.push_str(&format!("LH:{}\nLF:{}\nend_of_record\n", hit, found));
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_reports_lines_that_ran() {
+        let interpreter = Interpreter::new().unwrap();
+        start();
+        interpreter
+            .eval_source(
+                "(defn pick [n]\n  (if (gt n 0)\n    (+ n 1)\n    (- n 1)))\n(pick 2)\n(pick 3)",
+                "pick.huck",
+            )
+            .unwrap();
+        let coverage = finish().unwrap();
+
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(
+            coverage[0].lines,
+            BTreeMap::from([(1, 1), (2, 2), (3, 2), (4, 0), (5, 1), (6, 1)])
+        );
+        assert_eq!(coverage[0].covered(), (5, 6));
+        assert_eq!(coverage[0].missed(), vec![4]);
+        assert!(coverage[0]
+            .lcov()
+            .starts_with("SF:pick.huck\nDA:1,1\nDA:2,2\n"));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    error::HError,
+    expr::Expr,
+    parser::{list_lines, parse},
+};
+
+/// A public fn or method defined at the top level of a library file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocEntry {
+    pub signature: String, // e.g. `(greet name)` or `<string? repeat: n>`
+    pub doc: Option<String>,
+    pub line: Option<usize>,
+}
+
+/// Lists the defns and defms of `input`, in the order they are defined.
+/// Like in `defm`, a string leading a body with more forms after it is a
+/// docstring. Private `defn-`s are left out.
+pub fn extract(input: &str) -> Result<Vec<DocEntry>, HError> {
+    let lines = list_lines(input)?;
+    let line_of = |form: &Expr| {
+        lines
+            .iter()
+            .find(|(_, list)| list == form)
+            .map(|(line, _)| *line)
+    };
+    Ok(parse(input)?
+        .iter()
+        .filter_map(|form| {
+            let (signature, body) = match form {
+                Expr::List(list) => match list.as_slice() {
+                    [Expr::Symbol(head), Expr::Symbol(name), Expr::Vector(params), body @ ..]
+                        if head == "defn" || head == "defn-curried" =>
+                    {
+                        (fn_signature(name, params), body)
+                    }
+                    [Expr::Symbol(head), selector, Expr::Vector(params), body @ ..]
+                        if head == "defm" =>
+                    {
+                        (method_signature(selector, params), body)
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            let doc = match body {
+                [Expr::String(doc), rest @ ..] if !rest.is_empty() => Some(doc.to_string()),
+                _ => None,
+            };
+            Some(DocEntry {
+                signature,
+                doc,
+                line: line_of(form),
+            })
+        })
+        .collect())
+}
+
+fn fn_signature(name: &str, params: &[Expr]) -> String {
+    let parts: Vec<String> = std::iter::once(name.to_string())
+        .chain(params.iter().map(Expr::to_string))
+        .collect();
+    format!("({})", parts.join(" "))
+}
+
+/// Pairs each part of the method id with its argument, as `doc-method` does.
+fn method_signature(selector: &Expr, params: &[Expr]) -> String {
+    let parts: Vec<String> = std::iter::once(selector.to_string())
+        .chain(params.chunks(2).map(|pair| match pair {
+            [part, arg] => format!("{}: {}", part, arg),
+            part => part[0].to_string(),
+        }))
+        .collect();
+    format!("<{}>", parts.join(" "))
+}
+
+/// Renders the entries of each named file as a Markdown API reference.
+/// Files without entries are left out.
+pub fn to_markdown(title: &str, files: &[(String, Vec<DocEntry>)]) -> String {
+    let mut sections = vec![format!("# {}", title)];
+    for (name, entries) in files.iter().filter(|(_, entries)| !entries.is_empty()) {
+        sections.push(format!("## {}", name));
+        for entry in entries {
+            sections.push(format!("### `{}`", entry.signature));
+            if let Some(line) = entry.line {
+                sections.push(format!("Defined in `{}` on line {}.", name, line));
+            }
+            if let Some(doc) = &entry.doc {
+                sections.push(doc.to_string());
+            }
+        }
+    }
+    sections.join("\n\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_defns_and_defms() {
+        let entries = extract(
+            "(defn greet [name] \"Greets name.\" (println name))\n\
+             (defn- helper [] 1)\n\
+             (defn id [x] \"x\")\n\
+             (defm string? [repeat: n]\n  \"Repeats this n times.\"\n  this)\n\
+             (var x 1)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries,
+            vec![
+                DocEntry {
+                    signature: "(greet name)".to_string(),
+                    doc: Some("Greets name.".to_string()),
+                    line: Some(1),
+                },
+                DocEntry {
+                    signature: "(id x)".to_string(),
+                    doc: None,
+                    line: Some(3),
+                },
+                DocEntry {
+                    signature: "<string? repeat: n>".to_string(),
+                    doc: Some("Repeats this n times.".to_string()),
+                    line: Some(4),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_renders_markdown() {
+        let files = vec![
+            (
+                "src/greet.huck".to_string(),
+                extract("(defn greet [name & more] \"Greets *name*.\" nil)").unwrap(),
+            ),
+            ("src/empty.huck".to_string(), vec![]),
+        ];
+
+        assert_eq!(
+            to_markdown("API", &files),
+            "# API\n\n## src/greet.huck\n\n### `(greet name & more)`\n\n\
+             Defined in `src/greet.huck` on line 1.\n\nGreets *name*.\n"
+        );
+    }
+}
@@ -1,80 +1,208 @@
-use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
 use crate::{
     error::HError,
     expr::{Arity, Expr, Method},
+    interpreter::check_bindings,
     modules::core_module,
+    stats::{Kind, Live},
 };
 
-pub struct EnvRef(Rc<RefCell<Option<Env>>>);
+/// A shared handle to an environment. Handles can be sent between threads,
+/// so a core env built once can be extended by sessions on many threads.
+pub struct EnvRef(Arc<RwLock<Option<Env>>>);
 
 pub struct Env {
     vars: BTreeMap<String, Expr>,
     methods: BTreeMap<String, Vec<Method>>,
+    macros: BTreeMap<String, Expr>,
     enclosing: EnvRef,
+    // Set for sessions, whose enclosing env is shared and never written to.
+    copy_on_write: bool,
+    // Vars left out of imported modules, and the module each came from.
+    private: BTreeMap<String, String>,
+    _live: Live,    // Counts this env in `memory-stats`
+    defined: usize, // Vars added by `define`, counted against the binding limit
+}
+
+/// A copy of an environment's own bindings, excluding enclosing scopes.
+/// Atoms are references, so their contents are shared with the live env.
+#[derive(Clone)]
+pub struct EnvSnapshot {
+    vars: BTreeMap<String, Expr>,
+    methods: BTreeMap<String, Vec<Method>>,
+    macros: BTreeMap<String, Expr>,
 }
 
-fn new_rc_ref_cell<T>(x: T) -> Rc<RefCell<T>> {
-    Rc::new(RefCell::new(x))
+fn new_lock<T>(x: T) -> Arc<RwLock<T>> {
+    Arc::new(RwLock::new(x))
 }
 
 impl EnvRef {
     pub fn nil() -> EnvRef {
-        EnvRef(new_rc_ref_cell(None))
+        EnvRef(new_lock(None))
     }
 
     pub fn new(env: Env) -> EnvRef {
-        EnvRef(new_rc_ref_cell(Some(env)))
+        EnvRef(new_lock(Some(env)))
+    }
+
+    // Envs are only written while no evaluation is reading them, so a panic
+    // can't leave one half-written.
+    fn read(&self) -> RwLockReadGuard<'_, Option<Env>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Option<Env>> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
     }
 
     pub fn is_some(&self) -> bool {
-        self.0.borrow().as_ref().is_some()
+        self.read().as_ref().is_some()
     }
 
     pub fn clone_ref(&self) -> EnvRef {
-        EnvRef(Rc::clone(&self.0))
+        EnvRef(Arc::clone(&self.0))
     }
 
     pub fn get(&self, id: &str) -> Result<Expr, HError> {
-        self.0
-            .borrow()
+        self.read()
             .as_ref()
-            .ok_or_else(|| HError::EnvironmentNotFound)?
+            .ok_or(HError::EnvironmentNotFound)?
             .get(id)
     }
 
     pub fn merge(&self, env: Env) -> Result<(), HError> {
-        self.0
-            .borrow_mut()
+        self.write()
             .as_mut()
-            .ok_or_else(|| HError::EnvironmentNotFound)?
+            .ok_or(HError::EnvironmentNotFound)?
             .merge(env);
 
         Ok(())
     }
 
     pub fn get_methods(&self, id: &str) -> Result<Vec<Method>, HError> {
-        self.0
-            .borrow()
+        self.read()
             .as_ref()
-            .ok_or_else(|| HError::EnvironmentNotFound)?
+            .ok_or(HError::EnvironmentNotFound)?
             .get_methods(id)
     }
 
+    /// The expander of the macro `id`, if one is visible from this env.
+    pub fn get_macro(&self, id: &str) -> Option<Expr> {
+        self.read().as_ref()?.get_macro(id)
+    }
+
     pub fn set(&self, key: &str, value: Expr) -> Result<Expr, HError> {
-        self.0
-            .borrow_mut()
+        self.write()
             .as_mut()
-            .ok_or_else(|| HError::EnvironmentNotFound)?
+            .ok_or(HError::EnvironmentNotFound)?
             .set(key, value)
     }
 
-    pub fn def(&self, key: &str, value: Expr) {
-        self.0
-            .borrow_mut()
+    /// Brings the vars of the required module `name` accepted by `exported`
+    /// into this env, along with all of its methods. Vars left out are
+    /// reported as private when accessed from here.
+    pub fn import(
+        &self,
+        name: &str,
+        module: &EnvRef,
+        exported: impl Fn(&str) -> bool,
+    ) -> Result<(), HError> {
+        let module = module.read();
+        let module = module.as_ref().ok_or(HError::EnvironmentNotFound)?;
+        let mut env = self.write();
+        let env = env.as_mut().ok_or(HError::EnvironmentNotFound)?;
+        for (key, value) in &module.vars {
+            if exported(key) {
+                env.private.remove(key);
+                env.def(key, value.clone());
+            } else if !env.vars.contains_key(key) {
+                env.private.insert(key.to_string(), name.to_string());
+            }
+        }
+        for (key, methods) in &module.methods {
+            for method in methods {
+                env.defm(key, method.clone());
+            }
+        }
+        env.macros.extend(module.macros.clone());
+        Ok(())
+    }
+
+    /// The names of the vars visible from this env, including enclosing scopes.
+    pub fn names(&self) -> Result<BTreeSet<String>, HError> {
+        let env = self.read();
+        let env = env.as_ref().ok_or(HError::EnvironmentNotFound)?;
+        let mut names = match env.enclosing.is_some() {
+            true => env.enclosing.names()?,
+            false => BTreeSet::new(),
+        };
+        names.extend(env.vars.keys().cloned());
+        Ok(names)
+    }
+
+    /// The names of the methods visible from this env.
+    pub fn method_names(&self) -> Result<BTreeSet<String>, HError> {
+        let env = self.read();
+        let env = env.as_ref().ok_or(HError::EnvironmentNotFound)?;
+        let mut names = match env.enclosing.is_some() {
+            true => env.enclosing.method_names()?,
+            false => BTreeSet::new(),
+        };
+        names.extend(env.methods.keys().cloned());
+        Ok(names)
+    }
+
+    /// This env's own vars, excluding enclosing scopes.
+    pub fn locals(&self) -> Result<Vec<(String, Expr)>, HError> {
+        Ok(self
+            .read()
+            .as_ref()
+            .ok_or(HError::EnvironmentNotFound)?
+            .vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect())
+    }
+
+    pub fn snapshot(&self) -> Result<EnvSnapshot, HError> {
+        Ok(self
+            .read()
+            .as_ref()
+            .ok_or(HError::EnvironmentNotFound)?
+            .snapshot())
+    }
+
+    pub fn restore(&self, snapshot: EnvSnapshot) -> Result<(), HError> {
+        self.write()
+            .as_mut()
+            .ok_or(HError::EnvironmentNotFound)?
+            .restore(snapshot);
+        Ok(())
+    }
+
+    pub fn def(&self, key: &str, value: Expr) -> Result<(), HError> {
+        self.write()
             .as_mut()
-            .expect("Environment not found")
+            .ok_or(HError::EnvironmentNotFound)?
             .def(key, value);
+        Ok(())
+    }
+
+    /// Like `def`, but a new var counts against the active binding limit.
+    pub fn define(&self, key: &str, value: Expr) -> Result<(), HError> {
+        let mut env = self.write();
+        let env = env.as_mut().ok_or(HError::EnvironmentNotFound)?;
+        if !env.vars.contains_key(key) {
+            check_bindings(env.defined + 1)?;
+            env.defined += 1;
+        }
+        env.def(key, value);
+        Ok(())
     }
 
     pub fn defn(
@@ -82,20 +210,34 @@ impl EnvRef {
         name: &str,
         arity: Arity,
         fun: fn(args: &[Expr], env: EnvRef) -> Result<Expr, HError>,
-    ) {
-        self.0
-            .borrow_mut()
+    ) -> Result<(), HError> {
+        self.write()
             .as_mut()
-            .expect("Environment not found")
+            .ok_or(HError::EnvironmentNotFound)?
             .defn(name, arity, fun);
+        Ok(())
     }
 
-    pub fn defm(&self, name: &str, method: Method) {
-        self.0
-            .borrow_mut()
+    pub fn defm(&self, name: &str, method: Method) -> Result<Option<Method>, HError> {
+        Ok(self
+            .write()
             .as_mut()
-            .expect("Environment not found")
-            .defm(name, method);
+            .ok_or(HError::EnvironmentNotFound)?
+            .defm(name, method))
+    }
+
+    pub fn defmacro(&self, name: &str, expander: Expr) -> Result<(), HError> {
+        self.write()
+            .as_mut()
+            .ok_or(HError::EnvironmentNotFound)?
+            .defmacro(name, expander);
+        Ok(())
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -104,21 +246,41 @@ impl Env {
         Env {
             vars: BTreeMap::new(),
             methods: BTreeMap::new(),
+            macros: BTreeMap::new(),
             enclosing: EnvRef::nil(),
+            copy_on_write: false,
+            private: BTreeMap::new(),
+            _live: Live::new(Kind::Env),
+            defined: 0,
         }
     }
 
-    pub fn with_core_module() -> Env {
+    pub fn with_core_module() -> Result<Env, HError> {
         let mut env = Env::new();
-        env.merge_ref(core_module()).unwrap();
-        env
+        env.merge_ref(core_module()?)?;
+        Ok(env)
     }
 
     pub fn extend(env_ref: EnvRef) -> Env {
         Env {
             vars: BTreeMap::new(),
             methods: BTreeMap::new(),
+            macros: BTreeMap::new(),
             enclosing: env_ref,
+            copy_on_write: false,
+            private: BTreeMap::new(),
+            _live: Live::new(Kind::Env),
+            defined: 0,
+        }
+    }
+
+    /// Creates an isolated session on top of a shared `core`, e.g. one built
+    /// once with `core_module()`. The core is never modified: `set!` of a
+    /// core var and `defm` of a core method id act on session-local copies.
+    pub fn session(core: EnvRef) -> Env {
+        Env {
+            copy_on_write: true,
+            ..Env::extend(core)
         }
     }
 
@@ -135,46 +297,73 @@ impl Env {
         self.def(name, Expr::native_fn(name, arity, fun));
     }
 
-    pub fn defm(&mut self, name: &str, method: Method) {
-        self.methods
-            .entry(name.to_string())
-            .or_insert_with(Vec::new)
-            .push(method);
+    /// Adds a method, replacing any existing one with an identical selector.
+    /// Returns the replaced method, if any. The new definition always takes
+    /// precedence, as if it had been appended.
+    pub fn defm(&mut self, name: &str, method: Method) -> Option<Method> {
+        if self.copy_on_write && !self.methods.contains_key(name) {
+            if let Ok(shared) = self.enclosing.get_methods(name) {
+                self.methods.insert(name.to_string(), shared);
+            }
+        }
+        let methods = self.methods.entry(name.to_string()).or_default();
+        let replaced = methods
+            .iter()
+            .position(|m| m.selector == method.selector)
+            .map(|i| methods.remove(i));
+        methods.push(method);
+        replaced
+    }
+
+    /// Defines the macro `name`, whose `expander` is called with the
+    /// unevaluated arguments of each use and returns the code to run instead.
+    pub fn defmacro(&mut self, name: &str, expander: Expr) {
+        self.macros.insert(name.to_string(), expander);
     }
 
     pub fn merge(&mut self, env: Env) {
         self.vars.extend(env.vars.clone());
         self.methods.extend(env.methods.clone());
+        self.macros.extend(env.macros.clone());
     }
 
     pub fn merge_ref(&mut self, env: EnvRef) -> Result<(), HError> {
         self.vars.extend(
-            env.0
-                .borrow()
+            env.read()
                 .as_ref()
-                .ok_or_else(|| HError::EnvironmentNotFound)?
+                .ok_or(HError::EnvironmentNotFound)?
                 .vars
                 .clone(),
         );
         self.methods.extend(
-            env.0
-                .borrow()
+            env.read()
                 .as_ref()
-                .ok_or_else(|| HError::EnvironmentNotFound)?
+                .ok_or(HError::EnvironmentNotFound)?
                 .methods
                 .clone(),
         );
+        self.macros.extend(
+            env.read()
+                .as_ref()
+                .ok_or(HError::EnvironmentNotFound)?
+                .macros
+                .clone(),
+        );
         Ok(())
     }
 
     pub fn get(&self, key: &str) -> Result<Expr, HError> {
         let result = self.vars.get(key);
+        let unbound = |err| match self.private.get(key) {
+            Some(module) => HError::PrivateVar(key.to_string(), module.to_string()),
+            None => err,
+        };
         if result.is_none() && self.enclosing.is_some() {
-            return self.enclosing.get(key);
+            return self.enclosing.get(key).map_err(unbound);
         }
         match result {
             Some(value) => Ok(value.to_owned()),
-            _ => Err(HError::UnboundVar(key.to_string())),
+            _ => Err(unbound(HError::UnboundVar(key.to_string()))),
         }
     }
 
@@ -189,11 +378,25 @@ impl Env {
         }
     }
 
+    pub fn get_macro(&self, key: &str) -> Option<Expr> {
+        match self.macros.get(key) {
+            Some(expander) => Some(expander.clone()),
+            None => self.enclosing.get_macro(key),
+        }
+    }
+
     pub fn set(&mut self, key: &str, value: Expr) -> Result<Expr, HError> {
         if self.vars.contains_key(key) {
             self.vars
                 .insert(key.to_string(), value)
                 .ok_or_else(|| HError::UnboundVar(key.to_string()))
+        } else if self.copy_on_write {
+            let previous = self
+                .enclosing
+                .get(key)
+                .map_err(|_| HError::SetUninitializedVar(key.to_string()))?;
+            self.vars.insert(key.to_string(), value);
+            Ok(previous)
         } else if self.enclosing.is_some() {
             self.enclosing.set(key, value)
         } else {
@@ -201,6 +404,20 @@ impl Env {
         }
     }
 
+    pub fn snapshot(&self) -> EnvSnapshot {
+        EnvSnapshot {
+            vars: self.vars.clone(),
+            methods: self.methods.clone(),
+            macros: self.macros.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        self.vars = snapshot.vars;
+        self.methods = snapshot.methods;
+        self.macros = snapshot.macros;
+    }
+
     pub fn into_ref(self) -> EnvRef {
         EnvRef::new(self)
     }
@@ -250,6 +467,120 @@ mod tests {
         assert_eq!(env_ref.get("key").unwrap(), Expr::number(1.));
     }
 
+    #[test]
+    fn test_defm_replaces_identical_selectors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm number? [twice] (* this 2))
+            (defm true [twice] nil)
+            (defm number? [twice] (+ this this))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        let methods = env.get_methods("twice").unwrap();
+        assert_eq!(methods.len(), 2);
+        assert_eq!(*methods[1].selector, env.get("number?").unwrap());
+        assert_eq!(eval("<2 twice>", env.clone_ref()), Ok(Expr::number(4.)));
+    }
+
+    #[test]
+    fn test_mutating_a_nil_env_fails() {
+        let env = EnvRef::nil();
+
+        assert_eq!(env.def("a", Expr::Nil), Err(HError::EnvironmentNotFound));
+        assert_eq!(
+            env.defn("f", Arity::Count(0), |_, _| Ok(Expr::Nil)),
+            Err(HError::EnvironmentNotFound)
+        );
+        assert_eq!(env.define("a", Expr::Nil), Err(HError::EnvironmentNotFound));
+    }
+
+    #[test]
+    fn test_defm_returns_replaced_method() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        eval("(defm number? [twice] (* this 2))", env.clone_ref()).unwrap();
+        let original = env.get_methods("twice").unwrap().remove(0);
+
+        let mut replacement = original.clone();
+        replacement.function = vec![Expr::nil()].into();
+
+        assert_eq!(env.defm("twice", replacement.clone()), Ok(Some(original)));
+        replacement.selector = Box::new(Expr::boolean(true));
+        assert_eq!(env.defm("twice", replacement), Ok(None));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        eval("(var a 1)", env.clone_ref()).unwrap();
+
+        let snapshot = env.snapshot().unwrap();
+        eval(
+            "(set! a 2) (var b 3) (defm number? [twice] (* this 2))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(eval("<a twice>", env.clone_ref()), Ok(Expr::number(4.)));
+
+        env.restore(snapshot.clone()).unwrap();
+        assert_eq!(env.get("a"), Ok(Expr::number(1.)));
+        assert_eq!(env.get("b"), Err(HError::UnboundVar("b".to_string())));
+        assert_eq!(
+            env.get_methods("twice").map(|m| m.len()),
+            Err(HError::UnboundMethod("twice".to_string()))
+        );
+
+        // A snapshot can be restored more than once.
+        eval("(set! a 5)", env.clone_ref()).unwrap();
+        env.restore(snapshot).unwrap();
+        assert_eq!(env.get("a"), Ok(Expr::number(1.)));
+    }
+
+    #[test]
+    fn test_sessions_share_core_without_modifying_it() {
+        let core = core_module().unwrap();
+        let a = Env::session(core.clone_ref()).into_ref();
+        let b = Env::session(core.clone_ref()).into_ref();
+
+        eval(
+            "
+            (var only-a 1)
+            (set! *print-length* 2)
+            (defm true [+: n] :overridden)",
+            a.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(a.get("*print-length*"), Ok(Expr::number(2.)));
+        assert_eq!(b.get("*print-length*"), Ok(Expr::Nil));
+        assert_eq!(core.get("*print-length*"), Ok(Expr::Nil));
+        assert_eq!(
+            b.get("only-a"),
+            Err(HError::UnboundVar("only-a".to_string()))
+        );
+
+        assert_eq!(
+            eval("<1 + 2>", a.clone_ref()),
+            Ok(Expr::keyword(":overridden"))
+        );
+        assert_eq!(eval("<1 + 2>", b.clone_ref()), Ok(Expr::number(3.)));
+        // Methods copied into the session keep the core's other selectors.
+        assert_eq!(a.get_methods("+").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_session_set_of_unbound_var_fails() {
+        let session = Env::session(core_module().unwrap()).into_ref();
+
+        assert_eq!(
+            eval("(set! missing 1)", session),
+            Err(HError::SetUninitializedVar("missing".to_string()))
+        );
+    }
+
     #[test]
     fn test_can_extend_an_environment() {
         let mut env = Env::new();
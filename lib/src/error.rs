@@ -1,17 +1,259 @@
+use std::fmt;
+
 use crate::expr::{Arity, Expr};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum HError {
     UnboundVar(String),
+    PrivateVar(String, String), // Var, module that keeps it private
     UnboundMethod(String),
     UnexpectedForm(String, Expr),
     NotAFunction(String),
-    NotAMethod(String),
+    NotAMethod(String, Expr, Vec<Expr>), // Method id, receiver, selectors that rejected it
     InvalidEmptyList(String),
     SetUninitializedVar(String),
-    InvalidArity(String, Arity),
-    InvalidType(String, Expr), // Fn being called, violating Expr
+    ReservedName(String), // Special form a script tried to redefine
+    // Fn being called, args it accepts, args given
+    InvalidArity(String, Arity, usize),
+    InvalidType(String, Expr),          // Fn being called, violating Expr
+    TypeMismatch(String, String, Expr), // Parameter, its type hint, violating value
     ParseError(String),
     ScannerError(String),
+    ResourceLimit(String),
+    IndexOutOfBounds(String, Expr, usize), // Fn being called, index given, vector length
+    ModuleNotFound(String),                // Path that could not be read
+    CyclicRequire(Vec<String>), // Modules in the cycle, ending with the one required again
+    IoError(String),
+    TaskPending(Expr),                   // A task read before it finished
+    Nondeterministic(String),            // Native that can't run in deterministic mode
+    CassetteMismatch(String, Vec<Expr>), // Native and args with no matching recorded call
+    Interrupted,                         // A signal arrived with no handler trapping it
     EnvironmentNotFound,
+    EmptyCollection(String), // Fn that needs at least one element
+    UserError(Expr),         // Value passed to `throw`
+}
+
+impl HError {
+    /// A stable identifier for the kind of error, such as `E001` for
+    /// `UnboundVar`. Codes are never reused or renumbered, so new variants
+    /// take the next free one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            HError::UnboundVar(_) => "E001",
+            HError::PrivateVar(..) => "E002",
+            HError::UnboundMethod(_) => "E003",
+            HError::UnexpectedForm(..) => "E004",
+            HError::NotAFunction(_) => "E005",
+            HError::NotAMethod(..) => "E006",
+            HError::InvalidEmptyList(_) => "E007",
+            HError::SetUninitializedVar(_) => "E008",
+            HError::ReservedName(_) => "E009",
+            HError::InvalidArity(..) => "E010",
+            HError::InvalidType(..) => "E011",
+            HError::TypeMismatch(..) => "E012",
+            HError::ParseError(_) => "E013",
+            HError::ScannerError(_) => "E014",
+            HError::ResourceLimit(_) => "E015",
+            HError::IndexOutOfBounds(..) => "E016",
+            HError::ModuleNotFound(_) => "E017",
+            HError::CyclicRequire(_) => "E018",
+            HError::IoError(_) => "E019",
+            HError::TaskPending(_) => "E020",
+            HError::Nondeterministic(_) => "E021",
+            HError::CassetteMismatch(..) => "E022",
+            HError::Interrupted => "E023",
+            HError::EnvironmentNotFound => "E024",
+            HError::EmptyCollection(_) => "E025",
+            HError::UserError(_) => "E026",
+        }
+    }
+
+    /// The error as a value for scripts: what was thrown for a `UserError`,
+    /// or else a map of its `:code` and `:message`.
+    pub fn value(&self) -> Expr {
+        match self {
+            HError::UserError(value) => value.clone(),
+            err => Expr::map(&[
+                (Expr::keyword(":code"), Expr::string(err.code())),
+                (Expr::keyword(":message"), Expr::String(err.to_string())),
+            ]),
+        }
+    }
+}
+
+impl HError {
+    /// The name of the variant, such as `UnboundVar`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HError::UnboundVar(_) => "UnboundVar",
+            HError::PrivateVar(..) => "PrivateVar",
+            HError::UnboundMethod(_) => "UnboundMethod",
+            HError::UnexpectedForm(..) => "UnexpectedForm",
+            HError::NotAFunction(_) => "NotAFunction",
+            HError::NotAMethod(..) => "NotAMethod",
+            HError::InvalidEmptyList(_) => "InvalidEmptyList",
+            HError::SetUninitializedVar(_) => "SetUninitializedVar",
+            HError::ReservedName(_) => "ReservedName",
+            HError::InvalidArity(..) => "InvalidArity",
+            HError::InvalidType(..) => "InvalidType",
+            HError::TypeMismatch(..) => "TypeMismatch",
+            HError::ParseError(_) => "ParseError",
+            HError::ScannerError(_) => "ScannerError",
+            HError::ResourceLimit(_) => "ResourceLimit",
+            HError::IndexOutOfBounds(..) => "IndexOutOfBounds",
+            HError::ModuleNotFound(_) => "ModuleNotFound",
+            HError::CyclicRequire(_) => "CyclicRequire",
+            HError::IoError(_) => "IoError",
+            HError::TaskPending(_) => "TaskPending",
+            HError::Nondeterministic(_) => "Nondeterministic",
+            HError::CassetteMismatch(..) => "CassetteMismatch",
+            HError::Interrupted => "Interrupted",
+            HError::EnvironmentNotFound => "EnvironmentNotFound",
+            HError::EmptyCollection(_) => "EmptyCollection",
+            HError::UserError(_) => "UserError",
+        }
+    }
+
+    /// A description of the error for users, without its code.
+    pub fn message(&self) -> String {
+        match self {
+            HError::UnboundVar(name) => format!("Unbound var '{}'", name),
+            HError::PrivateVar(name, module) => {
+                format!("'{}' is private to module '{}'", name, module)
+            }
+            HError::UnboundMethod(id) => format!("No method '{}'", id),
+            HError::UnexpectedForm(message, form) => format!("{}: {}", message, form),
+            HError::NotAFunction(value) => format!("'{}' is not a function", value),
+            HError::NotAMethod(id, receiver, _) => {
+                format!("No '{}' method accepts {}", id, receiver)
+            }
+            HError::SetUninitializedVar(name) => {
+                format!("Can't set '{}' before it is defined", name)
+            }
+            HError::ReservedName(name) => {
+                format!("'{}' is a special form and can't be redefined", name)
+            }
+            HError::InvalidArity(name, arity, given) => {
+                let expected = match arity {
+                    Arity::Count(count) => plural(*count, "argument"),
+                    Arity::Range(min, usize::MAX) => {
+                        format!("at least {}", plural(*min, "argument"))
+                    }
+                    Arity::Range(min, max) => format!("{} to {} arguments", min, max),
+                };
+                format!(
+                    "Arity mismatch calling '{}': expected {}, got {}",
+                    name, expected, given
+                )
+            }
+            HError::InvalidType(name, value) => {
+                format!("Invalid argument to '{}': {}", name, value)
+            }
+            HError::TypeMismatch(param, hint, value) => {
+                format!("Expected '{}' to be {}, got {}", param, hint, value)
+            }
+            HError::InvalidEmptyList(message)
+            | HError::ParseError(message)
+            | HError::ScannerError(message)
+            | HError::ResourceLimit(message)
+            | HError::IoError(message) => message.to_string(),
+            HError::IndexOutOfBounds(name, index, len) => format!(
+                "Index {} passed to '{}' is out of bounds for length {}",
+                index, name, len
+            ),
+            HError::ModuleNotFound(path) => format!("Module not found: {}", path),
+            HError::CyclicRequire(modules) => {
+                format!("Cyclic require: {}", modules.join(" -> "))
+            }
+            HError::TaskPending(task) => format!("{} was read before it finished", task),
+            HError::Nondeterministic(name) => {
+                format!("'{}' can't run in deterministic mode", name)
+            }
+            HError::CassetteMismatch(name, args) => format!(
+                "No recorded call to '{}' with {}",
+                name,
+                Expr::Vector(args.clone())
+            ),
+            HError::Interrupted => "Interrupted".to_string(),
+            HError::EnvironmentNotFound => "Environment not found".to_string(),
+            HError::EmptyCollection(name) => {
+                format!("'{}' requires at least one element", name)
+            }
+            HError::UserError(value) => format!("Uncaught error: {}", value),
+        }
+    }
+
+    /// The value or form the error is about, if there is one.
+    pub fn expr(&self) -> Option<&Expr> {
+        match self {
+            HError::UnexpectedForm(_, expr)
+            | HError::NotAMethod(_, expr, _)
+            | HError::InvalidType(_, expr)
+            | HError::TypeMismatch(_, _, expr)
+            | HError::IndexOutOfBounds(_, expr, _)
+            | HError::TaskPending(expr)
+            | HError::UserError(expr) => Some(expr),
+            _ => None,
+        }
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        count => format!("{} {}s", count, noun),
+    }
+}
+
+/// Prefixes the message with the error's code, e.g. `E001: Unbound var 'x'`.
+impl fmt::Display for HError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for HError {}
+
+/// The code at the start of an error message, as printed by `Display`.
+pub fn code_of(message: &str) -> Option<&str> {
+    let (code, _) = message.split_once(": ")?;
+    let digits = code.strip_prefix('E')?;
+    (digits.len() == 3 && digits.chars().all(|c| c.is_ascii_digit())).then_some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_displays_code() {
+        let err = HError::UnboundVar("x".to_string());
+
+        assert_eq!(err.to_string(), "E001: Unbound var 'x'");
+        assert_eq!(code_of(&err.to_string()), Some("E001"));
+        assert_eq!(HError::EnvironmentNotFound.code(), "E024");
+    }
+
+    #[test]
+    fn test_describes_errors() {
+        let err = HError::InvalidArity("+".to_string(), Arity::Range(1, usize::MAX), 0);
+
+        assert_eq!(
+            err.message(),
+            "Arity mismatch calling '+': expected at least 1 argument, got 0"
+        );
+        assert_eq!(err.kind(), "InvalidArity");
+        assert_eq!(err.expr(), None);
+
+        let err = HError::InvalidType("/".to_string(), Expr::keyword(":two"));
+        assert_eq!(err.message(), "Invalid argument to '/': :two");
+        assert_eq!(err.expr(), Some(&Expr::keyword(":two")));
+    }
+
+    #[test]
+    fn test_code_of_ignores_other_messages() {
+        assert_eq!(code_of("oops"), None);
+        assert_eq!(code_of("Error: oops"), None);
+        assert_eq!(code_of("E12: oops"), None);
+    }
 }
@@ -1,11 +1,18 @@
-use std::collections::BTreeMap;
+use std::{cell::RefCell, collections::BTreeMap};
 
 use crate::{
+    coverage,
     env::{Env, EnvRef},
     error::HError,
-    expr::{Arity, Expr, Fn, Method, NativeFn},
-    modules::utils::{is_truthy, method_args, method_id},
+    expr::{Arity, Closure, Expr, Fn, Method, NativeFn},
+    interpreter::{break_on_error, check_collection_len, enter_call, tick},
+    modules::{
+        pattern::select_arm,
+        special_forms::{bind_pattern, let_binding, let_bindings},
+        utils::{hint_matches, is_truthy, method_args, method_id},
+    },
     parser::parse,
+    trace,
 };
 
 pub trait Callable {
@@ -13,12 +20,35 @@ pub trait Callable {
     fn arity(&self) -> &Arity;
 }
 
+thread_local! {
+    // Name of the file being evaluated, if known.
+    static SOURCE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
 pub fn eval(input: &str, env: EnvRef) -> Result<Expr, HError> {
     let exprs = parse(input)?;
     eval_exprs(&exprs, env)
 }
 
-pub fn eval_exprs(exprs: &Vec<Expr>, env: EnvRef) -> Result<Expr, HError> {
+/// Evaluates `input` as the contents of the file `name`, which definitions
+/// made while evaluating it are attributed to.
+pub fn eval_source(input: &str, name: &str, env: EnvRef) -> Result<Expr, HError> {
+    with_source(name, || eval(input, env)).inspect_err(|err| trace::locate(input, name, err))
+}
+
+/// Runs `f`, attributing definitions it makes to the file `name`.
+pub(crate) fn with_source<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let outer = SOURCE.with(|source| source.replace(Some(name.to_string())));
+    let result = f();
+    SOURCE.with(|source| source.replace(outer));
+    result
+}
+
+pub(crate) fn current_source() -> Option<String> {
+    SOURCE.with(|source| source.borrow().clone())
+}
+
+pub fn eval_exprs(exprs: &[Expr], env: EnvRef) -> Result<Expr, HError> {
     let mut result = Expr::Nil;
     for expr in exprs {
         result = eval_expr(expr, env.clone_ref())?;
@@ -27,6 +57,7 @@ pub fn eval_exprs(exprs: &Vec<Expr>, env: EnvRef) -> Result<Expr, HError> {
 }
 
 pub fn eval_expr(expr: &Expr, env: EnvRef) -> Result<Expr, HError> {
+    tick()?;
     match expr {
         Expr::List(list) => {
             if list.is_empty() {
@@ -34,13 +65,16 @@ pub fn eval_expr(expr: &Expr, env: EnvRef) -> Result<Expr, HError> {
                     "An empty list is invalid. Should this be a function call?".to_string(),
                 ));
             }
+            coverage::record(expr);
             let (f, args) = list.split_first().unwrap();
-            let function = resolve(f, env.clone_ref())?;
-            match function {
-                Expr::NativeFn(callable) => callable.call(args, env, None),
-                Expr::Fn(callable) => callable.call(args, env, None),
-                value => Err(HError::NotAFunction(format!("{}", value))),
-            }
+            let function = match resolve(f, env.clone_ref()) {
+                Ok(function) => function,
+                Err(err) => match expand(f, args, &env)? {
+                    Some(expansion) => return eval_expr(&expansion, env),
+                    None => return Err(trace::record_form(expr, err)),
+                },
+            };
+            call(&function, args, env).map_err(|err| trace::record_form(expr, err))
         }
         Expr::MethodList(list) => {
             if list.is_empty() {
@@ -48,45 +82,74 @@ pub fn eval_expr(expr: &Expr, env: EnvRef) -> Result<Expr, HError> {
                     "An empty method list is invalid. Should this be a method call?".to_string(),
                 ));
             }
+            coverage::record(expr);
             let (this, raw_args) = list.split_first().unwrap();
             let id = method_id(raw_args);
             let args = method_args(raw_args);
-            let function = get_first_method_matching(&id, this, env.clone_ref())?;
+            // Evaluate the receiver once, rather than once per selector tried.
+            let this = eval_expr(this, env.clone_ref())?;
+            let function = match get_first_method_matching(&id, &this, env.clone_ref()) {
+                Ok(Some(method)) => Some((method, args)),
+                Ok(None) | Err(HError::UnboundMethod(_)) => {
+                    get_variadic_method(raw_args, &this, env.clone_ref())?
+                }
+                Err(err) => return Err(err),
+            };
             match function {
-                Some(method) => method.call(&args, env, Some(this)),
-                _ => Err(HError::NotAMethod(id)),
+                Some((method, args)) => method
+                    .call(&args, env, Some(&this))
+                    .map_err(|err| trace::record_form(expr, err)),
+                _ => {
+                    let selectors = env
+                        .get_methods(&id)?
+                        .iter()
+                        .map(|m| (*m.selector).clone())
+                        .collect();
+                    Err(HError::NotAMethod(id, this, selectors))
+                }
             }
         }
         Expr::Symbol(value) => match env.get(value) {
             Ok(expr) => Ok(expr.to_owned()),
+            Err(err @ HError::PrivateVar(..)) => Err(err),
             _ => Err(HError::UnboundVar(value.to_string())),
         },
-        Expr::Map(map) => {
-            let resolved_map: BTreeMap<Expr, Expr> = map
-                .into_iter()
-                .map(|(key, value)| {
-                    Ok((
-                        eval_expr(&key, env.clone_ref())?,
-                        eval_expr(&value, env.clone_ref())?,
-                    ))
-                })
-                .collect::<Result<_, _>>()?;
-            Ok(Expr::Map(resolved_map))
-        }
-        Expr::Vector(vector) => {
-            let resolved_vector: Vec<Expr> = vector
-                .into_iter()
-                .map(|value| eval_expr(&value, env.clone_ref()))
-                .collect::<Result<_, _>>()?;
-            Ok(Expr::Vector(resolved_vector))
-        }
+        Expr::Map(map) => eval_map(map, env),
+        Expr::Vector(vector) => eval_vector(vector, env),
         _ => Ok(expr.to_owned()),
     }
 }
 
+// Collection literals are evaluated outside `eval_expr` to keep its stack
+// frame, which every level of recursion pays for, small.
+fn eval_map(map: &BTreeMap<Expr, Expr>, env: EnvRef) -> Result<Expr, HError> {
+    check_collection_len(map.len())?;
+    let resolved_map: BTreeMap<Expr, Expr> = map
+        .iter()
+        .map(|(key, value)| {
+            Ok((
+                eval_expr(key, env.clone_ref())?,
+                eval_expr(value, env.clone_ref())?,
+            ))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Expr::Map(resolved_map))
+}
+
+fn eval_vector(vector: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    check_collection_len(vector.len())?;
+    let resolved_vector: Vec<Expr> = vector
+        .iter()
+        .map(|value| eval_expr(value, env.clone_ref()))
+        .collect::<Result<_, _>>()?;
+    Ok(Expr::Vector(resolved_vector))
+}
+
 pub fn resolve(expr: &Expr, env: EnvRef) -> Result<Expr, HError> {
     match expr {
-        Expr::Symbol(id) => env.get(&id),
+        Expr::Symbol(id) => env.get(id),
+        // Keywords evaluate to themselves, and can be called to look them up.
+        Expr::Keyword(_) => Ok(expr.to_owned()),
         _ => Err(HError::UnexpectedForm(
             "Only symbols are resolvable".to_string(),
             expr.to_owned(),
@@ -94,8 +157,77 @@ pub fn resolve(expr: &Expr, env: EnvRef) -> Result<Expr, HError> {
     }
 }
 
+/// Calls `function` with unevaluated `args`, as if it were the head of a list.
+pub fn call(function: &Expr, args: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    match function {
+        Expr::NativeFn(callable) => callable.call(args, env, None),
+        Expr::Fn(callable) => callable.call(args, env, None),
+        Expr::Closure(callable) => callable.call(args, env, None),
+        Expr::Keyword(_) => lookup(function, args, env),
+        value => Err(HError::NotAFunction(format!("{}", value))),
+    }
+}
+
+/// Calls a keyword, looking it up in the map it is given, as in `(:name
+/// person)`. A missing key, or a nil map, returns the default given after the
+/// map, or nil.
+fn lookup(keyword: &Expr, args: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    Arity::Range(1, 2).check(&keyword.to_string(), args)?;
+    let resolved = resolve_args(args, env)?;
+    let default = resolved.get(1).cloned().unwrap_or(Expr::Nil);
+    match &resolved[0] {
+        Expr::Map(map) => Ok(map.get(keyword).cloned().unwrap_or(default)),
+        Expr::Nil => Ok(default),
+        invalid => Err(HError::InvalidType(keyword.to_string(), invalid.clone())),
+    }
+}
+
+/// Calls `function` with evaluated `values`, quoting them so they aren't
+/// evaluated again.
+pub fn apply(function: &Expr, values: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    let args: Vec<Expr> = values.iter().cloned().map(quoted).collect();
+    call(function, &args, env)
+}
+
+/// An expression that evaluates to `value`.
+pub fn quoted(value: Expr) -> Expr {
+    match value {
+        Expr::List(_) | Expr::MethodList(_) | Expr::Symbol(_) | Expr::Vector(_) | Expr::Map(_) => {
+            Expr::List(vec![Expr::symbol("quote"), value])
+        }
+        value => value,
+    }
+}
+
+/// Expands `form` once if it is a call to a macro.
+pub fn macroexpand_1(form: &Expr, env: &EnvRef) -> Result<Option<Expr>, HError> {
+    match form {
+        Expr::List(list) if !list.is_empty() => {
+            let (f, args) = list.split_first().unwrap();
+            match resolve(f, env.clone_ref()) {
+                Ok(_) => Ok(None),
+                Err(_) => expand(f, args, env),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Calls the macro named by `head`, if there is one, with the unevaluated
+/// `args` of a call. A var of the same name hides the macro, so this is only
+/// tried once `head` fails to resolve.
+fn expand(head: &Expr, args: &[Expr], env: &EnvRef) -> Result<Option<Expr>, HError> {
+    match head {
+        Expr::Symbol(id) => match env.get_macro(id) {
+            Some(expander) => apply(&expander, args, env.clone_ref()).map(Some),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
 pub fn resolve_args(args: &[Expr], env: EnvRef) -> Result<Vec<Expr>, HError> {
-    let mut result = Vec::new();
+    let mut result = Vec::with_capacity(args.len());
     for expr in args.iter() {
         result.push(eval_expr(expr, env.clone_ref())?);
     }
@@ -110,28 +242,43 @@ pub fn get_first_method_matching(
     let methods = env.get_methods(id)?;
     // Evaluate selectors in reverse order to ensure the most recently defined method takes precedence.
     for m in methods.iter().rev() {
-        match &*m.selector {
-            Expr::NativeFn(fun) => {
-                if is_truthy(&fun.call(&[this.clone()], env.clone_ref(), None)?) {
-                    return Ok(Some(m.clone()));
-                }
-            }
-            Expr::Fn(fun) => {
-                if is_truthy(&fun.call(&[this.clone()], env.clone_ref(), None)?) {
-                    return Ok(Some(m.clone()));
-                }
-            }
-            expr => {
-                if is_truthy(&expr) {
-                    return Ok(Some(m.clone()));
-                }
+        let selected = match &*m.selector {
+            selector @ (Expr::NativeFn(_) | Expr::Fn(_) | Expr::Closure(_)) => {
+                apply(selector, std::slice::from_ref(this), env.clone_ref())?
             }
+            expr => expr.clone(),
+        };
+        if is_truthy(&selected) {
+            return Ok(Some(m.clone()));
         }
     }
 
     Ok(None)
 }
 
+/// Finds a method whose last keyword takes the rest of the arguments, such
+/// as `[partial: & args]` for `<f partial: 1 2>`, returning it with the
+/// arguments to call it with. Longer names are tried first.
+fn get_variadic_method(
+    raw_args: &[Expr],
+    this: &Expr,
+    env: EnvRef,
+) -> Result<Option<(Method, Vec<Expr>)>, HError> {
+    for end in (0..raw_args.len()).step_by(2).rev() {
+        let id = format!("{} &", method_id(&raw_args[..=end]));
+        match get_first_method_matching(&id, this, env.clone_ref()) {
+            Ok(Some(method)) => {
+                let mut args = method_args(&raw_args[..end]);
+                args.extend_from_slice(&raw_args[end + 1..]);
+                return Ok(Some((method, args)));
+            }
+            Ok(None) | Err(HError::UnboundMethod(_)) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
 impl Callable for NativeFn {
     fn arity(&self) -> &Arity {
         &self.arity
@@ -143,32 +290,211 @@ impl Callable for NativeFn {
     }
 }
 
-impl Callable for Fn {
+impl Callable for Closure {
     fn arity(&self) -> &Arity {
         &self.arity
     }
 
     fn call(&self, args: &[Expr], env: EnvRef, _: Option<&Expr>) -> Result<Expr, HError> {
         self.arity.check(&self.id, args)?;
-        let mut arg_env = Env::extend(env.clone_ref());
+        let resolved = resolve_args(args, env.clone_ref())?;
+        (self.function)(&resolved, env)
+    }
+}
+
+impl Callable for Fn {
+    fn arity(&self) -> &Arity {
+        &self.arity
+    }
+
+    fn call(&self, args: &[Expr], env: EnvRef, _: Option<&Expr>) -> Result<Expr, HError> {
+        let _call = enter_call()?;
+        let mut frame = self.bind(args, env)?;
+        // A call in tail position replaces this one's frame instead of nesting
+        // a new call, so tail recursion runs in constant stack space.
+        let mut callee: Option<Fn> = None;
+        loop {
+            let current = callee.as_ref().unwrap_or(self);
+            let (fun, args, env) = match eval_body(&current.function, frame.clone_ref()) {
+                Ok(Tail::Value(value)) => return Ok(value),
+                Ok(Tail::Call(fun, args, env)) => (fun, args, env),
+                Err(err) => return break_on_error(current.traced(err), frame),
+            };
+            frame = match fun.bind(&args, env) {
+                Ok(next) => next,
+                Err(err) => return break_on_error(current.traced(err), frame),
+            };
+            callee = Some(fun);
+        }
+    }
+}
+
+impl Fn {
+    /// Records that `err` unwound out of a call to this function.
+    fn traced(&self, err: HError) -> HError {
+        trace::record_call(self.name.as_deref().unwrap_or("fn"), &self.source, err)
+    }
+
+    /// Creates the frame for a call with unevaluated `args`, which are
+    /// evaluated in the caller's `env`. The body runs in the defining scope.
+    fn bind(&self, args: &[Expr], env: EnvRef) -> Result<EnvRef, HError> {
+        self.arity
+            .check(self.name.as_deref().unwrap_or("fn"), args)?;
+        let mut arg_env = Env::extend(self.closure.clone_ref());
+        let check_types = self.hints.iter().any(Option::is_some)
+            && env
+                .get("*check-types*")
+                .is_ok_and(|check| is_truthy(&check));
+        let check = |i: usize, value: Expr| -> Result<Expr, HError> {
+            match &self.hints[i] {
+                Some(hint) if check_types && hint_matches(hint, &value) == Some(false) => Err(
+                    HError::TypeMismatch(self.args[i].id(), hint.to_string(), value),
+                ),
+                _ => Ok(value),
+            }
+        };
         for (i, binding) in self.args.iter().enumerate() {
             match binding {
-                Expr::Symbol(ref name) => {
-                    arg_env.def(name, eval_expr(&args[i], env.clone_ref())?.clone())
-                }
                 Expr::Ampersand => {
-                    arg_env.def(&self.args[i + 1].id(), Expr::Vector(args[i..].to_vec()));
+                    let rest = resolve_args(&args[i..], env.clone_ref())?;
+                    bind_param(
+                        &mut arg_env,
+                        &self.args[i + 1],
+                        check(i + 1, Expr::Vector(rest))?,
+                    )?;
                     break;
                 }
-                _ => {
-                    return Err(HError::UnexpectedForm(
-                        "Expected a symbol argument".to_string(),
-                        self.args[i].clone(),
-                    ))
+                _ => bind_param(
+                    &mut arg_env,
+                    binding,
+                    check(i, eval_expr(&args[i], env.clone_ref())?)?,
+                )?,
+            }
+        }
+        Ok(arg_env.into_ref())
+    }
+}
+
+/// Binds a parameter, which is a symbol or a vector or map pattern taking
+/// `value` apart.
+fn bind_param(env: &mut Env, param: &Expr, value: Expr) -> Result<(), HError> {
+    match param {
+        Expr::Symbol(name) => env.def(name, value),
+        Expr::Vector(_) | Expr::Map(_) => {
+            let mut bindings = vec![];
+            bind_pattern(param, value, &mut bindings)?;
+            for (name, value) in bindings {
+                env.def(&name, value);
+            }
+        }
+        _ => {
+            return Err(HError::UnexpectedForm(
+                "Expected a symbol argument".to_string(),
+                param.clone(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// The result of a function body: a value, or a call left in tail position
+/// for the caller to make.
+enum Tail {
+    Value(Expr),
+    Call(Fn, Vec<Expr>, EnvRef), // Callee, unevaluated args, env to evaluate them in
+}
+
+fn eval_body(body: &[Expr], env: EnvRef) -> Result<Tail, HError> {
+    match body.split_last() {
+        Some((last, init)) => {
+            eval_exprs(init, env.clone_ref())?;
+            eval_tail(last, env)
+        }
+        None => Ok(Tail::Value(Expr::Nil)),
+    }
+}
+
+/// Evaluates `expr` in tail position. Calls to `Fn`s are returned rather than
+/// made, looking through the branches of `if`, `if-let`, and `when-let`, the
+/// bodies of `let`, the arms of `match`, the last operand of `and` and `or`,
+/// and macro expansions.
+fn eval_tail(expr: &Expr, env: EnvRef) -> Result<Tail, HError> {
+    match expr {
+        Expr::List(list) if !list.is_empty() => {
+            eval_tail_call(expr, list, env).map_err(|err| trace::record_form(expr, err))
+        }
+        _ => eval_expr(expr, env).map(Tail::Value),
+    }
+}
+
+fn eval_tail_call(expr: &Expr, list: &[Expr], env: EnvRef) -> Result<Tail, HError> {
+    let (f, args) = list.split_first().unwrap();
+    tick()?;
+    coverage::record(expr);
+    let function = match resolve(f, env.clone_ref()) {
+        Ok(function) => function,
+        Err(err) => match expand(f, args, &env)? {
+            Some(expansion) => return eval_tail(&expansion, env),
+            None => return Err(err),
+        },
+    };
+    let native = match function {
+        Expr::Fn(fun) => return Ok(Tail::Call(fun, args.to_vec(), env)),
+        Expr::NativeFn(native) => native,
+        function => return call(&function, args, env).map(Tail::Value),
+    };
+    match native.id.as_str() {
+        "if" => {
+            native.arity.check(&native.id, args)?;
+            if is_truthy(&eval_expr(&args[0], env.clone_ref())?) {
+                eval_tail(&args[1], env)
+            } else if args.len() == 3 {
+                eval_tail(&args[2], env)
+            } else {
+                Ok(Tail::Value(Expr::Nil))
+            }
+        }
+        "if-let" | "when-let" => {
+            native.arity.check(&native.id, args)?;
+            let (name, value) = let_binding(&native.id, &args[0])?;
+            let value = eval_expr(value, env.clone_ref())?;
+            if is_truthy(&value) {
+                let let_env = Env::extend(env).into_ref();
+                let_env.def(name, value)?;
+                match native.id.as_str() {
+                    "if-let" => eval_tail(&args[1], let_env),
+                    _ => eval_body(&args[1..], let_env),
+                }
+            } else if native.id == "if-let" && args.len() == 3 {
+                eval_tail(&args[2], env)
+            } else {
+                Ok(Tail::Value(Expr::Nil))
+            }
+        }
+        "and" | "or" => {
+            let Some((last, init)) = args.split_last() else {
+                return native.call(args, env, None).map(Tail::Value);
+            };
+            for arg in init {
+                let value = eval_expr(arg, env.clone_ref())?;
+                if is_truthy(&value) != (native.id == "and") {
+                    return Ok(Tail::Value(value));
                 }
             }
+            eval_tail(last, env)
+        }
+        "let" => {
+            native.arity.check(&native.id, args)?;
+            eval_body(&args[1..], let_bindings(&args[0], env)?)
+        }
+        "match" => {
+            native.arity.check(&native.id, args)?;
+            match select_arm(args, env)? {
+                Some((body, arm_env)) => eval_tail(body, arm_env),
+                None => Ok(Tail::Value(Expr::Nil)),
+            }
         }
-        eval_exprs(&self.function, arg_env.into_ref())
+        _ => native.call(args, env, None).map(Tail::Value),
     }
 }
 
@@ -179,18 +505,26 @@ impl Callable for Method {
 
     fn call(&self, args: &[Expr], env: EnvRef, this: Option<&Expr>) -> Result<Expr, HError> {
         self.arity.check(&self.id, args)?;
-        let mut arg_env = Env::extend(env.clone_ref());
+        let _call = enter_call()?;
+        let mut arg_env = Env::extend(self.closure.clone_ref());
         match this {
-            Some(expr) => arg_env.def("this", eval_expr(&expr, env.clone_ref())?.clone()),
+            // The receiver has already been evaluated.
+            Some(this) => arg_env.def("this", this.clone()),
             None => {
                 return Err(HError::UnboundVar("No 'this' set for method".to_string()));
             }
         }
         for (i, binding) in self.args.iter().enumerate() {
             match binding {
-                Expr::Symbol(ref name) => {
-                    arg_env.def(name, eval_expr(&args[i], env.clone_ref())?.clone())
+                // Rest arguments, bound to the symbol after the `&`.
+                Expr::Ampersand => {
+                    if let Some(Expr::Symbol(name)) = self.args.get(i + 1) {
+                        let rest = resolve_args(&args[i..], env.clone_ref())?;
+                        arg_env.def(name, Expr::Vector(rest));
+                    }
+                    break;
                 }
+                Expr::Symbol(ref name) => arg_env.def(name, eval_expr(&args[i], env.clone_ref())?),
                 _ => {
                     return Err(HError::UnexpectedForm(
                         "Expected a symbol argument".to_string(),
@@ -199,7 +533,9 @@ impl Callable for Method {
                 }
             }
         }
-        eval_exprs(&self.function, arg_env.into_ref())
+        let frame = arg_env.into_ref();
+        eval_exprs(&self.function, frame.clone_ref())
+            .or_else(|err| break_on_error(trace::record_call(&self.id, &self.source, err), frame))
     }
 }
 
@@ -209,7 +545,7 @@ mod tests {
 
     #[test]
     fn test_calls_native_callable() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(+ 1 (/ (* 3 (- 5 2)) 3))", env).unwrap(),
@@ -219,7 +555,7 @@ mod tests {
 
     #[test]
     fn test_calls_fn() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var f (fn [a b] (+ a b)))", env.clone_ref()).unwrap();
 
@@ -228,7 +564,7 @@ mod tests {
 
     #[test]
     fn test_calls_method() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(defm true [+: n] (+ this n))", env.clone_ref()).unwrap();
 
@@ -237,7 +573,7 @@ mod tests {
 
     #[test]
     fn test_calls_most_recent_method_definition() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval(
             "
@@ -252,7 +588,7 @@ mod tests {
 
     #[test]
     fn test_calls_method_with_no_args() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(defm true [add-one] (+ this 1))", env.clone_ref()).unwrap();
 
@@ -261,7 +597,7 @@ mod tests {
 
     #[test]
     fn test_calls_method_with_multiple_args() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval(
             "(defm true [add: a divide-by: b] (/ (+ this a) b))",
@@ -277,7 +613,7 @@ mod tests {
 
     #[test]
     fn test_calls_variadic_fn() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var f (fn [a &b] [a b]))", env.clone_ref()).unwrap();
 
@@ -302,24 +638,226 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_destructures_fn_params() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(defn describe [[x y] {:name n :tags [tag]} & [more]] [x y n tag more])",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval(
+                "(describe [1 2] {:name \"a\" :tags [:t]} 3 4)",
+                env.clone_ref()
+            ),
+            eval("[1 2 \"a\" :t 3]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(describe [1] {} )", env.clone_ref()),
+            eval("[1 nil nil nil nil]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(describe 1 {})", env),
+            Err(HError::InvalidType(
+                "destructure".to_string(),
+                Expr::number(1.)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_reports_rejecting_selectors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(defm number? [add-one] (+ this 1))", env.clone_ref()).unwrap();
+
+        match eval("<\"a\" add-one>", env.clone_ref()) {
+            Err(HError::NotAMethod(id, this, selectors)) => {
+                assert_eq!(id, "add-one");
+                assert_eq!(this, Expr::string("a"));
+                assert_eq!(selectors, vec![env.get("number?").unwrap()]);
+            }
+            result => panic!("Expected NotAMethod, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_evaluates_method_receiver_once() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var calls (atom 0))
+            (defm number? [twice] (* this 2))
+            (defm false [twice] nil)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("<(swap! calls + 1) twice>", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+        assert_eq!(eval("(deref calls)", env.clone_ref()), Ok(Expr::number(1.)));
+    }
+
+    #[test]
+    fn test_evaluates_variadic_args() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var f (fn [a &b] b))", env.clone_ref()).unwrap();
+
+        assert_eq!(
+            eval("(f 1 (+ 1 1) (+ 1 2))", env.clone_ref()),
+            Ok(Expr::vector(&[Expr::number(2.), Expr::number(3.)]))
+        );
+    }
+
+    #[test]
+    fn test_fn_body_uses_closure_scope() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defn make-counter []
+                (var count 0)
+                (fn [] (set! count (+ count 1)) count))
+            (var counter (make-counter))
+            (counter)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(counter)", env.clone_ref()), Ok(Expr::number(2.)));
+        assert_eq!(
+            eval("count", env.clone_ref()),
+            Err(HError::UnboundVar("count".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_method_returns_fn_capturing_this() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm number? [adder] (fn [n] (+ this n)))
+            (var add-5 <5 adder>)
+            (var add-1 <1 adder>)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(add-5 2)", env.clone_ref()), Ok(Expr::number(7.)));
+        assert_eq!(eval("(add-1 2)", env.clone_ref()), Ok(Expr::number(3.)));
+    }
+
+    #[test]
+    fn test_method_callbacks_see_this_when_called_later() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm number? [on-call: f] (fn [] (f this)))
+            (var callback <3 on-call: (fn [n] (* n 10))>)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(callback)", env.clone_ref()), Ok(Expr::number(30.)));
+    }
+
+    #[test]
+    fn test_method_receivers_are_evaluated_once() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm true [show] this)
+            (defm (fn [v] (= v 'bar)) [bar?] true)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("<'foo show>", env.clone_ref()),
+            Ok(Expr::symbol("foo"))
+        );
+        assert_eq!(
+            eval("<'(1 2) show>", env.clone_ref()),
+            Ok(Expr::list(&[Expr::number(1.), Expr::number(2.)]))
+        );
+        assert_eq!(eval("<'bar bar?>", env), Ok(Expr::boolean(true)));
+    }
+
     #[test]
     fn test_checks_arity() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(var a 3 4)", env.clone_ref()),
-            Err(HError::InvalidArity("var".to_string(), Arity::Range(1, 2)))
+            Err(HError::InvalidArity("var".to_string(), Arity::Count(2), 3))
         );
 
         assert_eq!(
             eval("(set! a)", env.clone_ref()),
-            Err(HError::InvalidArity("set!".to_string(), Arity::Count(2)))
+            Err(HError::InvalidArity("set!".to_string(), Arity::Count(2), 1))
+        );
+
+        assert_eq!(
+            eval("(defn f [a] a) (f)", env.clone_ref())
+                .unwrap_err()
+                .to_string(),
+            "E010: Arity mismatch calling 'f': expected 1 argument, got 0"
+        );
+        assert_eq!(
+            eval("(var g (fn [a b] a)) (g 1)", env)
+                .unwrap_err()
+                .to_string(),
+            "E010: Arity mismatch calling 'fn': expected 2 arguments, got 1"
         );
     }
 
+    #[test]
+    fn test_tail_calls_reuse_frames() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defn count-down [n] (if (lte n 0) :done (count-down (- n 1))))
+            (defn even? [n] (if-let [more (gt n 0)] (odd? (- n 1)) true))
+            (defn count-odd [n] (or (lte n 0) (and (gt n 0) (count-odd (- n 1)))))
+            (defn sum-to [n total] (let [next (- n 1)] (if (lt n 1) total (sum-to next (+ total n)))))
+            (defn odd? [n] (when-let [more (gt n 0)] (var n (- n 1)) (even? n)))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(count-down 10000)", env.clone_ref()),
+            Ok(Expr::keyword(":done"))
+        );
+        assert_eq!(eval("(even? 1001)", env.clone_ref()), Ok(Expr::Nil));
+        assert_eq!(
+            eval("(count-odd 10000)", env.clone_ref()),
+            Ok(Expr::boolean(true))
+        );
+        assert_eq!(
+            eval("(sum-to 10000 0)", env.clone_ref()),
+            Ok(Expr::number(50005000.))
+        );
+        assert!(matches!(
+            eval("(count-down 1 2)", env),
+            Err(HError::InvalidArity(_, Arity::Count(1), 2))
+        ));
+    }
+
     #[test]
     fn test_resolves_map() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("{:a (+ 1 2)}", env.clone_ref()),
@@ -329,11 +867,42 @@ mod tests {
 
     #[test]
     fn test_resolves_vector() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("[1 (+ 1 2)]", env.clone_ref()),
             Ok(Expr::vector(&[Expr::number(1.), Expr::number(3.),]))
         );
     }
+
+    #[test]
+    fn test_calls_keywords_as_lookups() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(var person {:name \"Ada\" :address {:city \"London\"}})
+             (defn call-with [f m] (f m))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(
+            eval(
+                "[(:name person) (:city (:address person)) (:age person) (:age person 36)
+                  (:city (:job person)) (call-with :name person)]",
+                env.clone_ref()
+            ),
+            eval("[\"Ada\" \"London\" nil 36 nil \"Ada\"]", env.clone_ref())
+        );
+        assert!(matches!(
+            eval("(:name person 1 2)", env.clone_ref()),
+            Err(HError::InvalidArity(_, Arity::Range(1, 2), 3))
+        ));
+        assert_eq!(
+            eval("(:name [1])", env),
+            Err(HError::InvalidType(
+                ":name".to_string(),
+                Expr::vector(&[Expr::number(1.)])
+            ))
+        );
+    }
 }
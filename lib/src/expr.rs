@@ -1,8 +1,31 @@
-use std::collections::BTreeMap;
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
 use ordered_float::OrderedFloat;
 
-use crate::{env::EnvRef, error::HError};
+use crate::{env::EnvRef, error::HError, stats::Live};
+
+/// Options applied when displaying values, read from the dynamic vars
+/// `*print-length*`, `*print-depth*`, `*print-precision*`, and
+/// `*print-readably*`. `None` means unlimited.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct PrintLimits {
+    pub length: Option<usize>,
+    pub depth: Option<usize>,
+    pub precision: Option<usize>, // Digits after the decimal point
+    pub readably: bool,           // Quote and escape strings
+}
+
+thread_local! {
+    static PRINT_LIMITS: Cell<PrintLimits> = Cell::new(PrintLimits::default());
+    static PRINT_DEPTH: Cell<usize> = const { Cell::new(0) };
+    // Atoms currently being displayed, used to cut cycles.
+    static PRINTING_ATOMS: RefCell<Vec<*const Mutex<Expr>>> = const { RefCell::new(Vec::new()) };
+}
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Arity {
@@ -17,11 +40,15 @@ pub struct NativeFn {
 }
 
 pub struct Fn {
-    pub id: String,
+    pub id: Arc<str>,
+    pub name: Option<String>, // Set by defn or a named fn
     pub arity: Arity,
     pub args: Vec<Expr>,
-    pub function: Vec<Expr>,
+    pub hints: Vec<Option<String>>, // Type hinted for each of `args`
+    pub function: Arc<[Expr]>,      // Shared, so copying a function doesn't copy its body
     pub closure: EnvRef,
+    pub source: Option<String>, // Name of the file the function was defined in
+    pub(crate) live: Live,
 }
 
 pub struct Method {
@@ -29,10 +56,43 @@ pub struct Method {
     pub selector: Box<Expr>,
     pub arity: Arity,
     pub args: Vec<Expr>,
-    pub function: Vec<Expr>,
+    pub function: Arc<[Expr]>,
     pub closure: EnvRef,
+    pub doc: Option<String>,
+    pub source: Option<String>, // Name of the file the method was defined in
+}
+
+/// A function built at runtime rather than from source, e.g. by `partial`.
+/// Unlike a `NativeFn`, it receives evaluated arguments and can capture state.
+pub struct Closure {
+    pub id: String,
+    pub arity: Arity,
+    pub function: ClosureFn,
 }
 
+pub type ClosureFn = Arc<dyn std::ops::Fn(&[Expr], EnvRef) -> Result<Expr, HError> + Send + Sync>;
+
+/// A mutable reference cell. Atoms compare by identity, not by value.
+pub struct Atom(pub Arc<Mutex<Expr>>);
+
+/// Implemented by resources the host hands to scripts, such as database
+/// connections or sockets, so they display as more than an opaque handle.
+pub trait HuckInspect: Any + Send + Sync {
+    /// A short name for the kind of resource, e.g. `connection`.
+    fn type_name(&self) -> String;
+
+    /// A one-line summary, shown by `println` and the REPL.
+    fn summary(&self) -> String;
+
+    /// Named details listed by `inspect`.
+    fn fields(&self) -> Vec<(String, Expr)> {
+        vec![]
+    }
+}
+
+/// A value owned by the host. Like atoms, hosts compare by identity.
+pub struct Host(pub Arc<dyn HuckInspect>);
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Expr {
     List(Vec<Expr>),
@@ -43,10 +103,14 @@ pub enum Expr {
     Keyword(String),
     Symbol(String),
     Vector(Vec<Expr>),
+    NumVec(Vec<OrderedFloat<f64>>), // Numbers stored unboxed for bulk math
     Map(BTreeMap<Expr, Expr>),
     NativeFn(NativeFn),
     Fn(Fn),
+    Closure(Closure),
     Method(Method),
+    Atom(Atom),
+    Host(Host),
     Ampersand,
     Nil,
 }
@@ -76,6 +140,10 @@ impl Expr {
         Expr::Vector(exprs.to_vec())
     }
 
+    pub fn num_vec(values: &[f64]) -> Expr {
+        Expr::NumVec(values.iter().copied().map(OrderedFloat).collect())
+    }
+
     pub fn list(exprs: &[Expr]) -> Expr {
         Expr::List(exprs.to_vec())
     }
@@ -100,6 +168,26 @@ impl Expr {
         Expr::Map(map)
     }
 
+    pub fn atom(value: Expr) -> Expr {
+        Expr::Atom(Atom(Arc::new(Mutex::new(value))))
+    }
+
+    pub fn host(value: impl HuckInspect) -> Expr {
+        Expr::Host(Host(Arc::new(value)))
+    }
+
+    pub fn closure(
+        name: &str,
+        arity: Arity,
+        function: impl std::ops::Fn(&[Expr], EnvRef) -> Result<Expr, HError> + Send + Sync + 'static,
+    ) -> Expr {
+        Expr::Closure(Closure {
+            id: name.to_string(),
+            arity,
+            function: Arc::new(function),
+        })
+    }
+
     pub fn native_fn(
         name: &str,
         arity: Arity,
@@ -130,39 +218,184 @@ impl Arity {
         if matches {
             Ok(())
         } else {
-            Err(HError::InvalidArity(name.to_string(), self.clone()))
+            Err(HError::InvalidArity(
+                name.to_string(),
+                self.clone(),
+                args.len(),
+            ))
+        }
+    }
+}
+
+impl PrintLimits {
+    pub fn from_env(env: &EnvRef) -> PrintLimits {
+        let limit = |name: &str| match env.get(name) {
+            Ok(Expr::Number(value)) if *value >= 0. => Some(*value as usize),
+            _ => None,
+        };
+        PrintLimits {
+            length: limit("*print-length*"),
+            depth: limit("*print-depth*"),
+            precision: limit("*print-precision*"),
+            readably: !matches!(
+                env.get("*print-readably*"),
+                Ok(Expr::Nil | Expr::Boolean(false)) | Err(_)
+            ),
+        }
+    }
+
+    /// The same limits, printing in a form that reads back as the same value.
+    pub fn readably(self) -> PrintLimits {
+        PrintLimits {
+            readably: true,
+            ..self
         }
     }
+
+    /// Runs `f` with these limits applied to every `Display` of an `Expr`.
+    pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+        let previous = PRINT_LIMITS.with(|limits| limits.replace(self));
+        let result = f();
+        PRINT_LIMITS.with(|limits| limits.set(previous));
+        result
+    }
+}
+
+fn write_collection(
+    f: &mut std::fmt::Formatter<'_>,
+    open: &str,
+    close: &str,
+    len: usize,
+    items: impl Iterator<Item = String>,
+) -> std::fmt::Result {
+    let limits = PRINT_LIMITS.with(|limits| limits.get());
+    let depth = PRINT_DEPTH.with(|depth| depth.get());
+    if matches!(limits.depth, Some(max) if depth >= max) {
+        return write!(f, "#");
+    }
+
+    let shown = limits.length.unwrap_or(usize::MAX);
+    // Items are formatted lazily, so nested collections see the increased depth.
+    PRINT_DEPTH.with(|d| d.set(depth + 1));
+    let mut parts: Vec<String> = items.take(shown).collect();
+    PRINT_DEPTH.with(|d| d.set(depth));
+
+    if len > shown {
+        parts.push("...".to_string());
+    }
+    write!(f, "{}{}{}", open, parts.join(" "), close)
+}
+
+fn write_atom(f: &mut std::fmt::Formatter<'_>, atom: &Atom) -> std::fmt::Result {
+    let ptr = Arc::as_ptr(&atom.0);
+    if PRINTING_ATOMS.with(|atoms| atoms.borrow().contains(&ptr)) {
+        return write!(f, "#cycle");
+    }
+
+    PRINTING_ATOMS.with(|atoms| atoms.borrow_mut().push(ptr));
+    let value = atom.get().to_string();
+    PRINTING_ATOMS.with(|atoms| atoms.borrow_mut().pop());
+    write!(f, "#atom[{}]", value)
+}
+
+/// Escapes `value` with the escapes the scanner reads back: quotes,
+/// backslashes, line breaks, tabs, and the `#` of a `#{`.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '#' if chars.peek() == Some(&'{') => escaped.push_str("\\#"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expr::Number(OrderedFloat(value)) => write!(f, "{}", value),
+            Expr::Number(OrderedFloat(value)) => {
+                match PRINT_LIMITS.with(|limits| limits.get().precision) {
+                    Some(precision) => write!(f, "{:.*}", precision, value),
+                    None => write!(f, "{}", value),
+                }
+            }
             Expr::Symbol(value) => write!(f, "{}", value),
             Expr::Keyword(value) => write!(f, "{}", value),
-            Expr::String(value) => write!(f, "{}", value),
+            Expr::String(value) => {
+                if PRINT_LIMITS.with(|limits| limits.get().readably) {
+                    write!(f, "\"{}\"", escape(value))
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
             Expr::Boolean(value) => write!(f, "{}", value),
-            Expr::Vector(value) => write!(
+            Expr::List(value) => write_collection(
+                f,
+                "(",
+                ")",
+                value.len(),
+                value.iter().map(|e| e.to_string()),
+            ),
+            Expr::MethodList(value) => write_collection(
                 f,
-                "[{}]",
-                value
-                    .iter()
-                    .map(|e| e.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
+                "<",
+                ">",
+                value.len(),
+                value.iter().map(|e| e.to_string()),
             ),
-            Expr::Map(value) => write!(
+            Expr::Vector(value) => write_collection(
                 f,
-                "{{{}}}",
-                value
-                    .iter()
-                    .map(|(k, v)| format!("{} {}", k.to_string(), v.to_string()))
-                    .collect::<Vec<String>>()
-                    .join(" ")
+                "[",
+                "]",
+                value.len(),
+                value.iter().map(|e| e.to_string()),
             ),
+            Expr::NumVec(value) => write_collection(
+                f,
+                "#num-vec[",
+                "]",
+                value.len(),
+                value.iter().map(|n| Expr::Number(*n).to_string()),
+            ),
+            Expr::Map(value) => write_collection(
+                f,
+                "{",
+                "}",
+                value.len(),
+                value.iter().map(|(k, v)| format!("{} {}", k, v)),
+            ),
+            Expr::Atom(atom) => write_atom(f, atom),
+            Expr::Host(host) => write!(f, "#{}[{}]", host.0.type_name(), host.0.summary()),
+            Expr::NativeFn(native) => write!(f, "#fn[{} {}]", native.id, native.arity.describe()),
+            Expr::Fn(fun) => match &fun.name {
+                Some(name) => write!(f, "#fn[{} {}]", name, fun.arity.describe()),
+                None => write!(f, "#fn[{}]", fun.arity.describe()),
+            },
+            Expr::Closure(closure) => {
+                write!(f, "#fn[{} {}]", closure.id, closure.arity.describe())
+            }
+            Expr::Method(method) => write!(f, "#method[{}]", method.id),
+            Expr::Ampersand => write!(f, "&"),
             Expr::Nil => write!(f, "nil"),
-            val => write!(f, "{:?}", val),
+        }
+    }
+}
+
+impl Arity {
+    /// The accepted argument counts, e.g. `2 args` or `1+ args`.
+    fn describe(&self) -> String {
+        match self {
+            Arity::Count(1) => "1 arg".to_string(),
+            Arity::Count(count) => format!("{} args", count),
+            Arity::Range(min, usize::MAX) => format!("{}+ args", min),
+            Arity::Range(min, max) => format!("{}-{} args", min, max),
         }
     }
 }
@@ -183,7 +416,7 @@ impl Ord for NativeFn {
 
 impl PartialOrd for NativeFn {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.id.cmp(&other.id))
+        Some(self.cmp(other))
     }
 }
 
@@ -221,7 +454,7 @@ impl Ord for Fn {
 
 impl PartialOrd for Fn {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.id.cmp(&other.id))
+        Some(self.cmp(other))
     }
 }
 
@@ -234,11 +467,15 @@ impl std::fmt::Debug for Fn {
 impl Clone for Fn {
     fn clone(&self) -> Self {
         Fn {
-            id: self.id.to_string(),
+            id: Arc::clone(&self.id),
+            name: self.name.clone(),
             args: self.args.clone(),
+            hints: self.hints.clone(),
             arity: self.arity.to_owned(),
             closure: self.closure.clone_ref(),
             function: self.function.clone(),
+            source: self.source.clone(),
+            live: self.live.clone(),
         }
     }
 }
@@ -259,7 +496,7 @@ impl Ord for Method {
 
 impl PartialOrd for Method {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.id.cmp(&other.id))
+        Some(self.cmp(other))
     }
 }
 
@@ -278,6 +515,147 @@ impl Clone for Method {
             arity: self.arity.to_owned(),
             closure: self.closure.clone_ref(),
             function: self.function.clone(),
+            doc: self.doc.clone(),
+            source: self.source.clone(),
         }
     }
 }
+
+impl Closure {
+    fn ptr(&self) -> *const () {
+        Arc::as_ptr(&self.function) as *const ()
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr() == other.ptr()
+    }
+}
+
+impl Eq for Closure {}
+
+impl Ord for Closure {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.ptr().cmp(&other.ptr())
+    }
+}
+
+impl PartialOrd for Closure {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure").field("id", &self.id).finish()
+    }
+}
+
+impl Clone for Closure {
+    fn clone(&self) -> Self {
+        Closure {
+            id: self.id.to_string(),
+            arity: self.arity.to_owned(),
+            function: Arc::clone(&self.function),
+        }
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Atom {}
+
+impl Ord for Atom {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Debug for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Atom")
+            .field("ptr", &Arc::as_ptr(&self.0))
+            .finish()
+    }
+}
+
+impl Clone for Atom {
+    fn clone(&self) -> Self {
+        Atom(Arc::clone(&self.0))
+    }
+}
+
+impl Atom {
+    /// The value the atom holds.
+    pub fn get(&self) -> Expr {
+        self.lock().clone()
+    }
+
+    /// Stores `value` in the atom, returning the value it held.
+    pub fn replace(&self, value: Expr) -> Expr {
+        std::mem::replace(&mut self.lock(), value)
+    }
+
+    // A panic while the lock was held can't leave a half-written `Expr`.
+    fn lock(&self) -> MutexGuard<'_, Expr> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Host {
+    /// The host value, if it is a `T`.
+    pub fn downcast<T: HuckInspect>(&self) -> Option<&T> {
+        (self.0.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    fn ptr(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl PartialEq for Host {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr() == other.ptr()
+    }
+}
+
+impl Eq for Host {}
+
+impl Ord for Host {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.ptr().cmp(&other.ptr())
+    }
+}
+
+impl PartialOrd for Host {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Debug for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Host")
+            .field("type", &self.0.type_name())
+            .field("summary", &self.0.summary())
+            .finish()
+    }
+}
+
+impl Clone for Host {
+    fn clone(&self) -> Self {
+        Host(Arc::clone(&self.0))
+    }
+}
@@ -0,0 +1,837 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    cassette::{self, Cassette},
+    coverage,
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{eval, eval_exprs, eval_source},
+    expr::Expr,
+    modules::{
+        huckleberry, module_set, proc, process,
+        require::{self, FileLoader, LoadedModules, ModuleLoader},
+        scheduler, task, Module,
+    },
+    trace,
+};
+
+pub type Output = Rc<RefCell<dyn Write>>;
+pub type Input = Rc<RefCell<dyn BufRead>>;
+/// Called with an error and the env of the function call it escaped, or the
+/// top-level env. Returning a value makes the call return it instead;
+/// returning `None` lets the error propagate.
+pub type BreakHook = Rc<dyn Fn(&HError, EnvRef) -> Option<Expr>>;
+
+pub struct Options {
+    /// Maximum number of expressions evaluated by a single `eval` call.
+    pub budget: Option<usize>,
+    /// Maximum nesting of function and method calls.
+    pub recursion_limit: Option<usize>,
+    /// Maximum number of vars a script can define in a single env.
+    pub max_bindings: Option<usize>,
+    /// Maximum number of items in a vector or map built by a script.
+    pub max_collection_len: Option<usize>,
+    /// Maximum length, in bytes, of a string built by a script.
+    pub max_string_len: Option<usize>,
+    /// Destination for `print` and friends. Defaults to stdout.
+    pub output: Option<Output>,
+    /// Source of lines read by `prompt`. Defaults to stdin.
+    pub input: Option<Input>,
+    pub modules: Vec<Module>,
+    /// Source of files loaded with `require`. Defaults to the filesystem.
+    pub loader: Option<Rc<dyn ModuleLoader>>,
+    /// Run where an error is raised, e.g. to open a debugger.
+    pub break_on_error: Option<BreakHook>,
+    /// Makes runs reproducible: `now-ms` reads a clock that starts at the
+    /// epoch and only moves with `set-clock!`, and natives that depend on
+    /// the environment, such as `getenv` and `proc/spawn`, fail.
+    pub deterministic: bool,
+    /// Records the calls IO natives make, or replays recorded ones.
+    pub cassette: Option<Cassette>,
+    /// Lets scripts redefine special forms such as `if` and `defn`.
+    pub allow_redefine: bool,
+    /// Set from another thread to stop evaluation with `HError::Interrupted`.
+    /// It is cleared once evaluation stops.
+    pub interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            budget: None,
+            recursion_limit: None,
+            max_bindings: None,
+            max_collection_len: None,
+            max_string_len: None,
+            output: None,
+            input: None,
+            modules: Module::ALL.to_vec(),
+            loader: None,
+            break_on_error: None,
+            deterministic: false,
+            cassette: None,
+            allow_redefine: false,
+            interrupt: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Limits {
+    budget: Option<usize>,
+    recursion_limit: Option<usize>,
+    max_bindings: Option<usize>,
+    max_collection_len: Option<usize>,
+    max_string_len: Option<usize>,
+}
+
+thread_local! {
+    static LIMITS: Cell<Limits> = Cell::new(Limits::default());
+    static STEPS: Cell<usize> = const { Cell::new(0) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static OUTPUT: RefCell<Option<Output>> = const { RefCell::new(None) };
+    static INPUT: RefCell<Option<Input>> = const { RefCell::new(None) };
+    static LOADER: RefCell<Option<Rc<dyn ModuleLoader>>> = const { RefCell::new(None) };
+    static BREAK_HOOK: RefCell<Option<BreakHook>> = const { RefCell::new(None) };
+    // The frozen clock, in ms since the epoch, in deterministic mode.
+    static CLOCK: RefCell<Option<Rc<Cell<f64>>>> = const { RefCell::new(None) };
+    // Set while the hook runs, and after it lets an error propagate, so each
+    // error breaks once rather than once per enclosing call.
+    static BREAKING: Cell<bool> = const { Cell::new(false) };
+    // Number of enclosing `try`s, which handle errors without the hook.
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    static ALLOW_REDEFINE: Cell<bool> = const { Cell::new(false) };
+    static INTERRUPT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// A Huckleberry environment together with the options it is evaluated under.
+pub struct Interpreter {
+    env: EnvRef,
+    limits: Limits,
+    output: Option<Output>,
+    input: Option<Input>,
+    loader: Option<Rc<dyn ModuleLoader>>,
+    loaded: LoadedModules,
+    break_on_error: Option<BreakHook>,
+    clock: Option<Rc<Cell<f64>>>,
+    cassette: Option<Cassette>,
+    allow_redefine: bool,
+    interrupt: Option<Arc<AtomicBool>>,
+    procs: proc::Procs,
+    pending: task::Pending,
+    timers: scheduler::Timers,
+}
+
+impl Interpreter {
+    pub fn new() -> Result<Interpreter, HError> {
+        Interpreter::with_options(Options::default())
+    }
+
+    /// Fails if the prelude can't be loaded with `options.modules`.
+    pub fn with_options(options: Options) -> Result<Interpreter, HError> {
+        let mut env = Env::new();
+        env.merge_ref(module_set(&options.modules)?)?;
+        Ok(Interpreter::from_env(env, options))
+    }
+
+    /// Creates an interpreter whose session extends a shared `core` env
+    /// instead of building its own, so many sessions can be created cheaply.
+    /// `options.modules` is ignored; the core decides what is available.
+    /// An `EnvRef` is `Send + Sync`, so one core can back sessions on many
+    /// threads.
+    pub fn session(core: EnvRef, options: Options) -> Interpreter {
+        Interpreter::from_env(Env::session(core), options)
+    }
+
+    fn from_env(env: Env, options: Options) -> Interpreter {
+        Interpreter {
+            env: env.into_ref(),
+            limits: Limits {
+                budget: options.budget,
+                recursion_limit: options.recursion_limit,
+                max_bindings: options.max_bindings,
+                max_collection_len: options.max_collection_len,
+                max_string_len: options.max_string_len,
+            },
+            output: options.output,
+            input: options.input,
+            loader: options.loader,
+            loaded: LoadedModules::default(),
+            break_on_error: options.break_on_error,
+            clock: options.deterministic.then(Rc::default),
+            cassette: options.cassette,
+            allow_redefine: options.allow_redefine,
+            interrupt: options.interrupt,
+            procs: proc::Procs::default(),
+            pending: task::Pending::default(),
+            timers: scheduler::Timers::default(),
+        }
+    }
+
+    pub fn env(&self) -> EnvRef {
+        self.env.clone_ref()
+    }
+
+    pub fn eval(&self, input: &str) -> Result<Expr, HError> {
+        self.scope(|| eval(input, self.env()).or_else(|err| break_on_error(err, self.env())))
+    }
+
+    /// Like `eval`, attributing definitions to the file `name`.
+    pub fn eval_source(&self, input: &str, name: &str) -> Result<Expr, HError> {
+        coverage::record_source(name, input);
+        self.scope(|| {
+            eval_source(input, name, self.env()).or_else(|err| break_on_error(err, self.env()))
+        })
+    }
+
+    pub fn eval_exprs(&self, exprs: &[Expr]) -> Result<Expr, HError> {
+        self.scope(|| eval_exprs(exprs, self.env()).or_else(|err| break_on_error(err, self.env())))
+    }
+
+    /// Like `eval`, then awaits the tasks started by async host functions,
+    /// running their callbacks under this interpreter as they finish.
+    pub async fn eval_async(&self, input: &str) -> Result<Expr, HError> {
+        task::await_tasks(&self.pending, self.eval(input), |done, value| {
+            self.scope(|| task::finish(done, value, self.env()))
+        })
+        .await
+    }
+
+    /// Runs timers registered with `every` and `at` until none are left.
+    pub fn run_scheduled(&self) -> Result<(), HError> {
+        self.scope(|| {
+            scheduler::run_scheduled(self.env())
+                .or_else(|err| break_on_error(err, self.env()).map(|_| ()))
+        })
+    }
+
+    /// Runs the handlers registered with `on-exit`.
+    pub fn run_exit_handlers(&self) -> Result<(), HError> {
+        self.scope(process::run_exit_handlers)
+    }
+
+    /// Evaluates the prelude into this session again, preferring the files in
+    /// `dir`. Returns the files read from `dir`.
+    pub fn reload_prelude(&self, dir: &Path) -> Result<Vec<String>, HError> {
+        self.scope(|| huckleberry::reload_prelude(dir, self.env()))
+    }
+
+    /// Reloads required files that changed since they were loaded.
+    pub fn reload_changed(&self) -> Result<Vec<String>, HError> {
+        self.scope(|| require::reload_changed(self.env()))
+    }
+
+    /// Installs this interpreter's limits, IO, loader, loaded modules, and
+    /// its processes, tasks, and timers for the duration of `f`.
+    fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        trace::clear();
+        let limits = LIMITS.with(|limits| limits.replace(self.limits));
+        let steps = STEPS.with(|steps| steps.replace(0));
+        let depth = DEPTH.with(|depth| depth.replace(0));
+        let output = OUTPUT.with(|output| output.replace(self.output.clone()));
+        let input = INPUT.with(|input| input.replace(self.input.clone()));
+        let loader = LOADER.with(|loader| loader.replace(self.loader.clone()));
+        let loaded = require::replace_loaded_modules(Rc::clone(&self.loaded));
+        let hook = BREAK_HOOK.with(|hook| hook.replace(self.break_on_error.clone()));
+        let breaking = BREAKING.with(|breaking| breaking.replace(false));
+        let catching = CATCHING.with(|catching| catching.replace(0));
+        let clock = CLOCK.with(|clock| clock.replace(self.clock.clone()));
+        let cassette = cassette::replace_cassette(self.cassette.clone());
+        let allow_redefine = ALLOW_REDEFINE.with(|allow| allow.replace(self.allow_redefine));
+        let interrupt = INTERRUPT.with(|interrupt| interrupt.replace(self.interrupt.clone()));
+        let procs = proc::replace_procs(Rc::clone(&self.procs));
+        let pending = task::replace_pending(Rc::clone(&self.pending));
+        let timers = scheduler::replace_timers(Rc::clone(&self.timers));
+
+        let result = f();
+
+        LIMITS.with(|l| l.set(limits));
+        STEPS.with(|s| s.set(steps));
+        DEPTH.with(|d| d.set(depth));
+        OUTPUT.with(|o| o.replace(output));
+        INPUT.with(|i| i.replace(input));
+        LOADER.with(|l| l.replace(loader));
+        require::replace_loaded_modules(loaded);
+        BREAK_HOOK.with(|h| h.replace(hook));
+        BREAKING.with(|b| b.set(breaking));
+        CATCHING.with(|c| c.set(catching));
+        CLOCK.with(|c| c.replace(clock));
+        cassette::replace_cassette(cassette);
+        ALLOW_REDEFINE.with(|a| a.set(allow_redefine));
+        INTERRUPT.with(|i| i.replace(interrupt));
+        proc::replace_procs(procs);
+        task::replace_pending(pending);
+        scheduler::replace_timers(timers);
+        result
+    }
+}
+
+/// Counts one evaluation step against the active budget, and handles any
+/// pending signals or interrupt.
+pub(crate) fn tick() -> Result<(), HError> {
+    process::handle_signals()?;
+    if INTERRUPT.with(|interrupt| {
+        interrupt
+            .borrow()
+            .as_ref()
+            .is_some_and(|interrupt| interrupt.swap(false, Ordering::SeqCst))
+    }) {
+        return Err(HError::Interrupted);
+    }
+    let steps = STEPS.with(|steps| steps.get() + 1);
+    STEPS.with(|s| s.set(steps));
+    match LIMITS.with(|limits| limits.get().budget) {
+        Some(budget) if steps > budget => Err(HError::ResourceLimit(format!(
+            "Evaluation budget of {} steps exceeded",
+            budget
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn check_limit(
+    size: usize,
+    limit: impl FnOnce(Limits) -> Option<usize>,
+    name: &str,
+) -> Result<(), HError> {
+    match LIMITS.with(|limits| limit(limits.get())) {
+        Some(max) if size > max => Err(HError::ResourceLimit(format!(
+            "{} limit of {} exceeded",
+            name, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Fails if an env would hold more than the active limit of defined vars.
+pub(crate) fn check_bindings(count: usize) -> Result<(), HError> {
+    check_limit(count, |limits| limits.max_bindings, "Binding")
+}
+
+/// Fails if a collection of `len` items is over the active limit.
+pub(crate) fn check_collection_len(len: usize) -> Result<(), HError> {
+    check_limit(len, |limits| limits.max_collection_len, "Collection length")
+}
+
+/// Fails if a string of `len` bytes is over the active limit.
+pub(crate) fn check_string_len(len: usize) -> Result<(), HError> {
+    check_limit(len, |limits| limits.max_string_len, "String length")
+}
+
+/// The active interpreter's clock, if it runs in deterministic mode.
+pub(crate) fn frozen_clock() -> Option<Rc<Cell<f64>>> {
+    CLOCK.with(|clock| clock.borrow().clone())
+}
+
+/// Fails if `name` is called in deterministic mode.
+pub(crate) fn check_deterministic(name: &str) -> Result<(), HError> {
+    match frozen_clock() {
+        Some(_) => Err(HError::Nondeterministic(name.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Whether the active interpreter lets scripts redefine special forms.
+pub(crate) fn allow_redefine() -> bool {
+    ALLOW_REDEFINE.with(|allow| allow.get())
+}
+
+/// Gives the active break hook a chance to handle `err`, raised in `env`.
+pub(crate) fn break_on_error(err: HError, env: EnvRef) -> Result<Expr, HError> {
+    let hook = match BREAK_HOOK.with(|hook| hook.borrow().clone()) {
+        // Interrupts are requests to stop, not failures to debug.
+        Some(hook)
+            if err != HError::Interrupted
+                && !BREAKING.with(|b| b.get())
+                && CATCHING.with(|c| c.get()) == 0 =>
+        {
+            hook
+        }
+        _ => return Err(err),
+    };
+    BREAKING.with(|b| b.set(true));
+    let resumed = hook(&err, env);
+    BREAKING.with(|b| b.set(resumed.is_none()));
+    resumed.ok_or(err)
+}
+
+/// Runs `f`, whose errors the script handles itself, without breaking on them.
+/// An aborted error that `f` recovers from stops unwinding here, so later
+/// errors break again.
+pub(crate) fn catching<T>(f: impl FnOnce() -> T) -> T {
+    CATCHING.with(|c| c.set(c.get() + 1));
+    let breaking = BREAKING.with(|b| b.get());
+    let result = f();
+    BREAKING.with(|b| b.set(breaking));
+    CATCHING.with(|c| c.set(c.get() - 1));
+    result
+}
+
+/// Tracks one level of call nesting until the returned guard is dropped.
+pub(crate) fn enter_call() -> Result<CallGuard, HError> {
+    let depth = DEPTH.with(|depth| depth.get() + 1);
+    if let Some(limit) = LIMITS.with(|limits| limits.get().recursion_limit) {
+        if depth > limit {
+            return Err(HError::ResourceLimit(format!(
+                "Recursion limit of {} exceeded",
+                limit
+            )));
+        }
+    }
+    DEPTH.with(|d| d.set(depth));
+    Ok(CallGuard)
+}
+
+pub(crate) struct CallGuard;
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+    }
+}
+
+/// Writes program output to the active interpreter's writer, or stdout.
+pub fn write_output(text: &str) {
+    let output = OUTPUT.with(|output| output.borrow().clone());
+    // Scripts have no way to handle write failures, so they are dropped.
+    let _ = match output {
+        Some(writer) => writer.borrow_mut().write_all(text.as_bytes()),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+}
+
+/// Flushes the active interpreter's writer, or stdout.
+pub fn flush_output() {
+    let output = OUTPUT.with(|output| output.borrow().clone());
+    let _ = match output {
+        Some(writer) => writer.borrow_mut().flush(),
+        None => std::io::stdout().flush(),
+    };
+}
+
+/// Reads a line from the active interpreter's input, or stdin, without the
+/// line ending. Returns `None` at the end of the input.
+pub fn read_input_line() -> Result<Option<String>, HError> {
+    let input = INPUT.with(|input| input.borrow().clone());
+    let mut line = String::new();
+    let read = match input {
+        Some(reader) => reader.borrow_mut().read_line(&mut line),
+        None => std::io::stdin().lock().read_line(&mut line),
+    }
+    .map_err(|err| HError::IoError(err.to_string()))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    check_string_len(line.len())?;
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+/// Whether output goes to stdout rather than a custom writer.
+pub(crate) fn writes_stdout() -> bool {
+    OUTPUT.with(|output| output.borrow().is_none())
+}
+
+/// Whether input is read from stdin rather than a custom reader.
+pub(crate) fn reads_stdin() -> bool {
+    INPUT.with(|input| input.borrow().is_none())
+}
+
+/// The active interpreter's module loader, or the filesystem.
+pub(crate) fn module_loader() -> Rc<dyn ModuleLoader> {
+    LOADER
+        .with(|loader| loader.borrow().clone())
+        .unwrap_or_else(|| Rc::new(FileLoader::from_env(PathBuf::new())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::core_module;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_evaluates_in_persistent_env() {
+        let interpreter = Interpreter::new().unwrap();
+
+        interpreter.eval("(var a 2)").unwrap();
+
+        assert_eq!(interpreter.eval("(+ a 1)"), Ok(Expr::number(3.)));
+    }
+
+    #[test]
+    fn test_enforces_budget() {
+        let interpreter = Interpreter::with_options(Options {
+            budget: Some(100),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(interpreter.eval("(+ 1 2)"), Ok(Expr::number(3.)));
+        assert_eq!(
+            interpreter.eval("(for-each i (range 0 1000) i)"),
+            Err(HError::ResourceLimit(
+                "Evaluation budget of 100 steps exceeded".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_enforces_recursion_limit() {
+        let interpreter = Interpreter::with_options(Options {
+            recursion_limit: Some(20),
+            ..Options::default()
+        })
+        .unwrap();
+
+        interpreter
+            .eval("(defn down [n] (if (gt n 0) (+ (down (- n 1)) 0) n))")
+            .unwrap();
+
+        assert_eq!(interpreter.eval("(down 15)"), Ok(Expr::number(0.)));
+        assert_eq!(
+            interpreter.eval("(down 25)"),
+            Err(HError::ResourceLimit(
+                "Recursion limit of 20 exceeded".to_string()
+            ))
+        );
+        // The depth is unwound after an error.
+        assert_eq!(interpreter.eval("(down 15)"), Ok(Expr::number(0.)));
+        // Tail calls don't nest.
+        interpreter
+            .eval("(defn loop [n] (if (gt n 0) (loop (- n 1)) n))")
+            .unwrap();
+        assert_eq!(interpreter.eval("(loop 60)"), Ok(Expr::number(0.)));
+    }
+
+    #[test]
+    fn test_enforces_size_limits() {
+        let interpreter = Interpreter::with_options(Options {
+            max_bindings: Some(2),
+            max_collection_len: Some(100),
+            max_string_len: Some(10),
+            ..Options::default()
+        })
+        .unwrap();
+
+        interpreter
+            .eval("(var a 1) (defn b [] 2) (var a 3)")
+            .unwrap();
+        assert_eq!(
+            interpreter.eval("(var c 4)"),
+            Err(HError::ResourceLimit(
+                "Binding limit of 2 exceeded".to_string()
+            ))
+        );
+        assert!(interpreter.eval("(range 0 100)").is_ok());
+        assert_eq!(
+            interpreter.eval("(range 0 1000000000)"),
+            Err(HError::ResourceLimit(
+                "Collection length limit of 100 exceeded".to_string()
+            ))
+        );
+        assert_eq!(
+            interpreter.eval("(pr-str [1 2 3 4 5])"),
+            Err(HError::ResourceLimit(
+                "String length limit of 10 exceeded".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_deterministic_mode_freezes_clock() {
+        let interpreter = Interpreter::with_options(Options {
+            deterministic: true,
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(interpreter.eval("(now-ms)"), Ok(Expr::number(0.)));
+        interpreter
+            .eval("(set-clock! (parse-time \"2024-05-01T10:00:00Z\"))")
+            .unwrap();
+        assert_eq!(
+            interpreter.eval("(format-time (now-ms))"),
+            Ok(Expr::string("2024-05-01T10:00:00Z"))
+        );
+        assert_eq!(
+            interpreter.eval("(getenv \"HOME\")"),
+            Err(HError::Nondeterministic("getenv".to_string()))
+        );
+        assert!(matches!(
+            Interpreter::new().unwrap().eval("(set-clock! 0)"),
+            Err(HError::UnexpectedForm(..))
+        ));
+    }
+
+    #[test]
+    fn test_allows_redefining_special_forms() {
+        let interpreter = Interpreter::with_options(Options {
+            allow_redefine: true,
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(interpreter.eval("(var if 5) if"), Ok(Expr::number(5.)));
+        assert_eq!(
+            Interpreter::new().unwrap().eval("(var if 5)"),
+            Err(HError::ReservedName("if".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_replays_recorded_io() {
+        let path = std::env::temp_dir().join(format!("huck-cassette-{}", std::process::id()));
+        let run = |cassette: Cassette, input: &str| {
+            let interpreter = Interpreter::with_options(Options {
+                input: Some(Rc::new(RefCell::new(std::io::Cursor::new(
+                    input.to_string(),
+                )))),
+                output: Some(Rc::new(RefCell::new(Vec::new()))),
+                cassette: Some(cassette),
+                ..Options::default()
+            })
+            .unwrap();
+            interpreter.eval("[(prompt \"Name: \") (getenv \"HUCK_CASSETTE_TEST\")]")
+        };
+
+        let recorded = run(Cassette::record(&path).unwrap(), "Ada\n").unwrap();
+        // Replaying serves the recorded answers rather than reading input.
+        assert_eq!(
+            run(Cassette::replay(&path).unwrap(), ""),
+            Ok(recorded.clone())
+        );
+        assert_eq!(recorded, Expr::vector(&[Expr::string("Ada"), Expr::Nil]));
+
+        let interpreter = Interpreter::with_options(Options {
+            cassette: Some(Cassette::replay(&path).unwrap()),
+            ..Options::default()
+        })
+        .unwrap();
+        assert_eq!(
+            interpreter.eval("(getenv \"HOME\")"),
+            Err(HError::CassetteMismatch(
+                "getenv".to_string(),
+                vec![Expr::string("HOME")]
+            ))
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_captures_output() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let interpreter = Interpreter::with_options(Options {
+            output: Some(output.clone()),
+            ..Options::default()
+        })
+        .unwrap();
+
+        interpreter.eval("(print 1 2) (println \" done\")").unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "12 done\n"
+        );
+    }
+
+    #[test]
+    fn test_sessions_are_isolated() {
+        let core = core_module().unwrap();
+        let a = Interpreter::session(core.clone_ref(), Options::default());
+        let b = Interpreter::session(core, Options::default());
+
+        a.eval("(var x 1)").unwrap();
+
+        assert_eq!(a.eval("x"), Ok(Expr::number(1.)));
+        assert_eq!(b.eval("x"), Err(HError::UnboundVar("x".to_string())));
+    }
+
+    #[test]
+    fn test_sessions_share_core_across_threads() {
+        let core = core_module().unwrap();
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let core = core.clone_ref();
+                std::thread::spawn(move || {
+                    let session = Interpreter::session(core, Options::default());
+                    session.eval(&format!("(var x {i}) (+ x 1)")).unwrap()
+                })
+            })
+            .collect();
+
+        for (i, worker) in workers.into_iter().enumerate() {
+            assert_eq!(worker.join().unwrap(), Expr::number(i as f64 + 1.));
+        }
+    }
+
+    #[test]
+    fn test_timers_belong_to_their_interpreter() {
+        let a = Interpreter::new().unwrap();
+        let b = Interpreter::new().unwrap();
+
+        a.eval("(var ran (atom false)) (at 0 (fn [] (swap! ran (fn [_] true))))")
+            .unwrap();
+        b.run_scheduled().unwrap();
+        assert_eq!(a.eval("(deref ran)"), Ok(Expr::boolean(false)));
+
+        a.run_scheduled().unwrap();
+        assert_eq!(a.eval("(deref ran)"), Ok(Expr::boolean(true)));
+    }
+
+    #[test]
+    fn test_breaks_on_error_in_exprs_and_timers() {
+        let breaks = Rc::new(Cell::new(0));
+        let counted = Rc::clone(&breaks);
+        let interpreter = Interpreter::with_options(Options {
+            break_on_error: Some(Rc::new(move |_: &HError, _: EnvRef| {
+                counted.set(counted.get() + 1);
+                Some(Expr::Nil)
+            })),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval_exprs(&[Expr::symbol("missing")]),
+            Ok(Expr::Nil)
+        );
+        interpreter
+            .eval("(var ran (atom false)) (at 0 5) (at 0 (fn [] (reset! ran true)))")
+            .unwrap();
+        assert_eq!(interpreter.run_scheduled(), Ok(()));
+        assert_eq!(interpreter.eval("(deref ran)"), Ok(Expr::boolean(true)));
+        assert_eq!(breaks.get(), 2);
+    }
+
+    #[test]
+    fn test_breaks_on_error_in_failing_call() {
+        // Each frame the hook was called in.
+        let seen: Rc<RefCell<Vec<EnvRef>>> = Rc::default();
+        let recorded = Rc::clone(&seen);
+        let interpreter = Interpreter::with_options(Options {
+            break_on_error: Some(Rc::new(move |_: &HError, env: EnvRef| {
+                recorded.borrow_mut().push(env);
+                // Resume the first failure, abort the second.
+                match recorded.borrow().len() {
+                    1 => Some(Expr::number(0.)),
+                    _ => None,
+                }
+            })),
+            ..Options::default()
+        })
+        .unwrap();
+
+        interpreter
+            .eval("(defn half [n] (var twice (* n 2)) (/ n :two)) (defn outer [n] (half n))")
+            .unwrap();
+
+        assert_eq!(interpreter.eval("(+ (outer 4) 1)"), Ok(Expr::number(1.)));
+        assert_eq!(
+            interpreter.eval("(outer 6)"),
+            Err(HError::InvalidType("/".to_string(), Expr::keyword(":two")))
+        );
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(
+            seen[0].locals(),
+            Ok(vec![
+                ("n".to_string(), Expr::number(4.)),
+                ("twice".to_string(), Expr::number(8.))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_breaks_again_after_abort() {
+        let breaks = Rc::new(Cell::new(0));
+        let counted = Rc::clone(&breaks);
+        let interpreter = Interpreter::with_options(Options {
+            break_on_error: Some(Rc::new(move |_: &HError, _: EnvRef| {
+                counted.set(counted.get() + 1);
+                None
+            })),
+            ..Options::default()
+        })
+        .unwrap();
+
+        // An aborted error breaks once on its way out, not once per frame.
+        assert!(interpreter
+            .eval("(defn f [] (throw :a)) (defn g [] (f)) (g)")
+            .is_err());
+        assert_eq!(breaks.get(), 1);
+        assert!(interpreter.eval("(g)").is_err());
+        assert_eq!(breaks.get(), 2);
+    }
+
+    #[test]
+    fn test_try_handles_errors_without_breaking() {
+        let breaks = Rc::new(Cell::new(0));
+        let counted = Rc::clone(&breaks);
+        let interpreter = Interpreter::with_options(Options {
+            break_on_error: Some(Rc::new(move |_: &HError, _: EnvRef| {
+                counted.set(counted.get() + 1);
+                None
+            })),
+            budget: Some(100),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval("(defn f [] (throw :a)) (try (f) (catch e e))"),
+            Ok(Expr::keyword(":a"))
+        );
+        assert_eq!(breaks.get(), 0);
+        assert!(matches!(
+            interpreter.eval("(try (for-each i (range 0 1000) i) (catch e e))"),
+            Err(HError::ResourceLimit(_))
+        ));
+    }
+
+    #[test]
+    fn test_requires_through_loader() {
+        let sources = BTreeMap::from([(
+            "greeting".to_string(),
+            "(defn greet [] \"hello\")".to_string(),
+        )]);
+        let interpreter = Interpreter::with_options(Options {
+            loader: Some(Rc::new(sources)),
+            ..Options::default()
+        })
+        .unwrap();
+
+        interpreter.eval("(require \"greeting\")").unwrap();
+
+        assert_eq!(interpreter.eval("(greet)"), Ok(Expr::string("hello")));
+        assert_eq!(
+            interpreter.eval("(require \"missing\")"),
+            Err(HError::ModuleNotFound("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_limits_modules() {
+        let interpreter = Interpreter::with_options(Options {
+            modules: vec![],
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval("(inspect 1)"),
+            Err(HError::UnboundVar("inspect".to_string()))
+        );
+        assert_eq!(interpreter.eval("<1 + 2>"), Ok(Expr::number(3.)));
+    }
+}
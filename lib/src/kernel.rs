@@ -0,0 +1,648 @@
+//! An eval server for notebooks and editors, speaking JSON-RPC 2.0 with one
+//! message per line. Cells are evaluated in one persistent session:
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"code": "(println 1) [1 2]"}}
+//! <-- {"id":1,"jsonrpc":"2.0","result":{"inspect":"[2 items]\n  0: 1\n  1: 2","output":"1\n","value":"[1 2]"}}
+//! ```
+//! `eval` returns the value printed readably, its `inspect` tree, and what the
+//! cell printed. `complete` lists the names starting with its `prefix` param,
+//! and `doc` describes the methods or var called `name`. `interrupt` stops a
+//! running `eval` when served over TCP, and `shutdown` ends the server.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    io::{BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+};
+
+use crate::{
+    error::HError,
+    expr::PrintLimits,
+    interpreter::{Interpreter, Options},
+    modules::{doc::method_doc, inspect::inspect},
+};
+
+// JSON-RPC error codes.
+const PARSE_ERROR: f64 = -32700.;
+const INVALID_REQUEST: f64 = -32600.;
+const METHOD_NOT_FOUND: f64 = -32601.;
+const INVALID_PARAMS: f64 = -32602.;
+const EVAL_ERROR: f64 = -32000.;
+
+pub struct Kernel {
+    interpreter: Interpreter,
+    output: Rc<RefCell<Vec<u8>>>,
+    interrupt: Arc<AtomicBool>,
+}
+
+impl Kernel {
+    /// Creates a kernel whose session is evaluated under `options`. Output is
+    /// always captured, so `options.output` is replaced, as is
+    /// `options.interrupt`.
+    pub fn new(options: Options) -> Result<Kernel, HError> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let interpreter = Interpreter::with_options(Options {
+            output: Some(output.clone()),
+            interrupt: Some(interrupt.clone()),
+            ..options
+        })?;
+        Ok(Kernel {
+            interpreter,
+            output,
+            interrupt,
+        })
+    }
+
+    /// Responds to each request read from `input` until it ends or a
+    /// `shutdown` request arrives.
+    pub fn serve(&self, input: impl BufRead, output: impl Write) -> std::io::Result<()> {
+        self.respond(input.lines(), output).map(|_| ())
+    }
+
+    /// Serves clients connecting to `listener`, one at a time, until one asks
+    /// to shut down. Requests are read on another thread, so an `interrupt`
+    /// can stop an `eval` that is still running.
+    pub fn serve_tcp(&self, listener: TcpListener) -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let reader = BufReader::new(stream.try_clone()?);
+            let interrupt = self.interrupt.clone();
+            let (sender, requests) = mpsc::channel();
+            std::thread::spawn(move || {
+                for line in reader.lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    if method(&line).as_deref() == Some("interrupt") {
+                        interrupt.store(true, Ordering::SeqCst);
+                    }
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+            let shutdown = self.respond(requests.into_iter().map(Ok), &stream)?;
+            // Unblocks the reader if the client is still connected.
+            let _ = stream.shutdown(Shutdown::Both);
+            if shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Responds to `lines`, returning whether one asked to shut down.
+    fn respond(
+        &self,
+        lines: impl Iterator<Item = std::io::Result<String>>,
+        mut output: impl Write,
+    ) -> std::io::Result<bool> {
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = self.handle(&line);
+            if let Some(response) = response {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+            if shutdown {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The response to one request, if it has an id, and whether it asked to
+    /// shut down. Requests without an id are notifications, which get no
+    /// response.
+    fn handle(&self, line: &str) -> (Option<Json>, bool) {
+        let request = match Json::parse(line) {
+            Some(Json::Object(request)) => request,
+            Some(_) => {
+                return (
+                    Some(response(
+                        Json::Null,
+                        Err(error(INVALID_REQUEST, "Expected an object")),
+                    )),
+                    false,
+                )
+            }
+            None => {
+                return (
+                    Some(response(
+                        Json::Null,
+                        Err(error(PARSE_ERROR, "Invalid JSON")),
+                    )),
+                    false,
+                )
+            }
+        };
+        let id = request.get("id").cloned();
+        let param = |key: &str| match request.get("params") {
+            Some(Json::Object(params)) => match params.get(key) {
+                Some(Json::String(value)) => Ok(value.as_str()),
+                _ => Err(error(
+                    INVALID_PARAMS,
+                    &format!("Expected a \"{}\" string param", key),
+                )),
+            },
+            _ => Err(error(INVALID_PARAMS, "Expected params")),
+        };
+        let result = match request.get("method") {
+            Some(Json::String(method)) => match method.as_str() {
+                "eval" => param("code").and_then(|code| self.eval(code)),
+                "complete" => param("prefix").map(|prefix| self.complete(prefix)),
+                "doc" => param("name").and_then(|name| self.doc(name)),
+                "interrupt" => {
+                    // Nothing is running, so there is nothing left to stop.
+                    self.interrupt.store(false, Ordering::SeqCst);
+                    Ok(Json::Null)
+                }
+                "shutdown" => return (id.map(|id| response(id, Ok(Json::Null))), true),
+                method => Err(error(
+                    METHOD_NOT_FOUND,
+                    &format!("Unknown method: {}", method),
+                )),
+            },
+            _ => Err(error(INVALID_REQUEST, "Expected a method")),
+        };
+        (id.map(|id| response(id, result)), false)
+    }
+
+    fn eval(&self, code: &str) -> Result<Json, Json> {
+        let result = self.interpreter.eval(code);
+        let printed = Json::String(String::from_utf8_lossy(&self.output.take()).to_string());
+        match result {
+            Ok(value) => {
+                let text = PrintLimits::from_env(&self.interpreter.env())
+                    .readably()
+                    .scope(|| value.to_string());
+                let mut result = BTreeMap::new();
+                result.insert("value".to_string(), Json::String(text));
+                if let Ok(tree) = inspect(&value, &[]) {
+                    result.insert("inspect".to_string(), Json::String(tree));
+                }
+                result.insert("output".to_string(), printed);
+                Ok(Json::Object(result))
+            }
+            Err(err) => {
+                let mut error = eval_error(&err);
+                if let Json::Object(fields) = &mut error {
+                    if let Some(Json::Object(data)) = fields.get_mut("data") {
+                        data.insert("output".to_string(), printed);
+                    }
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// The vars and methods visible in the session that start with `prefix`.
+    fn complete(&self, prefix: &str) -> Json {
+        let env = self.interpreter.env();
+        let mut names = env.names().unwrap_or_default();
+        names.extend(env.method_names().unwrap_or_default());
+        Json::Array(
+            names
+                .into_iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(Json::String)
+                .collect(),
+        )
+    }
+
+    /// Describes the methods named `name`, or else the var.
+    fn doc(&self, name: &str) -> Result<Json, Json> {
+        let env = self.interpreter.env();
+        if let Ok(text) = method_doc(name, env.clone_ref()) {
+            return Ok(Json::String(text));
+        }
+        match env.get(name) {
+            Ok(value) => Ok(Json::String(value.to_string())),
+            Err(err) => Err(eval_error(&err)),
+        }
+    }
+}
+
+/// The method of the request on `line`, if it is one.
+fn method(line: &str) -> Option<String> {
+    match Json::parse(line)? {
+        Json::Object(mut request) => match request.remove("method")? {
+            Json::String(method) => Some(method),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A response carrying `result`, or an error object built by `error`.
+fn response(id: Json, result: Result<Json, Json>) -> Json {
+    let (key, value) = match result {
+        Ok(result) => ("result", result),
+        Err(error) => ("error", error),
+    };
+    Json::Object(BTreeMap::from([
+        ("jsonrpc".to_string(), Json::String("2.0".to_string())),
+        ("id".to_string(), id),
+        (key.to_string(), value),
+    ]))
+}
+
+fn error(code: f64, message: &str) -> Json {
+    Json::Object(BTreeMap::from([
+        ("code".to_string(), Json::Number(code)),
+        ("message".to_string(), Json::String(message.to_string())),
+    ]))
+}
+
+/// An error object for `err`, with its code in `data`.
+fn eval_error(err: &HError) -> Json {
+    let mut error = error(EVAL_ERROR, &err.to_string());
+    if let Json::Object(fields) = &mut error {
+        fields.insert(
+            "data".to_string(),
+            Json::Object(BTreeMap::from([(
+                "code".to_string(),
+                Json::String(err.code().to_string()),
+            )])),
+        );
+    }
+    error
+}
+
+/// The JSON values requests are made of.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        chars.peek().is_none().then_some(value)
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Chars) -> Option<Json> {
+    skip_whitespace(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut object = BTreeMap::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(object));
+            }
+            loop {
+                skip_whitespace(chars);
+                chars.next_if_eq(&'"')?;
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                chars.next_if_eq(&':')?;
+                object.insert(key, parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(object)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut array = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(array));
+            }
+            loop {
+                array.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(array)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        _ => {
+            let mut token = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || "+-.".contains(*c)) {
+                token.push(c);
+            }
+            match token.as_str() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Boolean(true)),
+                "false" => Some(Json::Boolean(false)),
+                number => number.parse().ok().map(Json::Number),
+            }
+        }
+    }
+}
+
+/// Reads the rest of a string, after its opening quote.
+fn parse_string(chars: &mut Chars) -> Option<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' => {
+                    let code = match parse_hex(chars)? {
+                        // Characters outside the BMP are escaped as a pair of
+                        // UTF-16 surrogates, high then low.
+                        high @ 0xD800..=0xDBFF => {
+                            if chars.next()? != '\\' || chars.next()? != 'u' {
+                                return None;
+                            }
+                            match parse_hex(chars)? {
+                                low @ 0xDC00..=0xDFFF => {
+                                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                                }
+                                _ => return None,
+                            }
+                        }
+                        code => code,
+                    };
+                    value.push(char::from_u32(code)?);
+                }
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Reads the four hex digits of a `\u` escape.
+fn parse_hex(chars: &mut Chars) -> Option<u32> {
+    let code: String = chars.by_ref().take(4).collect();
+    u32::from_str_radix(&code, 16).ok()
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Boolean(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve(requests: &str) -> Vec<Json> {
+        let kernel = Kernel::new(Options::default()).unwrap();
+        let mut output = Vec::new();
+        kernel.serve(requests.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| Json::parse(line).unwrap())
+            .collect()
+    }
+
+    fn field<'a>(json: &'a Json, path: &[&str]) -> &'a Json {
+        path.iter().fold(json, |json, key| match json {
+            Json::Object(entries) => &entries[*key],
+            _ => panic!("{} is not an object", json),
+        })
+    }
+
+    #[test]
+    fn test_evaluates_cells_in_one_session() {
+        let responses = serve(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"code": "(var a 2) (println \"a\tb\")"}}
+{"jsonrpc": "2.0", "id": "two", "method": "eval", "params": {"code": "[a \"x\"]"}}
+{"jsonrpc": "2.0", "id": 3, "method": "eval", "params": {"code": "(print 1) b"}}
+{"jsonrpc": "2.0", "id": 4, "method": "shutdown"}
+{"jsonrpc": "2.0", "id": 5, "method": "eval", "params": {"code": "a"}}"#,
+        );
+
+        assert_eq!(responses.len(), 4);
+        assert_eq!(field(&responses[0], &["id"]), &Json::Number(1.));
+        assert_eq!(
+            field(&responses[0], &["result", "output"]),
+            &Json::String("a\tb\n".to_string())
+        );
+        assert_eq!(
+            field(&responses[1], &["id"]),
+            &Json::String("two".to_string())
+        );
+        assert_eq!(
+            field(&responses[1], &["result", "value"]),
+            &Json::String("[2 \"x\"]".to_string())
+        );
+        assert_eq!(
+            field(&responses[2], &["error", "message"]),
+            &Json::String("E001: Unbound var 'b'".to_string())
+        );
+        assert_eq!(
+            field(&responses[2], &["error", "data", "output"]),
+            &Json::String("1".to_string())
+        );
+        assert_eq!(
+            field(&responses[2], &["error", "data", "code"]),
+            &Json::String("E001".to_string())
+        );
+        assert_eq!(field(&responses[3], &["result"]), &Json::Null);
+    }
+
+    #[test]
+    fn test_reports_invalid_requests() {
+        let responses = serve(
+            "{\"id\": 1, \"method\": \"eval\"\n[1, 2]\n{\"id\": 2, \"method\": \"run\"}\n{\"id\": 3, \"method\": \"eval\", \"params\": {}}",
+        );
+
+        let codes: Vec<&Json> = responses
+            .iter()
+            .map(|response| field(response, &["error", "code"]))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                PARSE_ERROR,
+                INVALID_REQUEST,
+                METHOD_NOT_FOUND,
+                INVALID_PARAMS
+            ]
+            .map(Json::Number)
+            .iter()
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_completes_and_describes_names() {
+        let responses = serve(
+            r#"{"id": 1, "method": "eval", "params": {"code": "(defn to-upper [s] s) (defm number? [to-words] this)"}}
+{"id": 2, "method": "complete", "params": {"prefix": "to-"}}
+{"id": 3, "method": "doc", "params": {"name": "to-upper"}}
+{"id": 4, "method": "doc", "params": {"name": "to-words"}}
+{"id": 5, "method": "doc", "params": {"name": "missing"}}"#,
+        );
+
+        assert_eq!(
+            field(&responses[1], &["result"]),
+            &Json::Array(vec![
+                Json::String("to-upper".to_string()),
+                Json::String("to-vector".to_string()),
+                Json::String("to-words".to_string())
+            ])
+        );
+        assert_eq!(
+            field(&responses[2], &["result"]),
+            &Json::String("#fn[to-upper 1 arg]".to_string())
+        );
+        assert!(matches!(
+            field(&responses[3], &["result"]),
+            Json::String(doc) if doc.starts_with("<this to-words>")
+        ));
+        assert_eq!(
+            field(&responses[4], &["error", "message"]),
+            &Json::String("E001: Unbound var 'missing'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interrupts_eval_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            let mut responses = BufReader::new(stream.try_clone().unwrap()).lines();
+            let mut request = |line: &str| writeln!(stream, "{}", line).unwrap();
+            request(
+                r#"{"id": 1, "method": "eval", "params": {"code": "(defn spin [] (spin)) (spin)"}}"#,
+            );
+            request(r#"{"id": 2, "method": "interrupt"}"#);
+            let interrupted = responses.next().unwrap().unwrap();
+            request(r#"{"id": 3, "method": "eval", "params": {"code": "(+ 1 2)"}}"#);
+            request(r#"{"id": 4, "method": "shutdown"}"#);
+            let rest: Vec<String> = responses.map(Result::unwrap).collect();
+            (interrupted, rest)
+        });
+
+        let kernel = Kernel::new(Options::default()).unwrap();
+        kernel.serve_tcp(listener).unwrap();
+        let (interrupted, rest) = client.join().unwrap();
+
+        assert_eq!(
+            field(&Json::parse(&interrupted).unwrap(), &["error", "message"]),
+            &Json::String("E023: Interrupted".to_string())
+        );
+        assert_eq!(rest.len(), 3);
+        assert_eq!(
+            field(&Json::parse(&rest[1]).unwrap(), &["result", "value"]),
+            &Json::String("3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_json_round_trips() {
+        let text = r#"{"a":[1,-2.5,true,null],"b":"q\"\\\n\u001b"}"#;
+
+        let json = Json::parse(text).unwrap();
+
+        assert_eq!(json.to_string(), text);
+        assert_eq!(Json::parse("[1,]"), None);
+        assert_eq!(Json::parse("{} x"), None);
+    }
+
+    #[test]
+    fn test_json_decodes_surrogate_pairs() {
+        assert_eq!(
+            Json::parse(r#""a\ud83d\ude00b""#),
+            Some(Json::String("a\u{1f600}b".to_string()))
+        );
+        assert_eq!(Json::parse(r#""\ud83d""#), None);
+        assert_eq!(Json::parse(r#""\ud83d\u0041""#), None);
+    }
+
+    #[test]
+    fn test_answers_only_requests_with_ids() {
+        let responses = serve(
+            r#"{"jsonrpc": "2.0", "method": "eval", "params": {"code": "(var a \"\ud83d\ude00\")"}}
+{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"code": "a"}}"#,
+        );
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(
+            field(&responses[0], &["result", "value"]),
+            &Json::String("\"\u{1f600}\"".to_string())
+        );
+    }
+}
@@ -1,7 +1,21 @@
+// `HError` carries the offending `Expr`, and `Expr` map keys hold `EnvRef`s
+// through closures; both are deliberate.
+#![allow(clippy::result_large_err, clippy::mutable_key_type)]
+
+pub mod analysis;
+pub mod cassette;
+pub mod coverage;
+pub mod docs;
 pub mod env;
 pub mod error;
 pub mod evaluator;
 pub mod expr;
+pub mod interpreter;
+pub mod kernel;
+pub mod manifest;
 pub mod modules;
 pub mod parser;
 mod scanner;
+pub mod stats;
+pub mod syntax;
+pub mod trace;
@@ -0,0 +1,263 @@
+//! `huck.toml` manifests, which list the libraries a project depends on.
+//!
+//! Only the subset of TOML manifests need is understood: `[section]` headers,
+//! `key = "string"` pairs, inline tables of strings, and `#` comment lines.
+//! ```toml
+//! [dependencies]
+//! utils = "https://example.com/utils.git"
+//! json = { git = "https://example.com/json.git", rev = "v1.0" }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::HError;
+
+/// Directory dependencies are fetched into, next to the manifest. It is
+/// searched for modules, so `(require "utils/strings")` finds
+/// `.huck_modules/utils/strings.huck`.
+pub const MODULES_DIR: &str = ".huck_modules";
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub git: String,
+    pub rev: Option<String>,
+}
+
+enum Value {
+    String(String),
+    Table(BTreeMap<String, String>),
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, HError> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let invalid = || HError::ParseError(format!("huck.toml:{}: {}", number + 1, line));
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                section = name
+                    .strip_suffix(']')
+                    .ok_or_else(invalid)?
+                    .trim()
+                    .to_string();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let (key, value) = (
+                key.trim().to_string(),
+                parse_value(value).ok_or_else(invalid)?,
+            );
+            let dependency = match (section.as_str(), value) {
+                ("package", Value::String(name)) if key == "name" => {
+                    manifest.name = Some(name);
+                    continue;
+                }
+                ("dependencies", Value::String(git)) => Dependency {
+                    name: key,
+                    git,
+                    rev: None,
+                },
+                ("dependencies", Value::Table(mut table)) => Dependency {
+                    name: key,
+                    git: table.remove("git").ok_or_else(invalid)?,
+                    rev: table.remove("rev"),
+                },
+                _ => continue,
+            };
+            if !dependency.is_safe() {
+                return Err(invalid());
+            }
+            manifest.dependencies.push(dependency);
+        }
+        Ok(manifest)
+    }
+
+    /// Clones or updates every dependency into `MODULES_DIR` below `root`
+    /// using `git`, returning the directories fetched.
+    pub fn fetch(&self, root: &Path) -> Result<Vec<PathBuf>, HError> {
+        let modules = root.join(MODULES_DIR);
+        let mut fetched = vec![];
+        for dependency in &self.dependencies {
+            if !dependency.is_safe() {
+                return Err(HError::IoError(format!(
+                    "Unsafe dependency '{}'",
+                    dependency.name
+                )));
+            }
+            let dir = modules.join(&dependency.name);
+            let dir_arg = dir.to_string_lossy().to_string();
+            if !dir.exists() {
+                git(&["clone", "--quiet", "--", &dependency.git, &dir_arg])?;
+            } else if dependency.rev.is_some() {
+                git(&["-C", &dir_arg, "fetch", "--quiet"])?;
+            } else {
+                git(&["-C", &dir_arg, "pull", "--quiet", "--ff-only"])?;
+            }
+            if let Some(rev) = &dependency.rev {
+                git(&["-C", &dir_arg, "checkout", "--quiet", rev])?;
+            }
+            fetched.push(dir);
+        }
+        Ok(fetched)
+    }
+}
+
+impl Dependency {
+    /// Whether the dependency can be handed to `git`: its name has to stay
+    /// a single directory inside `MODULES_DIR`, and neither its name nor its
+    /// rev may be mistaken for an option.
+    fn is_safe(&self) -> bool {
+        let name = &self.name;
+        !name.is_empty()
+            && !name.contains('/')
+            && !name.contains('\\')
+            && !name.contains("..")
+            && !name.starts_with('-')
+            && !self.rev.as_ref().is_some_and(|rev| rev.starts_with('-'))
+    }
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('{') {
+        let mut table = BTreeMap::new();
+        for pair in split_outside_quotes(inner.strip_suffix('}')?, ',') {
+            if pair.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = pair.split_once('=')?;
+            table.insert(key.trim().to_string(), parse_string(value)?);
+        }
+        return Some(Value::Table(table));
+    }
+    parse_string(text).map(Value::String)
+}
+
+fn parse_string(text: &str) -> Option<String> {
+    let text = text.trim();
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    (!inner.contains('"')).then(|| inner.to_string())
+}
+
+/// Splits `text` on each `separator` that is not inside a quoted string.
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn git(args: &[&str]) -> Result<(), HError> {
+    let status = Command::new("git")
+        .args(args)
+        .status()
+        .map_err(|err| HError::IoError(format!("Unable to run git: {}", err)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(HError::IoError(format!("git {} failed", args.join(" "))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            "
+            # A tool built on shared libraries.
+            [package]
+            name = \"tool\"
+
+            [dependencies]
+            utils = \"https://example.com/utils.git\"
+            json = { git = \"https://example.com/json.git\", rev = \"v1.0\" }
+            ",
+        );
+
+        assert_eq!(
+            manifest,
+            Ok(Manifest {
+                name: Some("tool".to_string()),
+                dependencies: vec![
+                    Dependency {
+                        name: "utils".to_string(),
+                        git: "https://example.com/utils.git".to_string(),
+                        rev: None,
+                    },
+                    Dependency {
+                        name: "json".to_string(),
+                        git: "https://example.com/json.git".to_string(),
+                        rev: Some("v1.0".to_string()),
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_manifest() {
+        assert_eq!(
+            Manifest::parse("[dependencies]\njson = { rev = \"v1.0\" }"),
+            Err(HError::ParseError(
+                "huck.toml:2: json = { rev = \"v1.0\" }".to_string()
+            ))
+        );
+    }
+    #[test]
+    fn test_parse_commas_in_strings() {
+        let manifest = Manifest::parse(
+            "[dependencies]\njson = { git = \"https://example.com/a,b.git\", rev = \"v1.0\" }",
+        );
+
+        assert_eq!(
+            manifest.map(|manifest| manifest.dependencies),
+            Ok(vec![Dependency {
+                name: "json".to_string(),
+                git: "https://example.com/a,b.git".to_string(),
+                rev: Some("v1.0".to_string()),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_parse_unsafe_dependencies() {
+        for line in [
+            "../escape = \"https://example.com/utils.git\"",
+            "a/b = \"https://example.com/utils.git\"",
+            "-c = \"https://example.com/utils.git\"",
+            "json = { git = \"https://example.com/json.git\", rev = \"--orphan\" }",
+        ] {
+            assert_eq!(
+                Manifest::parse(&format!("[dependencies]\n{}", line)),
+                Err(HError::ParseError(format!("huck.toml:2: {}", line)))
+            );
+        }
+    }
+}
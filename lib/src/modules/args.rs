@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, resolve_args},
+    expr::{Arity, Expr},
+};
+
+pub fn args_module() -> Env {
+    let mut env = Env::new();
+
+    // Replaced by the CLI with the arguments following the script path.
+    env.def("*args*", Expr::Vector(Vec::new()));
+
+    env.defn(
+        "parse-number",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::String(value) => Ok(value
+                    .trim()
+                    .parse::<f64>()
+                    .map(Expr::number)
+                    .unwrap_or(Expr::Nil)),
+                invalid => Err(HError::InvalidType(
+                    "parse-number".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "try-parse",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::String(value) => Ok(match value.trim().parse::<f64>() {
+                    Ok(number) => Expr::vector(&[Expr::boolean(true), Expr::number(number)]),
+                    Err(_) => Expr::vector(&[
+                        Expr::boolean(false),
+                        Expr::String(format!("Not a number: {}", value)),
+                    ]),
+                }),
+                invalid => Err(HError::InvalidType(
+                    "try-parse".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "parse-args",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            match (&resolved[0], &resolved[1]) {
+                (Expr::Vector(argv), Expr::Map(spec)) => parse_args(argv, spec, env),
+                (Expr::Vector(_), invalid) | (invalid, _) => Err(HError::InvalidType(
+                    "parse-args".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env
+}
+
+struct Spec {
+    flags: Vec<String>,
+    options: BTreeMap<String, BTreeMap<Expr, Expr>>,
+}
+
+/// Parses `--flag`, `--option value`, and `--option=value` arguments as
+/// described by `spec`. The result maps each flag and option to its value,
+/// `:args` to the positional arguments, `:help` to a usage summary, and
+/// `:help?` to whether `--help` was given. Everything after `--` is
+/// positional.
+pub fn parse_args(argv: &[Expr], spec: &BTreeMap<Expr, Expr>, env: EnvRef) -> Result<Expr, HError> {
+    let spec = read_spec(spec)?;
+    let mut result = BTreeMap::new();
+    for flag in &spec.flags {
+        result.insert(keyword(flag), Expr::boolean(false));
+    }
+    for (name, option) in &spec.options {
+        let default = option.get(&Expr::keyword(":default")).cloned();
+        result.insert(keyword(name), default.unwrap_or(Expr::Nil));
+    }
+    result.insert(Expr::keyword(":help"), Expr::String(help(&spec)));
+    result.insert(Expr::keyword(":help?"), Expr::boolean(false));
+
+    let mut positional = Vec::new();
+    let mut args = argv.iter();
+    while let Some(arg) = args.next() {
+        let arg = match arg {
+            Expr::String(arg) => arg,
+            invalid => {
+                return Err(HError::InvalidType(
+                    "parse-args".to_string(),
+                    invalid.clone(),
+                ))
+            }
+        };
+        let name = match arg.strip_prefix("--") {
+            Some("") => {
+                positional.extend(args.cloned());
+                break;
+            }
+            Some(name) => name,
+            None => {
+                positional.push(Expr::string(arg));
+                continue;
+            }
+        };
+        let (name, inline) = match name.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (name, None),
+        };
+
+        if name == "help" {
+            result.insert(Expr::keyword(":help?"), Expr::boolean(true));
+        } else if spec.flags.iter().any(|flag| flag == name) && inline.is_none() {
+            result.insert(keyword(name), Expr::boolean(true));
+        } else if let Some(option) = spec.options.get(name) {
+            let value = match inline {
+                Some(value) => value.to_string(),
+                None => match args.next() {
+                    Some(Expr::String(value)) => value.to_string(),
+                    _ => {
+                        return Err(HError::UnexpectedForm(
+                            "Missing option value".to_string(),
+                            Expr::string(arg),
+                        ))
+                    }
+                },
+            };
+            let value = match option.get(&Expr::keyword(":parse")) {
+                Some(parse) => call(parse, &[Expr::String(value)], env.clone_ref())?,
+                None => Expr::String(value),
+            };
+            result.insert(keyword(name), value);
+        } else {
+            return Err(HError::UnexpectedForm(
+                "Unknown option".to_string(),
+                Expr::string(arg),
+            ));
+        }
+    }
+    result.insert(Expr::keyword(":args"), Expr::Vector(positional));
+    Ok(Expr::Map(result))
+}
+
+fn read_spec(spec: &BTreeMap<Expr, Expr>) -> Result<Spec, HError> {
+    let mut flags = Vec::new();
+    match spec.get(&Expr::keyword(":flags")) {
+        Some(Expr::Vector(names)) => {
+            for name in names {
+                flags.push(option_name(name)?);
+            }
+        }
+        None => (),
+        Some(invalid) => return Err(invalid_spec(invalid)),
+    }
+
+    let mut options = BTreeMap::new();
+    match spec.get(&Expr::keyword(":options")) {
+        Some(Expr::Map(entries)) => {
+            for (name, option) in entries {
+                match option {
+                    Expr::Map(option) => options.insert(option_name(name)?, option.clone()),
+                    invalid => return Err(invalid_spec(invalid)),
+                };
+            }
+        }
+        None => (),
+        Some(invalid) => return Err(invalid_spec(invalid)),
+    }
+    Ok(Spec { flags, options })
+}
+
+fn option_name(expr: &Expr) -> Result<String, HError> {
+    match expr {
+        Expr::Keyword(name) => Ok(name.trim_start_matches(':').to_string()),
+        invalid => Err(invalid_spec(invalid)),
+    }
+}
+
+fn invalid_spec(expr: &Expr) -> HError {
+    HError::UnexpectedForm("Invalid parse-args spec".to_string(), expr.clone())
+}
+
+fn keyword(name: &str) -> Expr {
+    Expr::keyword(&format!(":{}", name))
+}
+
+fn help(spec: &Spec) -> String {
+    let mut lines = vec!["Options:".to_string()];
+    for flag in &spec.flags {
+        lines.push(format!("  --{}", flag));
+    }
+    for (name, option) in &spec.options {
+        let mut line = format!("  --{} <value>", name);
+        if let Some(doc) = option.get(&Expr::keyword(":doc")) {
+            line.push_str(&format!("  {}", doc));
+        }
+        if let Some(default) = option.get(&Expr::keyword(":default")) {
+            line.push_str(&format!(" (default: {})", default));
+        }
+        lines.push(line);
+    }
+    lines.push("  --help".to_string());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    const SPEC: &str = "{:flags [:verbose] :options {:port {:default 80 :parse parse-number}}}";
+
+    #[test]
+    fn test_try_parse() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(try-parse \" 8080\")", env.clone_ref()),
+            eval("[true 8080]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(try-parse \"80a\")", env.clone_ref()),
+            eval("[false \"Not a number: 80a\"]", env)
+        );
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        let parsed = eval(
+            &format!(
+                "(parse-args [\"in.txt\" \"--verbose\" \"--port\" \"8080\" \"--\" \"--x\"] {})",
+                SPEC
+            ),
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval(
+                "(fn [p] [(get p :verbose) (get p :port) (get p :args)])",
+                env.clone_ref()
+            )
+            .and_then(|f| call(&f, &[parsed], env.clone_ref())),
+            eval("[true 8080 [\"in.txt\" \"--x\"]]", env)
+        );
+    }
+
+    #[test]
+    fn test_parse_args_defaults_and_help() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            &format!(
+                "(var parsed (parse-args [\"--port=1\" \"--help\"] {}))",
+                SPEC
+            ),
+            env.clone_ref(),
+        )
+        .unwrap();
+        eval(
+            &format!("(var defaults (parse-args [] {}))", SPEC),
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(get parsed :port)", env.clone_ref()),
+            Ok(Expr::number(1.))
+        );
+        assert_eq!(
+            eval("(get parsed :help?)", env.clone_ref()),
+            Ok(Expr::boolean(true))
+        );
+        assert_eq!(
+            eval("(get defaults :port)", env.clone_ref()),
+            Ok(Expr::number(80.))
+        );
+        assert_eq!(
+            eval("(get defaults :verbose)", env.clone_ref()),
+            Ok(Expr::boolean(false))
+        );
+        assert_eq!(
+            eval("(get defaults :help)", env),
+            Ok(Expr::string(
+                "Options:\n  --verbose\n  --port <value> (default: 80)\n  --help"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                &format!("(parse-args [\"--nope\"] {})", SPEC),
+                env.clone_ref()
+            ),
+            Err(HError::UnexpectedForm(
+                "Unknown option".to_string(),
+                Expr::string("--nope")
+            ))
+        );
+        assert_eq!(
+            eval(&format!("(parse-args [\"--port\"] {})", SPEC), env),
+            Err(HError::UnexpectedForm(
+                "Missing option value".to_string(),
+                Expr::string("--port")
+            ))
+        );
+    }
+}
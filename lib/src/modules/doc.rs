@@ -0,0 +1,123 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr, Method},
+    interpreter::write_output,
+};
+
+pub fn doc_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "doc-method",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env.clone_ref())?[0] {
+                Expr::String(id) => {
+                    write_output(&format!("{}\n", method_doc(id, env)?));
+                    Ok(Expr::Nil)
+                }
+                invalid => Err(HError::InvalidType(
+                    "doc-method".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env
+}
+
+/// Describes every method registered under `id`, in dispatch order.
+pub fn method_doc(id: &str, env: EnvRef) -> Result<String, HError> {
+    let methods = env.get_methods(id)?;
+    let entries: Vec<String> = methods.iter().rev().map(describe_method).collect();
+    Ok(entries.join("\n\n"))
+}
+
+fn describe_method(method: &Method) -> String {
+    let mut lines = vec![
+        format!("<this {}>", signature(method)),
+        format!("  Selector: {}", selector_name(&method.selector)),
+    ];
+    if let Some(source) = &method.source {
+        lines.push(format!("  Defined in: {}", source));
+    }
+    if let Some(doc) = &method.doc {
+        lines.push(format!("  {}", doc));
+    }
+    lines.join("\n")
+}
+
+fn selector_name(selector: &Expr) -> String {
+    match selector {
+        Expr::NativeFn(fun) => fun.id.to_string(),
+        expr => expr.to_string(),
+    }
+}
+
+/// Pairs each part of the method id with its argument, e.g. `to: max do: f`.
+fn signature(method: &Method) -> String {
+    if method.args.is_empty() {
+        return method.id.to_string();
+    }
+    method
+        .id
+        .split(' ')
+        .zip(method.args.iter())
+        .map(|(part, arg)| format!("{}: {}", part, arg))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{env::Env, evaluator::eval};
+
+    #[test]
+    fn test_method_doc() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm number? [times: n do: f] \"Calls f n times.\" nil)
+            (defm true [times: n do: f] nil)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            method_doc("times do", env.clone_ref()),
+            Ok("<this times: n do: f>\n  Selector: true\n\n\
+                <this times: n do: f>\n  Selector: number?\n  Calls f n times."
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_method_doc_names_prelude_file() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            method_doc("less-than", env),
+            Ok(
+                "<this less-than: n>\n  Selector: number?\n  Defined in: <core/number.huck>"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_method_doc_without_args() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(defm number? [twice] (* this 2))", env.clone_ref()).unwrap();
+
+        assert_eq!(
+            method_doc("twice", env.clone_ref()),
+            Ok("<this twice>\n  Selector: number?".to_string())
+        );
+    }
+}
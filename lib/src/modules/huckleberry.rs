@@ -1,31 +1,147 @@
-use crate::{env::EnvRef, evaluator::eval};
-
-const CODE: &str = "
-(defm number? [to: max] (range this max))
-(defm number? [to: max do: f] (for-each i (range this max) (f i)))
-(defm number? [less-than: n] (lt this n))
-(defm number? [less-than-eq: n] (lte this n))
-(defm number? [greater-than: n] (gt this n))
-(defm number? [greater-than-eq: n] (gte this n))
-(defm true [=: e] (= this e))
-(defm true [!=: e] (!= this e))
-(defm number? [+: n] (+ this n))
-(defm number? [-: n] (- this n))
-(defm number? [/: n] (/ this n))
-(defm number? [*: n] (* this n))
-";
-
-pub fn add_eval_definitions(env: EnvRef) {
-    eval(CODE, env.clone_ref()).unwrap();
+use std::{cell::RefCell, collections::BTreeMap, path::Path, rc::Rc};
+
+use crate::{
+    env::EnvRef,
+    error::HError,
+    evaluator::{eval_exprs, eval_source, with_source},
+    expr::Expr,
+    parser::parse,
+};
+
+/// Prelude files written in Huckleberry, in load order. Each file may only
+/// rely on special forms, natives, and the files before it.
+pub const PRELUDE: &[(&str, &str)] = &[
+    ("core/number.huck", include_str!("core/number.huck")),
+    ("core/equality.huck", include_str!("core/equality.huck")),
+    ("core/function.huck", include_str!("core/function.huck")),
+];
+
+// Each prelude file is parsed once per thread, rather than once per env
+// built. Exprs can't be shared between threads.
+thread_local! {
+    static PARSED: RefCell<BTreeMap<&'static str, Rc<[Expr]>>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// The directory `PRELUDE` files were built from, so they can be edited and
+/// reloaded without rebuilding.
+pub const PRELUDE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/modules");
+
+pub fn add_eval_definitions(env: EnvRef) -> Result<(), HError> {
+    for (name, _) in PRELUDE {
+        add_prelude_file(name, env.clone_ref())?;
+    }
+    Ok(())
+}
+
+/// Evaluates a single prelude file into `env`. Its methods are attributed to
+/// a virtual file name, e.g. `<core/number.huck>`.
+pub fn add_prelude_file(name: &str, env: EnvRef) -> Result<Expr, HError> {
+    let exprs = parsed(name)?;
+    with_source(&format!("<{}>", name), || eval_exprs(&exprs, env))
+}
+
+fn parsed(name: &str) -> Result<Rc<[Expr]>, HError> {
+    if let Some(exprs) = PARSED.with(|parsed| parsed.borrow().get(name).cloned()) {
+        return Ok(exprs);
+    }
+    let (file, source) = PRELUDE
+        .iter()
+        .find(|(file, _)| *file == name)
+        .ok_or_else(|| HError::ModuleNotFound(name.to_string()))?;
+    let exprs: Rc<[Expr]> = parse(source)?.into();
+    PARSED.with(|parsed| parsed.borrow_mut().insert(file, Rc::clone(&exprs)));
+    Ok(exprs)
+}
+
+/// Evaluates the prelude into `env` again, reading each file from `dir` if
+/// it is there and using the built-in copy otherwise. Other bindings in
+/// `env` are kept. Returns the files read from `dir`.
+pub fn reload_prelude(dir: &Path, env: EnvRef) -> Result<Vec<String>, HError> {
+    let mut read = vec![];
+    for (name, _) in PRELUDE {
+        match std::fs::read_to_string(dir.join(name)) {
+            Ok(source) => {
+                eval_source(&source, &format!("<{}>", name), env.clone_ref())?;
+                read.push(name.to_string());
+            }
+            Err(_) => {
+                add_prelude_file(name, env.clone_ref())?;
+            }
+        }
+    }
+    Ok(read)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{env::Env, evaluator::eval, expr::Expr};
+    use super::*;
+    use crate::{
+        env::Env,
+        evaluator::eval,
+        modules::{native::native_module, special_forms::special_forms_module},
+    };
+
+    #[test]
+    fn test_prelude_files_load_in_order() {
+        let env = Env::new().into_ref();
+        env.merge(special_forms_module()).unwrap();
+        env.merge(native_module()).unwrap();
+
+        for (name, _) in PRELUDE {
+            assert!(add_prelude_file(name, env.clone_ref()).is_ok(), "{}", name);
+        }
+        assert_eq!(
+            add_prelude_file("core/missing.huck", env),
+            Err(HError::ModuleNotFound("core/missing.huck".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_reloads_prelude_from_disk() {
+        let dir = std::env::temp_dir().join(format!("huck-prelude-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("core")).unwrap();
+        std::fs::write(dir.join("core/function.huck"), "(defn answer [] 42)").unwrap();
+        let env = Env::with_core_module().unwrap().into_ref();
+        eval("(var kept 1)", env.clone_ref()).unwrap();
+
+        let read = reload_prelude(&dir, env.clone_ref());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(read, Ok(vec!["core/function.huck".to_string()]));
+        assert_eq!(eval("(answer)", env.clone_ref()), Ok(Expr::number(42.)));
+        assert_eq!(eval("kept", env.clone_ref()), Ok(Expr::number(1.)));
+        assert_eq!(env.get_methods("+").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parses_prelude_files_once() {
+        let first = parsed("core/number.huck").unwrap();
+
+        assert!(Rc::ptr_eq(&first, &parsed("core/number.huck").unwrap()));
+        assert!(!Rc::ptr_eq(&first, &parsed("core/equality.huck").unwrap()));
+    }
+
+    #[test]
+    fn test_reports_prelude_errors() {
+        // The prelude relies on natives, so it can't load without them.
+        let env = Env::new().into_ref();
+        env.merge(special_forms_module()).unwrap();
+
+        assert!(add_eval_definitions(env).is_err());
+    }
+
+    #[test]
+    fn test_prelude_methods_name_their_file() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        let methods = env.get_methods("+").unwrap();
+
+        assert_eq!(methods[0].source, Some("<core/number.huck>".to_string()));
+    }
 
     #[test]
     fn test_number_to() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("<1 to: 5>", env.clone_ref()),
@@ -40,7 +156,7 @@ mod tests {
 
     #[test]
     fn test_number_boolean_methods() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("<1 less-than: 5>", env.clone_ref()).unwrap(),
@@ -71,7 +187,7 @@ mod tests {
 
     #[test]
     fn test_eq_methods() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("<[1 2 3] = [1 2 3]>", env.clone_ref()).unwrap(),
@@ -96,7 +212,7 @@ mod tests {
 
     #[test]
     fn test_math_methods() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(eval("<1 + 2>", env.clone_ref()).unwrap(), Expr::number(3.));
         assert_eq!(eval("<1 - 2>", env.clone_ref()).unwrap(), Expr::number(-1.));
@@ -106,7 +222,7 @@ mod tests {
 
     #[test]
     fn test_number_to_do() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
         eval(
             "
             (var a 1)
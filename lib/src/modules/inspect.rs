@@ -0,0 +1,189 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::write_output,
+    stats::memory_stats,
+};
+
+/// Number of entries shown per collection before the remainder is summarized.
+const INSPECT_LENGTH: usize = 10;
+
+pub fn inspect_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "inspect",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            write_output(&format!("{}\n", inspect(&resolved[0], &resolved[1..])?));
+            Ok(Expr::Nil)
+        },
+    );
+
+    env.defn(
+        "memory-stats",
+        Arity::Count(0),
+        |_: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            let stats = memory_stats();
+            Ok(Expr::map(&[
+                (Expr::keyword(":envs"), Expr::number(stats.envs as f64)),
+                (Expr::keyword(":fns"), Expr::number(stats.fns as f64)),
+            ]))
+        },
+    );
+
+    env
+}
+
+/// Renders `expr` as an indented tree, one entry per line.
+/// `path` is a sequence of vector indices and map keys selecting the
+/// sub-value to render, e.g. `[0 :name]`.
+pub fn inspect(expr: &Expr, path: &[Expr]) -> Result<String, HError> {
+    let mut target = expr;
+    for key in path {
+        let selected = match (target, key) {
+            (Expr::Vector(vec), Expr::Number(index)) if **index >= 0. => vec.get(**index as usize),
+            (Expr::Map(map), key) => map.get(key),
+            _ => None,
+        };
+        target = selected.ok_or_else(|| {
+            HError::UnexpectedForm("Invalid inspect path".to_string(), key.clone())
+        })?;
+    }
+
+    let mut lines = Vec::new();
+    inspect_lines(target, "", 0, &mut lines);
+    Ok(lines.join("\n"))
+}
+
+fn inspect_lines(expr: &Expr, label: &str, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    match expr {
+        Expr::Vector(vec) => {
+            lines.push(format!("{}{}[{}]", indent, label, count(vec.len(), "item")));
+            for (i, value) in vec.iter().take(INSPECT_LENGTH).enumerate() {
+                inspect_lines(value, &format!("{}: ", i), depth + 1, lines);
+            }
+            summarize_rest(vec.len(), depth + 1, lines);
+        }
+        Expr::Map(map) => {
+            lines.push(format!(
+                "{}{}{{{}}}",
+                indent,
+                label,
+                count(map.len(), "entry")
+            ));
+            for (key, value) in map.iter().take(INSPECT_LENGTH) {
+                inspect_lines(value, &format!("{} => ", key), depth + 1, lines);
+            }
+            summarize_rest(map.len(), depth + 1, lines);
+        }
+        Expr::Host(host) => {
+            lines.push(format!("{}{}{}", indent, label, expr));
+            for (name, value) in host.0.fields() {
+                inspect_lines(&value, &format!("{}: ", name), depth + 1, lines);
+            }
+        }
+        Expr::String(value) => lines.push(format!("{}{}{:?}", indent, label, value)),
+        value => lines.push(format!("{}{}{}", indent, label, value)),
+    }
+}
+
+fn summarize_rest(len: usize, depth: usize, lines: &mut Vec<String>) {
+    if len > INSPECT_LENGTH {
+        lines.push(format!(
+            "{}... (+ {} more)",
+            "  ".repeat(depth),
+            len - INSPECT_LENGTH
+        ));
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    match (n, noun) {
+        (1, _) => format!("1 {}", noun),
+        (_, "entry") => format!("{} entries", n),
+        _ => format!("{} {}s", n, noun),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{env::Env, evaluator::eval, expr::HuckInspect};
+
+    #[test]
+    fn test_inspects_nested_structures() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let value = eval("{:name \"Bob\" :tags [1 2]}", env).unwrap();
+
+        assert_eq!(
+            inspect(&value, &[]).unwrap(),
+            "{2 entries}\n  :name => \"Bob\"\n  :tags => [2 items]\n    0: 1\n    1: 2"
+        );
+    }
+
+    #[test]
+    fn test_truncates_long_vectors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let value = eval("(range 0 1000)", env).unwrap();
+        let result = inspect(&value, &[]).unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "[1000 items]");
+        assert_eq!(lines[11], "  ... (+ 990 more)");
+    }
+
+    struct Connection;
+
+    impl HuckInspect for Connection {
+        fn type_name(&self) -> String {
+            "connection".to_string()
+        }
+
+        fn summary(&self) -> String {
+            "db.local:5432".to_string()
+        }
+
+        fn fields(&self) -> Vec<(String, Expr)> {
+            vec![("open".to_string(), Expr::boolean(true))]
+        }
+    }
+
+    #[test]
+    fn test_inspects_host_values() {
+        let value = Expr::host(Connection);
+
+        assert_eq!(value.to_string(), "#connection[db.local:5432]");
+        assert_eq!(
+            inspect(&Expr::vector(std::slice::from_ref(&value)), &[]).unwrap(),
+            "[1 item]\n  0: #connection[db.local:5432]\n    open: true"
+        );
+        match value {
+            Expr::Host(host) => assert!(host.downcast::<Connection>().is_some()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_inspects_path() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let value = eval("[{:name \"Bob\"}]", env).unwrap();
+
+        assert_eq!(
+            inspect(&value, &[Expr::number(0.), Expr::keyword(":name")]).unwrap(),
+            "\"Bob\""
+        );
+        assert_eq!(
+            inspect(&value, &[Expr::number(1.)]),
+            Err(HError::UnexpectedForm(
+                "Invalid inspect path".to_string(),
+                Expr::number(1.)
+            ))
+        );
+    }
+}
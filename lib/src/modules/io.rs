@@ -0,0 +1,231 @@
+use std::{
+    cell::Cell,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    cassette::recorded,
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::{flush_output, read_input_line, reads_stdin, write_output},
+};
+
+pub fn io_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "prompt",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> { prompt("prompt", args, env, false) },
+    );
+
+    env.defn(
+        "prompt-secret",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            prompt("prompt-secret", args, env, true)
+        },
+    );
+
+    env.defn(
+        "with-temp-file",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let path = temp_path("file");
+            fs::File::create(&path).map_err(|err| HError::IoError(err.to_string()))?;
+            let result = call_with_path(args, &path, env);
+            let _ = fs::remove_file(&path);
+            result
+        },
+    );
+
+    env.defn(
+        "with-temp-dir",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let path = temp_path("dir");
+            fs::create_dir(&path).map_err(|err| HError::IoError(err.to_string()))?;
+            let result = call_with_path(args, &path, env);
+            let _ = fs::remove_dir_all(&path);
+            result
+        },
+    );
+
+    env
+}
+
+thread_local! {
+    static TEMP_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A path in the system temp dir that no other process or call uses.
+fn temp_path(kind: &str) -> PathBuf {
+    let count = TEMP_COUNT.with(|count| count.replace(count.get() + 1));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!(
+        "huck-{}-{}-{:?}-{}-{}",
+        kind,
+        std::process::id(),
+        std::thread::current().id(),
+        count,
+        nanos
+    ))
+}
+
+/// Calls the function in `args` with `path`. The caller removes the path
+/// afterwards, whether or not the call succeeded.
+fn call_with_path(args: &[Expr], path: &std::path::Path, env: EnvRef) -> Result<Expr, HError> {
+    let fun = resolve_args(args, env.clone_ref())?.remove(0);
+    let path = Expr::string(&path.to_string_lossy());
+    call(&fun, &[path], env)
+}
+
+/// Shows a message and reads one line of input, returning nil at the end of
+/// the input.
+fn prompt(name: &str, args: &[Expr], env: EnvRef, secret: bool) -> Result<Expr, HError> {
+    let resolved = resolve_args(args, env)?;
+    let message = match &resolved[0] {
+        Expr::String(message) => message.to_string(),
+        invalid => return Err(HError::InvalidType(name.to_string(), invalid.clone())),
+    };
+    write_output(&message);
+    flush_output();
+
+    recorded(name, &resolved, || {
+        let line = if secret && reads_stdin() {
+            let hidden = EchoGuard::hide();
+            let line = read_input_line()?;
+            // The newline the user typed was not echoed either.
+            if hidden.active() {
+                write_output("\n");
+            }
+            line
+        } else {
+            read_input_line()?
+        };
+        Ok(line.map(|line| Expr::string(&line)).unwrap_or(Expr::Nil))
+    })
+}
+
+/// Turns off terminal echo on stdin until dropped. Does nothing when stdin
+/// is not a terminal.
+#[cfg(unix)]
+struct EchoGuard(Option<libc::termios>);
+
+#[cfg(unix)]
+impl EchoGuard {
+    fn hide() -> EchoGuard {
+        // SAFETY: termios is plain data, and the calls only touch stdin's
+        // terminal settings, which are restored on drop.
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 0 {
+                return EchoGuard(None);
+            }
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut term) != 0 {
+                return EchoGuard(None);
+            }
+            let original = term;
+            term.c_lflag &= !libc::ECHO;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term) != 0 {
+                return EchoGuard(None);
+            }
+            EchoGuard(Some(original))
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        if let Some(original) = &self.0 {
+            // SAFETY: restores settings read from the same terminal.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct EchoGuard;
+
+#[cfg(not(unix))]
+impl EchoGuard {
+    fn hide() -> EchoGuard {
+        EchoGuard
+    }
+
+    fn active(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use crate::{
+        evaluator::eval,
+        interpreter::{Interpreter, Options},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_temp_paths_are_removed() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        let file = eval("(with-temp-file (fn [path] path))", env.clone_ref()).unwrap();
+        let dir = eval("(with-temp-dir (fn [path] path))", env.clone_ref()).unwrap();
+        let failed = eval(
+            "(var kept nil) (with-temp-file (fn [path] (set! kept path) (missing)))",
+            env.clone_ref(),
+        );
+
+        assert_ne!(file, dir);
+        for path in [file, dir, env.get("kept").unwrap()] {
+            match path {
+                Expr::String(path) => assert!(!std::path::Path::new(&path).exists()),
+                invalid => panic!("Expected a path, got {}", invalid),
+            }
+        }
+        assert_eq!(failed, Err(HError::UnboundVar("missing".to_string())));
+    }
+
+    #[test]
+    fn test_prompt_reads_lines() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let interpreter = Interpreter::with_options(Options {
+            output: Some(output.clone()),
+            input: Some(Rc::new(RefCell::new(Cursor::new("Ada\r\nhunter2\n")))),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval("(prompt \"Name: \")"),
+            Ok(Expr::string("Ada"))
+        );
+        assert_eq!(
+            interpreter.eval("(prompt-secret \"Password: \")"),
+            Ok(Expr::string("hunter2"))
+        );
+        assert_eq!(interpreter.eval("(prompt \"More: \")"), Ok(Expr::Nil));
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "Name: Password: More: "
+        );
+    }
+}
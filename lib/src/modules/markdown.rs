@@ -0,0 +1,431 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::check_string_len,
+};
+
+/// Width of the line drawn for a horizontal rule.
+const RULE_WIDTH: usize = 40;
+
+/// Renders the common subset of Markdown: headings, paragraphs, fenced code
+/// blocks, lists, block quotes, and rules, with emphasis, strong text, code,
+/// and links inside them. Anything else is kept as text.
+pub fn markdown_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "markdown->ansi",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let source = check_source(&resolve_args(args, env)?[0], "markdown->ansi")?;
+            let text = render_ansi(&parse_blocks(&source));
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
+        },
+    );
+
+    env.defn(
+        "markdown->html",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let source = check_source(&resolve_args(args, env)?[0], "markdown->html")?;
+            let text = to_html(&source);
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
+        },
+    );
+
+    env
+}
+
+/// Renders Markdown as HTML, e.g. for generated documentation.
+pub fn to_html(source: &str) -> String {
+    render_html(&parse_blocks(source))
+}
+
+fn check_source(expr: &Expr, fun_name: &str) -> Result<String, HError> {
+    match expr {
+        Expr::String(source) => Ok(source.to_string()),
+        _ => Err(HError::InvalidType(fun_name.to_string(), expr.clone())),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    Code(Option<String>, String), // Language, if given, and the code
+    List(bool, Vec<String>),      // Whether it is numbered, and the items
+    Quote(Vec<Block>),
+    Rule,
+}
+
+#[derive(Debug, PartialEq)]
+enum Inline {
+    Text(String),
+    Code(String),
+    Strong(Vec<Inline>),
+    Emphasis(Vec<Inline>),
+    Link(Vec<Inline>, String), // Text and URL
+}
+
+fn parse_blocks(source: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut lines = source.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(language) = trimmed.strip_prefix("```") {
+            let mut code = Vec::new();
+            for line in lines.by_ref() {
+                if line.trim() == "```" {
+                    break;
+                }
+                code.push(line);
+            }
+            let language = (!language.is_empty()).then(|| language.to_string());
+            blocks.push(Block::Code(language, code.join("\n")));
+        } else if let Some((level, text)) = heading(trimmed) {
+            blocks.push(Block::Heading(level, text.to_string()));
+        } else if is_rule(trimmed) {
+            blocks.push(Block::Rule);
+        } else if trimmed.starts_with('>') {
+            let mut quoted = vec![strip_quote(trimmed)];
+            while let Some(line) = lines.next_if(|line| line.trim().starts_with('>')) {
+                quoted.push(strip_quote(line.trim()));
+            }
+            blocks.push(Block::Quote(parse_blocks(&quoted.join("\n"))));
+        } else if let Some((numbered, item)) = list_item(trimmed) {
+            let mut items = vec![item.to_string()];
+            while let Some(line) = lines.next_if(|line| !line.trim().is_empty()) {
+                match list_item(line.trim()) {
+                    Some((_, item)) => items.push(item.to_string()),
+                    // Other lines continue the item before them.
+                    None => {
+                        let last = items.last_mut().unwrap();
+                        last.push(' ');
+                        last.push_str(line.trim());
+                    }
+                }
+            }
+            blocks.push(Block::List(numbered, items));
+        } else {
+            let mut text = vec![trimmed];
+            while let Some(line) = lines.next_if(|line| continues_paragraph(line)) {
+                text.push(line.trim());
+            }
+            blocks.push(Block::Paragraph(text.join(" ")));
+        }
+    }
+    blocks
+}
+
+/// The level and text of a heading such as `## Usage`.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    match line[level..].strip_prefix(' ') {
+        Some(text) if (1..=6).contains(&level) => Some((level, text.trim())),
+        _ => None,
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    line.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|rule| line.chars().all(|c| c == *rule))
+}
+
+fn strip_quote(line: &str) -> &str {
+    let line = &line[1..];
+    line.strip_prefix(' ').unwrap_or(line)
+}
+
+/// Whether the line is numbered, and its text, if it starts a list item.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some((false, item));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    match line[digits..].strip_prefix(". ") {
+        Some(item) if digits > 0 => Some((true, item)),
+        _ => None,
+    }
+}
+
+fn continues_paragraph(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty()
+        && !line.starts_with("```")
+        && !line.starts_with('>')
+        && heading(line).is_none()
+        && list_item(line).is_none()
+        && !is_rule(line)
+}
+
+fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut inlines = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let parsed = match chars[i] {
+            '`' => find(&chars, i + 1, "`").map(|end| {
+                let code = chars[i + 1..end].iter().collect();
+                (Inline::Code(code), end + 1)
+            }),
+            '*' | '_' if chars.get(i + 1) == Some(&chars[i]) => {
+                let delimiter: String = chars[i..i + 2].iter().collect();
+                find(&chars, i + 2, &delimiter).map(|end| {
+                    let inner: String = chars[i + 2..end].iter().collect();
+                    (Inline::Strong(parse_inline(&inner)), end + 2)
+                })
+            }
+            '*' | '_' => find(&chars, i + 1, &chars[i].to_string())
+                .filter(|end| *end > i + 1)
+                .map(|end| {
+                    let inner: String = chars[i + 1..end].iter().collect();
+                    (Inline::Emphasis(parse_inline(&inner)), end + 1)
+                }),
+            '[' => find(&chars, i + 1, "](").and_then(|middle| {
+                find(&chars, middle + 2, ")").map(|end| {
+                    let label: String = chars[i + 1..middle].iter().collect();
+                    let url = chars[middle + 2..end].iter().collect();
+                    (Inline::Link(parse_inline(&label), url), end + 1)
+                })
+            }),
+            _ => None,
+        };
+        match parsed {
+            Some((inline, next)) => {
+                if !plain.is_empty() {
+                    inlines.push(Inline::Text(std::mem::take(&mut plain)));
+                }
+                inlines.push(inline);
+                i = next;
+            }
+            None => {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    if !plain.is_empty() {
+        inlines.push(Inline::Text(plain));
+    }
+    inlines
+}
+
+/// The position of the next `delimiter` in `chars` from `start`.
+fn find(chars: &[char], start: usize, delimiter: &str) -> Option<usize> {
+    let delimiter: Vec<char> = delimiter.chars().collect();
+    (start..chars.len()).find(|i| chars[*i..].starts_with(&delimiter))
+}
+
+fn render_ansi(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Heading(1, text) => format!("\x1b[1;4m{}\x1b[0m", ansi_inline(text)),
+            Block::Heading(_, text) => format!("\x1b[1m{}\x1b[0m", ansi_inline(text)),
+            Block::Paragraph(text) => ansi_inline(text),
+            Block::Code(_, code) => code
+                .lines()
+                .map(|line| format!("    \x1b[36m{}\x1b[0m", line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::List(numbered, items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| match numbered {
+                    true => format!("{}. {}", i + 1, ansi_inline(item)),
+                    false => format!("• {}", ansi_inline(item)),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Quote(blocks) => render_ansi(blocks)
+                .lines()
+                .map(|line| format!("\x1b[2m│\x1b[0m {}", line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Rule => "─".repeat(RULE_WIDTH),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn ansi_inline(text: &str) -> String {
+    write_ansi(&parse_inline(text))
+}
+
+fn write_ansi(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => text.to_string(),
+            Inline::Code(code) => format!("\x1b[36m{}\x1b[0m", code),
+            Inline::Strong(inner) => format!("\x1b[1m{}\x1b[0m", write_ansi(inner)),
+            Inline::Emphasis(inner) => format!("\x1b[3m{}\x1b[0m", write_ansi(inner)),
+            Inline::Link(label, url) => {
+                format!("{} (\x1b[4m{}\x1b[0m)", write_ansi(label), url)
+            }
+        })
+        .collect()
+}
+
+fn render_html(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Heading(level, text) => {
+                format!("<h{0}>{1}</h{0}>", level, html_inline(text))
+            }
+            Block::Paragraph(text) => format!("<p>{}</p>", html_inline(text)),
+            Block::Code(Some(language), code) => format!(
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                escape_html(language),
+                escape_html(code)
+            ),
+            Block::Code(None, code) => format!("<pre><code>{}</code></pre>", escape_html(code)),
+            Block::List(numbered, items) => {
+                let tag = if *numbered { "ol" } else { "ul" };
+                let items: String = items
+                    .iter()
+                    .map(|item| format!("<li>{}</li>", html_inline(item)))
+                    .collect();
+                format!("<{0}>{1}</{0}>", tag, items)
+            }
+            Block::Quote(blocks) => format!("<blockquote>{}</blockquote>", render_html(blocks)),
+            Block::Rule => "<hr>".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn html_inline(text: &str) -> String {
+    write_html(&parse_inline(text))
+}
+
+fn write_html(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => escape_html(text),
+            Inline::Code(code) => format!("<code>{}</code>", escape_html(code)),
+            Inline::Strong(inner) => format!("<strong>{}</strong>", write_html(inner)),
+            Inline::Emphasis(inner) => format!("<em>{}</em>", write_html(inner)),
+            Inline::Link(label, url) => {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(safe_url(url)),
+                    write_html(label)
+                )
+            }
+        })
+        .collect()
+}
+
+/// Replaces URLs that would run code when followed, such as `javascript:`
+/// links, with `#`. Browsers ignore case and stray whitespace or control
+/// characters in a scheme, so those are ignored here too.
+fn safe_url(url: &str) -> &str {
+    let scheme: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .take_while(|c| *c != ':')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let has_scheme = url.contains(':');
+    match scheme.as_str() {
+        "javascript" | "vbscript" | "data" if has_scheme => "#",
+        _ => url,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_parses_blocks() {
+        assert_eq!(
+            parse_blocks("# Title\nSome\ntext.\n\n- one\n- two\n  more\n\n```huck\n(+ 1 2)\n```\n> quoted\n---"),
+            vec![
+                Block::Heading(1, "Title".to_string()),
+                Block::Paragraph("Some text.".to_string()),
+                Block::List(false, vec!["one".to_string(), "two more".to_string()]),
+                Block::Code(Some("huck".to_string()), "(+ 1 2)".to_string()),
+                Block::Quote(vec![Block::Paragraph("quoted".to_string())]),
+                Block::Rule,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parses_inline_styles() {
+        assert_eq!(
+            parse_inline("a **b _c_** `d*` [e](f) 2 * 3"),
+            vec![
+                Inline::Text("a ".to_string()),
+                Inline::Strong(vec![
+                    Inline::Text("b ".to_string()),
+                    Inline::Emphasis(vec![Inline::Text("c".to_string())]),
+                ]),
+                Inline::Text(" ".to_string()),
+                Inline::Code("d*".to_string()),
+                Inline::Text(" ".to_string()),
+                Inline::Link(vec![Inline::Text("e".to_string())], "f".to_string()),
+                Inline::Text(" 2 * 3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_renders_html_and_ansi() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "(markdown->html \"## Use <huck>\n1. Run `huck`\n2. **Done**\")",
+                env.clone_ref()
+            ),
+            Ok(Expr::string(
+                "<h2>Use &lt;huck&gt;</h2>\n<ol><li>Run <code>huck</code></li><li><strong>Done</strong></li></ol>"
+            ))
+        );
+        assert_eq!(
+            eval("(markdown->ansi \"# Hi\n\n*there*\")", env.clone_ref()),
+            Ok(Expr::string("\x1b[1;4mHi\x1b[0m\n\n\x1b[3mthere\x1b[0m"))
+        );
+        assert!(matches!(
+            eval("(markdown->html 1)", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_neutralizes_script_links() {
+        assert_eq!(
+            to_html("[a](javascript:alert`1`) [b](JavaScript:x) [c](java\tscript:x)"),
+            "<p><a href=\"#\">a</a> <a href=\"#\">b</a> <a href=\"#\">c</a></p>"
+        );
+        assert_eq!(
+            to_html("[d](vbscript:x) [e](data:text/html,x) [f](https://a.b/c:d) [g](notes.md)"),
+            "<p><a href=\"#\">d</a> <a href=\"#\">e</a> <a href=\"https://a.b/c:d\">f</a> <a href=\"notes.md\">g</a></p>"
+        );
+    }
+}
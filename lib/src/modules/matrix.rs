@@ -0,0 +1,170 @@
+use ordered_float::OrderedFloat;
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::check_collection_len,
+    modules::{num_vec::numbers, utils::check_num},
+};
+
+type Rows = Vec<Vec<OrderedFloat<f64>>>;
+
+/// A matrix is a vector of num-vec rows of one length, so each row works
+/// with the num-vec math. The functions here also take a vector of vectors
+/// of numbers wherever they take a matrix.
+pub fn matrix_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "matrix",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let rows = rows("matrix", &resolve_args(args, env)?[0])?;
+            check_collection_len(rows.len() * columns(&rows))?;
+            Ok(to_matrix(rows))
+        },
+    );
+
+    env.defn(
+        "mat-mul",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let a = rows("mat-mul", &resolved[0])?;
+            let b = rows("mat-mul", &resolved[1])?;
+            if columns(&a) != b.len() {
+                return Err(HError::InvalidType(
+                    "mat-mul".to_string(),
+                    resolved[1].clone(),
+                ));
+            }
+            check_collection_len(a.len() * columns(&b))?;
+            let product = a
+                .iter()
+                .map(|row| {
+                    (0..columns(&b))
+                        .map(|j| OrderedFloat(row.iter().zip(&b).map(|(x, r)| **x * *r[j]).sum()))
+                        .collect()
+                })
+                .collect();
+            Ok(to_matrix(product))
+        },
+    );
+
+    env.defn(
+        "transpose",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let rows = rows("transpose", &resolve_args(args, env)?[0])?;
+            let transposed = (0..columns(&rows))
+                .map(|j| rows.iter().map(|row| row[j]).collect())
+                .collect();
+            Ok(to_matrix(transposed))
+        },
+    );
+
+    env.defn(
+        "identity",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let size = resolve_args(args, env)?.remove(0);
+            let n = match check_num(&size, "identity")? {
+                n if n >= 0. && n.fract() == 0. => n as usize,
+                _ => return Err(HError::InvalidType("identity".to_string(), size)),
+            };
+            check_collection_len(n.saturating_mul(n))?;
+            let rows = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| OrderedFloat(if i == j { 1. } else { 0. }))
+                        .collect()
+                })
+                .collect();
+            Ok(to_matrix(rows))
+        },
+    );
+
+    env
+}
+
+/// The rows of a matrix, or of a vector of vectors of numbers, which must
+/// all be the same length.
+fn rows(name: &str, expr: &Expr) -> Result<Rows, HError> {
+    let rows = match expr {
+        Expr::Vector(rows) => rows
+            .iter()
+            .map(|row| numbers(name, row))
+            .collect::<Result<Rows, HError>>()?,
+        invalid => return Err(HError::InvalidType(name.to_string(), invalid.clone())),
+    };
+    match rows.iter().all(|row| row.len() == columns(&rows)) {
+        true => Ok(rows),
+        false => Err(HError::InvalidType(name.to_string(), expr.clone())),
+    }
+}
+
+fn columns(rows: &Rows) -> usize {
+    rows.first().map_or(0, Vec::len)
+}
+
+fn to_matrix(rows: Rows) -> Expr {
+    Expr::Vector(rows.into_iter().map(Expr::NumVec).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{env::Env, error::HError, evaluator::eval};
+
+    #[test]
+    fn test_multiplies_and_transposes() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var m (matrix [[1 2] [3 4]]))", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(mat-mul m [[0 1] [1 0]])", env.clone_ref()),
+            eval("(matrix [[2 1] [4 3]])", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(mat-mul m (identity 2))", env.clone_ref()),
+            eval("m", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(transpose [[1 2 3]])", env.clone_ref()),
+            eval("(matrix [[1] [2] [3]])", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(mat-mul [[1 2 3]] (transpose [[1 2 3]]))", env.clone_ref()),
+            eval("(matrix [[14]])", env)
+        );
+    }
+
+    #[test]
+    fn test_rejects_mismatched_shapes() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert!(matches!(
+            eval("(matrix [[1 2] [3]])", env.clone_ref()),
+            Err(HError::InvalidType(..))
+        ));
+        assert!(matches!(
+            eval("(mat-mul [[1 2]] [[1 2]])", env.clone_ref()),
+            Err(HError::InvalidType(..))
+        ));
+        assert!(matches!(
+            eval("(identity 1.5)", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_displays_matrices() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(identity 2)", env).unwrap().to_string(),
+            "[#num-vec[1 0] #num-vec[0 1]]"
+        );
+    }
+}
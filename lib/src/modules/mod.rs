@@ -1,18 +1,139 @@
-use crate::env::{Env, EnvRef};
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+};
 
 use self::{
-    huckleberry::add_eval_definitions, native::native_module, special_forms::special_forms_module,
+    args::args_module, doc::doc_module, huckleberry::add_eval_definitions, inspect::inspect_module,
+    io::io_module, matrix::matrix_module, native::native_module, num_vec::num_vec_module,
+    os::os_module, proc::proc_module, process::process_module, require::require_module,
+    scheduler::scheduler_module, schema::schema_module, special_forms::special_forms_module,
+    statistics::statistics_module, strings::strings_module, task::task_module, term::term_module,
+    time::time_module, units::units_module, watch::watch_module,
 };
 
+#[cfg(feature = "markdown")]
+use self::markdown::markdown_module;
+
+pub mod args;
+pub mod doc;
 pub mod huckleberry;
+pub mod inspect;
+pub mod io;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+pub mod matrix;
 pub mod native;
+pub mod num_vec;
+pub mod os;
+pub mod pattern;
+pub mod proc;
+pub mod process;
+pub mod require;
+pub mod scheduler;
+pub mod schema;
 pub mod special_forms;
+pub mod statistics;
+pub mod strings;
+pub mod task;
+pub mod term;
+pub mod time;
+pub mod units;
 pub mod utils;
+pub mod watch;
+
+/// Modules that can be left out of an interpreter. Special forms, natives,
+/// and the prelude are always loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Module {
+    Inspect,
+    Doc,
+    Require,
+    Io,
+    Term,
+    Args,
+    Process,
+    Proc,
+    Os,
+    Units,
+    Time,
+    Scheduler,
+    Task,
+    Watch,
+    Schema,
+    NumVec,
+    Statistics,
+    Matrix,
+    Strings,
+    #[cfg(feature = "markdown")]
+    Markdown,
+}
+
+impl Module {
+    pub const ALL: &'static [Module] = &[
+        Module::Inspect,
+        Module::Doc,
+        Module::Require,
+        Module::Io,
+        Module::Term,
+        Module::Args,
+        Module::Process,
+        Module::Proc,
+        Module::Os,
+        Module::Units,
+        Module::Time,
+        Module::Scheduler,
+        Module::Task,
+        Module::Watch,
+        Module::Schema,
+        Module::NumVec,
+        Module::Statistics,
+        Module::Matrix,
+        Module::Strings,
+        #[cfg(feature = "markdown")]
+        Module::Markdown,
+    ];
+
+    pub fn env(&self) -> Env {
+        match self {
+            Module::Inspect => inspect_module(),
+            Module::Doc => doc_module(),
+            Module::Require => require_module(),
+            Module::Io => io_module(),
+            Module::Term => term_module(),
+            Module::Args => args_module(),
+            Module::Process => process_module(),
+            Module::Proc => proc_module(),
+            Module::Os => os_module(),
+            Module::Units => units_module(),
+            Module::Time => time_module(),
+            Module::Scheduler => scheduler_module(),
+            Module::Task => task_module(),
+            Module::Watch => watch_module(),
+            Module::Schema => schema_module(),
+            Module::NumVec => num_vec_module(),
+            Module::Statistics => statistics_module(),
+            Module::Matrix => matrix_module(),
+            Module::Strings => strings_module(),
+            #[cfg(feature = "markdown")]
+            Module::Markdown => markdown_module(),
+        }
+    }
+}
+
+pub fn core_module() -> Result<EnvRef, HError> {
+    module_set(Module::ALL)
+}
 
-pub fn core_module() -> EnvRef {
+/// Builds an env with `modules` and the prelude, failing if the prelude
+/// can't be evaluated with them.
+pub fn module_set(modules: &[Module]) -> Result<EnvRef, HError> {
     let env = Env::new().into_ref();
-    env.merge(special_forms_module()).unwrap();
-    env.merge(native_module()).unwrap();
-    add_eval_definitions(env.clone_ref());
-    env
+    env.merge(special_forms_module())?;
+    env.merge(native_module())?;
+    for module in modules {
+        env.merge(module.env())?;
+    }
+    add_eval_definitions(env.clone_ref())?;
+    Ok(env)
 }
@@ -1,19 +1,30 @@
+use std::collections::BTreeMap;
+
 use crate::{
     env::{Env, EnvRef},
-    error::HError,
-    evaluator::resolve_args,
-    expr::{Arity, Expr},
-    modules::utils::check_num,
+    error::{code_of, HError},
+    evaluator::{apply, macroexpand_1, resolve_args},
+    expr::{Arity, Expr, PrintLimits},
+    interpreter::{catching, check_collection_len, check_string_len, write_output},
+    modules::{
+        num_vec::element_wise,
+        special_forms::gensym,
+        utils::{check_num, is_truthy},
+    },
+    trace,
 };
 
 macro_rules! num_operator {
     ($name:expr, $op:tt) => {
         Expr::native_fn($name, Arity::Range(1, usize::MAX), |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
             let resolved = resolve_args(args, env)?;
-            let mut result = check_num(&resolved[0], $name)?;
-            for expr in &resolved[1..] {
-                result = result $op check_num(expr, $name)?;
+            if resolved.iter().any(|expr| matches!(expr, Expr::NumVec(_))) {
+                return element_wise($name, &resolved, |a, b| a $op b);
             }
+            let first = check_num(&resolved[0], $name)?;
+            let result = resolved[1..]
+                .iter()
+                .try_fold(first, |acc, expr| Ok(acc $op check_num(expr, $name)?))?;
             Ok(Expr::number(result))
         })
     };
@@ -25,7 +36,8 @@ macro_rules! num_bool_operator {
             let resolved = resolve_args(args, env)?;
             let first = check_num(&resolved[0], $name)?;
             for expr in &resolved[1..] {
-                if !(first $op check_num(expr, $name)?) {
+                let holds = first $op check_num(expr, $name)?;
+                if !holds {
                     return Ok(Expr::boolean(false));
                 };
             }
@@ -40,7 +52,8 @@ macro_rules! generic_operator {
             let resolved = resolve_args(args, env)?;
             let first = &resolved[0];
             for expr in &resolved[1..] {
-                if !(first $op expr) {
+                let holds = first $op expr;
+                if !holds {
                     return Ok(Expr::boolean(false));
                 };
             }
@@ -69,6 +82,9 @@ pub fn native_module() -> Env {
         "get",
         Arity::Count(2),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let check_bounds = env
+                .get("*check-bounds*")
+                .is_ok_and(|check| is_truthy(&check));
             let resolved = resolve_args(args, env)?;
             match &resolved[0] {
                 Expr::Map(map) => match map.get(&resolved[1]) {
@@ -76,8 +92,8 @@ pub fn native_module() -> Env {
                     None => Ok(Expr::Nil),
                 },
                 Expr::Vector(vec) => {
-                    let index = match &resolved[1] {
-                        Expr::Number(value) => (**value) as usize,
+                    let mut index = match &resolved[1] {
+                        Expr::Number(value) => **value,
                         invalid => {
                             return Err(HError::UnexpectedForm(
                                 "Invalid vector index".to_string(),
@@ -85,8 +101,17 @@ pub fn native_module() -> Env {
                             ))
                         }
                     };
-                    match vec.get(index) {
+                    // Negative indexes count back from the end.
+                    if index < 0. {
+                        index += vec.len() as f64;
+                    }
+                    match (index >= 0.).then(|| vec.get(index as usize)).flatten() {
                         Some(value) => Ok(value.clone()),
+                        None if check_bounds => Err(HError::IndexOutOfBounds(
+                            "get".to_string(),
+                            resolved[1].clone(),
+                            vec.len(),
+                        )),
                         None => Ok(Expr::Nil),
                     }
                 }
@@ -99,35 +124,715 @@ pub fn native_module() -> Env {
     );
 
     env.defn(
-        "range",
-        Arity::Count(2),
+        "range",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match (&resolved[0], &resolved[1]) {
+                (Expr::Number(min), Expr::Number(max)) => {
+                    let min = **min as i64;
+                    let max = **max as i64;
+                    check_collection_len(max.saturating_sub(min).max(0) as usize)?;
+                    Ok(Expr::Vector(
+                        (min..max).map(|n| Expr::number(n as f64)).collect(),
+                    ))
+                }
+                (min, max) => Err(HError::UnexpectedForm(
+                    "Invalid range".to_string(),
+                    Expr::vector(&[min.clone(), max.clone()]),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "slice",
+        Arity::Count(3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "slice")?;
+            let start = check_slice_index(&resolved[1], vec.len())?;
+            let end = check_slice_index(&resolved[2], vec.len())?;
+            if end < start {
+                return Err(HError::IndexOutOfBounds(
+                    "slice".to_string(),
+                    resolved[2].clone(),
+                    vec.len(),
+                ));
+            }
+            Ok(Expr::vector(&vec[start..end]))
+        },
+    );
+
+    env.defn(
+        "take",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[1], "take")?;
+            let count = check_index(&resolved[0], vec.len(), "take")?;
+            Ok(Expr::vector(&vec[..count]))
+        },
+    );
+
+    env.defn(
+        "drop",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[1], "drop")?;
+            let count = check_index(&resolved[0], vec.len(), "drop")?;
+            Ok(Expr::vector(&vec[count..]))
+        },
+    );
+
+    env.defn(
+        "insert-at",
+        Arity::Count(3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut vec = check_vector(&resolved[0], "insert-at")?.clone();
+            let index = check_index(&resolved[1], vec.len(), "insert-at")?;
+            check_collection_len(vec.len() + 1)?;
+            vec.insert(index, resolved[2].clone());
+            Ok(Expr::Vector(vec))
+        },
+    );
+
+    env.defn(
+        "remove-at",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut vec = check_vector(&resolved[0], "remove-at")?.clone();
+            let index = check_index(&resolved[1], vec.len(), "remove-at")?;
+            // Unlike the other natives, the end of the vector isn't a valid index.
+            if index == vec.len() {
+                return Err(HError::IndexOutOfBounds(
+                    "remove-at".to_string(),
+                    resolved[1].clone(),
+                    vec.len(),
+                ));
+            }
+            vec.remove(index);
+            Ok(Expr::Vector(vec))
+        },
+    );
+
+    env.defn(
+        "conj",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut vec = check_vector(&resolved[0], "conj")?.clone();
+            check_collection_len(vec.len() + resolved.len() - 1)?;
+            vec.extend_from_slice(&resolved[1..]);
+            Ok(Expr::Vector(vec))
+        },
+    );
+
+    env.defn(
+        "push",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut vec = check_vector(&resolved[0], "push")?.clone();
+            check_collection_len(vec.len() + 1)?;
+            vec.push(resolved[1].clone());
+            Ok(Expr::Vector(vec))
+        },
+    );
+
+    env.defn(
+        "pop",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match check_vector(&resolved[0], "pop")?.split_last() {
+                Some((_, init)) => Ok(Expr::vector(init)),
+                None => Err(HError::EmptyCollection("pop".to_string())),
+            }
+        },
+    );
+
+    env.defn(
+        "first",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "first")?;
+            Ok(vec.first().cloned().unwrap_or(Expr::Nil))
+        },
+    );
+
+    env.defn(
+        "rest",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "rest")?;
+            Ok(Expr::vector(vec.get(1..).unwrap_or_default()))
+        },
+    );
+
+    env.defn(
+        "last",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "last")?;
+            Ok(vec.last().cloned().unwrap_or(Expr::Nil))
+        },
+    );
+
+    env.defn(
+        "nth",
+        Arity::Range(2, 3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "nth")?;
+            let index = check_num(&resolved[1], "nth")?;
+            if index.fract() != 0. {
+                return Err(HError::InvalidType("nth".to_string(), resolved[1].clone()));
+            }
+            match (
+                (index >= 0.).then(|| vec.get(index as usize)).flatten(),
+                resolved.get(2),
+            ) {
+                (Some(value), _) => Ok(value.clone()),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) => Err(HError::IndexOutOfBounds(
+                    "nth".to_string(),
+                    resolved[1].clone(),
+                    vec.len(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "concat",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vecs = resolved
+                .iter()
+                .map(|vec| check_vector(vec, "concat"))
+                .collect::<Result<Vec<_>, HError>>()?;
+            check_collection_len(vecs.iter().map(|vec| vec.len()).sum())?;
+            Ok(Expr::Vector(vecs.into_iter().flatten().cloned().collect()))
+        },
+    );
+
+    env.defn(
+        "reverse",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let vec = check_vector(&resolved[0], "reverse")?;
+            Ok(Expr::Vector(vec.iter().rev().cloned().collect()))
+        },
+    );
+
+    env.defn(
+        "sort",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut vec = check_vector(&resolved[0], "sort")?.clone();
+            vec.sort();
+            Ok(Expr::Vector(vec))
+        },
+    );
+
+    env.defn(
+        "sort-by",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            let vec = check_vector(&resolved[1], "sort-by")?;
+            let mut keyed = vec
+                .iter()
+                .map(|value| {
+                    Ok((
+                        apply(&resolved[0], std::slice::from_ref(value), env.clone_ref())?,
+                        value,
+                    ))
+                })
+                .collect::<Result<Vec<_>, HError>>()?;
+            // Stable, so values with equal keys keep their order.
+            keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(Expr::Vector(
+                keyed.into_iter().map(|(_, value)| value.clone()).collect(),
+            ))
+        },
+    );
+
+    env.defn(
+        "contains?",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match (&resolved[0], &resolved[1]) {
+                (Expr::Vector(vec), value) => Ok(Expr::boolean(vec.contains(value))),
+                (Expr::String(s), Expr::String(part)) => {
+                    Ok(Expr::boolean(s.contains(part.as_str())))
+                }
+                (Expr::String(_), invalid) | (invalid, _) => Err(HError::InvalidType(
+                    "contains?".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "assoc",
+        Arity::Range(3, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut map = check_map(&resolved[0], "assoc")?.clone();
+            let pairs = &resolved[1..];
+            if pairs.len() % 2 == 1 {
+                return Err(HError::UnexpectedForm(
+                    "Expected a value for each key".to_string(),
+                    pairs[pairs.len() - 1].clone(),
+                ));
+            }
+            for pair in pairs.chunks(2) {
+                map.insert(pair[0].clone(), pair[1].clone());
+            }
+            check_collection_len(map.len())?;
+            Ok(Expr::Map(map))
+        },
+    );
+
+    env.defn(
+        "dissoc",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut map = check_map(&resolved[0], "dissoc")?.clone();
+            for key in &resolved[1..] {
+                map.remove(key);
+            }
+            Ok(Expr::Map(map))
+        },
+    );
+
+    env.defn(
+        "merge",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let mut merged = BTreeMap::new();
+            // Later maps win when they share a key.
+            for map in &resolved {
+                merged.extend(check_map(map, "merge")?.clone());
+                check_collection_len(merged.len())?;
+            }
+            Ok(Expr::Map(merged))
+        },
+    );
+
+    env.defn(
+        "keys",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let map = check_map(&resolved[0], "keys")?;
+            Ok(Expr::Vector(map.keys().cloned().collect()))
+        },
+    );
+
+    env.defn(
+        "vals",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let map = check_map(&resolved[0], "vals")?;
+            Ok(Expr::Vector(map.values().cloned().collect()))
+        },
+    );
+
+    env.defn(
+        "contains-key?",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let map = check_map(&resolved[0], "contains-key?")?;
+            Ok(Expr::boolean(map.contains_key(&resolved[1])))
+        },
+    );
+
+    env.defn(
+        "update",
+        Arity::Range(3, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            let mut map = check_map(&resolved[0], "update")?.clone();
+            let key = &resolved[1];
+            // A missing key is updated from nil.
+            let mut values = vec![map.get(key).cloned().unwrap_or(Expr::Nil)];
+            values.extend_from_slice(&resolved[3..]);
+            let value = apply(&resolved[2], &values, env)?;
+            map.insert(key.clone(), value);
+            Ok(Expr::Map(map))
+        },
+    );
+
+    env.defn(
+        "select-keys",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let map = check_map(&resolved[0], "select-keys")?;
+            let keys = check_vector(&resolved[1], "select-keys")?;
+            Ok(Expr::Map(
+                keys.iter()
+                    .filter_map(|key| Some((key.clone(), map.get(key)?.clone())))
+                    .collect(),
+            ))
+        },
+    );
+
+    env.defn(
+        "values",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            Ok(Expr::Vector(resolve_args(args, env)?))
+        },
+    );
+
+    env.defn(
+        "try-call",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            match catching(|| apply(&resolved[0], &resolved[1..], env)) {
+                Ok(value) => Ok(Expr::vector(&[Expr::boolean(true), value])),
+                // Running out of budget or being interrupted should still stop the script.
+                Err(err @ (HError::ResourceLimit(_) | HError::Interrupted)) => Err(err),
+                Err(err) => {
+                    trace::clear();
+                    Ok(Expr::vector(&[
+                        Expr::boolean(false),
+                        Expr::String(err.to_string()),
+                    ]))
+                }
+            }
+        },
+    );
+
+    env.defn(
+        "error-code",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::String(message) => Ok(code_of(message).map_or(Expr::Nil, Expr::string)),
+                Expr::Map(map) => Ok(map
+                    .get(&Expr::keyword(":code"))
+                    .cloned()
+                    .unwrap_or(Expr::Nil)),
+                invalid => Err(HError::InvalidType(
+                    "error-code".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "throw",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            Err(HError::UserError(resolve_args(args, env)?.remove(0)))
+        },
+    );
+
+    env.defn(
+        "namespace",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::Keyword(keyword) => Ok(match keyword[1..].split_once('/') {
+                    Some((namespace, _)) => Expr::string(namespace),
+                    None => Expr::Nil,
+                }),
+                invalid => Err(HError::InvalidType(
+                    "namespace".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "name",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::Keyword(keyword) => Ok(Expr::string(match keyword[1..].split_once('/') {
+                    Some((_, name)) => name,
+                    None => &keyword[1..],
+                })),
+                Expr::String(name) => Ok(Expr::string(name)),
+                invalid => Err(HError::InvalidType("name".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "number?",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match resolved[0] {
+                Expr::Number(_) => Ok(Expr::boolean(true)),
+                _ => Ok(Expr::boolean(false)),
+            }
+        },
+    );
+
+    env.defn(
+        "atom",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            Ok(Expr::atom(resolved[0].clone()))
+        },
+    );
+
+    env.defn(
+        "deref",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::Atom(atom) => Ok(atom.get()),
+                invalid => Err(HError::InvalidType("deref".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "reset!",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match &resolved[0] {
+                Expr::Atom(atom) => {
+                    atom.replace(resolved[1].clone());
+                    Ok(resolved[1].clone())
+                }
+                invalid => Err(HError::InvalidType("reset!".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "swap!",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            match &resolved[0] {
+                Expr::Atom(atom) => {
+                    let current = atom.get();
+                    let mut fn_args = vec![current];
+                    fn_args.extend_from_slice(&resolved[2..]);
+                    let value = apply(&resolved[1], &fn_args, env)?;
+                    atom.replace(value.clone());
+                    Ok(value)
+                }
+                invalid => Err(HError::InvalidType("swap!".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "arity",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let arity = match &resolve_args(args, env)?[0] {
+                Expr::NativeFn(fun) => fun.arity.clone(),
+                Expr::Fn(fun) => fun.arity.clone(),
+                Expr::Closure(fun) => fun.arity.clone(),
+                Expr::Method(method) => method.arity.clone(),
+                invalid => return Err(HError::InvalidType("arity".to_string(), invalid.clone())),
+            };
+            let (min, max) = match arity {
+                Arity::Count(count) => (count, count),
+                Arity::Range(min, max) => (min, max),
+            };
+            // Variadic functions have no upper bound.
+            let max = match max {
+                usize::MAX => Expr::Nil,
+                max => Expr::number(max as f64),
+            };
+            Ok(Expr::map(&[
+                (Expr::keyword(":min"), Expr::number(min as f64)),
+                (Expr::keyword(":max"), max),
+            ]))
+        },
+    );
+
+    env.defn(
+        "params",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::NativeFn(_) | Expr::Closure(_) => Ok(Expr::Nil),
+                Expr::Fn(fun) => Ok(Expr::vector(&fun.args)),
+                Expr::Method(method) => Ok(Expr::vector(&method.args)),
+                invalid => Err(HError::InvalidType("params".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "fn?",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            Ok(Expr::boolean(matches!(
+                resolved[0],
+                Expr::NativeFn(_) | Expr::Fn(_) | Expr::Closure(_)
+            )))
+        },
+    );
+
+    env.defn(
+        "partial",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut resolved = resolve_args(args, env)?;
+            let fun = resolved.remove(0);
+            Ok(Expr::closure(
+                "partial",
+                Arity::Range(0, usize::MAX),
+                move |args: &[Expr], env: EnvRef| {
+                    let mut all = resolved.clone();
+                    all.extend_from_slice(args);
+                    apply(&fun, &all, env)
+                },
+            ))
+        },
+    );
+
+    env.defn(
+        "apply",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut resolved = resolve_args(args, env.clone_ref())?;
+            let fun = resolved.remove(0);
+            // The last argument is spread into the call.
+            match resolved.pop() {
+                Some(Expr::Vector(rest)) => resolved.extend(rest),
+                Some(invalid) => return Err(HError::InvalidType("apply".to_string(), invalid)),
+                None => (),
+            }
+            apply(&fun, &resolved, env)
+        },
+    );
+
+    env.defn(
+        "comp",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let funs = resolve_args(args, env)?;
+            Ok(Expr::closure(
+                "comp",
+                Arity::Range(0, usize::MAX),
+                move |args: &[Expr], env: EnvRef| {
+                    // The last function takes every argument, the rest one each.
+                    let (last, rest) = funs.split_last().unwrap();
+                    let mut result = apply(last, args, env.clone_ref())?;
+                    for fun in rest.iter().rev() {
+                        result = apply(fun, std::slice::from_ref(&result), env.clone_ref())?;
+                    }
+                    Ok(result)
+                },
+            ))
+        },
+    );
+
+    env.defn(
+        "curry",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            curry(resolve_args(args, env)?.remove(0))
+        },
+    );
+
+    env.defn(
+        "macroexpand-1",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let form = resolve_args(args, env.clone_ref())?.remove(0);
+            Ok(macroexpand_1(&form, &env)?.unwrap_or(form))
+        },
+    );
+
+    env.defn(
+        "macroexpand",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut form = resolve_args(args, env.clone_ref())?.remove(0);
+            while let Some(expansion) = macroexpand_1(&form, &env)? {
+                form = expansion;
+            }
+            Ok(form)
+        },
+    );
+
+    env.defn(
+        "gensym",
+        Arity::Range(0, 1),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
-            let resolved = resolve_args(args, env)?;
-            match (&resolved[0], &resolved[1]) {
-                (Expr::Number(min), Expr::Number(max)) => {
-                    let min = **min as i64;
-                    let max = **max as i64;
-                    Ok(Expr::Vector(
-                        (min..max).map(|n| Expr::number(n as f64)).collect(),
-                    ))
-                }
-                (min, max) => Err(HError::UnexpectedForm(
-                    "Invalid range".to_string(),
-                    Expr::vector(&[min.clone(), max.clone()]),
-                )),
+            match resolve_args(args, env)?.first() {
+                None => Ok(Expr::symbol(&gensym("G"))),
+                Some(Expr::String(prefix)) => Ok(Expr::symbol(&gensym(prefix))),
+                Some(invalid) => Err(HError::InvalidType("gensym".to_string(), invalid.clone())),
             }
         },
     );
 
+    env.def("*print-length*", Expr::Nil);
+    env.def("*print-depth*", Expr::Nil);
+    env.def("*print-precision*", Expr::Nil);
+    env.def("*print-readably*", Expr::Nil);
+    // Whether `^type` hints on fn parameters are checked when called.
+    env.def("*check-types*", Expr::Nil);
+    // Whether `get` raises an error past the end of a vector instead of
+    // returning nil.
+    env.def("*check-bounds*", Expr::Nil);
+
+    env.defn(
+        "pr-str",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let limits = PrintLimits::from_env(&env).readably();
+            let resolved = resolve_args(args, env)?;
+            let text = limits.scope(|| {
+                resolved
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            });
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
+        },
+    );
+
     env.defn(
-        "number?",
-        Arity::Count(1),
+        "str",
+        Arity::Range(0, usize::MAX),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let limits = PrintLimits::from_env(&env);
             let resolved = resolve_args(args, env)?;
-            match resolved[0] {
-                Expr::Number(_) => Ok(Expr::boolean(true)),
-                _ => Ok(Expr::boolean(false)),
-            }
+            let text = limits.scope(|| resolved.iter().map(|e| e.to_string()).collect::<String>());
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
         },
     );
 
@@ -145,7 +850,7 @@ pub fn native_module() -> Env {
         Arity::Range(0, usize::MAX),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
             print_resolved_exprs(args, env)?;
-            println!();
+            write_output("\n");
             Ok(Expr::Nil)
         },
     );
@@ -153,9 +858,86 @@ pub fn native_module() -> Env {
     env
 }
 
+fn check_vector<'a>(expr: &'a Expr, fun_name: &str) -> Result<&'a Vec<Expr>, HError> {
+    match expr {
+        Expr::Vector(vec) => Ok(vec),
+        _ => Err(HError::InvalidType(fun_name.to_string(), expr.clone())),
+    }
+}
+
+fn check_map<'a>(expr: &'a Expr, fun_name: &str) -> Result<&'a BTreeMap<Expr, Expr>, HError> {
+    match expr {
+        Expr::Map(map) => Ok(map),
+        _ => Err(HError::InvalidType(fun_name.to_string(), expr.clone())),
+    }
+}
+
+/// Reads a position in a vector of length `len`, from 0 up to and
+/// including `len`.
+pub(crate) fn check_index(expr: &Expr, len: usize, fun_name: &str) -> Result<usize, HError> {
+    let index = check_num(expr, fun_name)?;
+    if index.fract() != 0. {
+        return Err(HError::InvalidType(fun_name.to_string(), expr.clone()));
+    }
+    if index < 0. || index > len as f64 {
+        return Err(HError::IndexOutOfBounds(
+            fun_name.to_string(),
+            expr.clone(),
+            len,
+        ));
+    }
+    Ok(index as usize)
+}
+
+/// Like `check_index`, but negative positions count back from the end.
+fn check_slice_index(expr: &Expr, len: usize) -> Result<usize, HError> {
+    match expr {
+        Expr::Number(index) if **index < 0. && **index >= -(len as f64) => {
+            check_index(&Expr::number(**index + len as f64), len, "slice")
+                .map_err(|_| HError::InvalidType("slice".to_string(), expr.clone()))
+        }
+        _ => check_index(expr, len, "slice"),
+    }
+}
+
+/// Turns a function of n arguments into n nested functions of one argument.
+pub fn curry(fun: Expr) -> Result<Expr, HError> {
+    let arity = match &fun {
+        Expr::NativeFn(native) => native.arity.clone(),
+        Expr::Fn(fun) => fun.arity.clone(),
+        Expr::Closure(closure) => closure.arity.clone(),
+        invalid => return Err(HError::InvalidType("curry".to_string(), invalid.clone())),
+    };
+    match arity {
+        Arity::Count(count) if count > 1 => Ok(curried(fun, count, Vec::new())),
+        Arity::Count(_) => Ok(fun),
+        Arity::Range(_, _) => Err(HError::UnexpectedForm(
+            "Only functions with a fixed arity can be curried".to_string(),
+            fun,
+        )),
+    }
+}
+
+fn curried(fun: Expr, remaining: usize, collected: Vec<Expr>) -> Expr {
+    Expr::closure(
+        "curry",
+        Arity::Count(1),
+        move |args: &[Expr], env: EnvRef| {
+            let mut collected = collected.clone();
+            collected.push(args[0].clone());
+            if remaining == 1 {
+                apply(&fun, &collected, env)
+            } else {
+                Ok(curried(fun.clone(), remaining - 1, collected))
+            }
+        },
+    )
+}
+
 fn print_resolved_exprs(exprs: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    let limits = PrintLimits::from_env(&env);
     for expr in resolve_args(exprs, env)? {
-        print!("{}", expr);
+        limits.scope(|| write_output(&expr.to_string()));
     }
     Ok(Expr::Nil)
 }
@@ -163,11 +945,16 @@ fn print_resolved_exprs(exprs: &[Expr], env: EnvRef) -> Result<Expr, HError> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{env::Env, evaluator::eval};
+    use crate::{
+        env::Env,
+        evaluator::eval,
+        interpreter::{Interpreter, Options},
+    };
+    use std::{cell::RefCell, rc::Rc};
 
     #[test]
     fn test_add_op() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(+ 1 2 3 4 5)", env.clone_ref()),
@@ -179,28 +966,28 @@ mod tests {
 
     #[test]
     fn test_sub_op() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(eval("(- 1 2 3 4 5)", env), Ok(Expr::number(-13.)));
     }
 
     #[test]
     fn test_mul_op() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(eval("(* 1 2 3 4 5)", env), Ok(Expr::number(120.)));
     }
 
     #[test]
     fn test_div_op() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(eval("(/ 20 2 2)", env), Ok(Expr::number(5.)));
     }
 
     #[test]
     fn test_eq() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(= 1 1)", env_ref.clone_ref()),
@@ -230,7 +1017,7 @@ mod tests {
 
     #[test]
     fn test_not_eq() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(!= 1 1)", env_ref.clone_ref()),
@@ -263,7 +1050,7 @@ mod tests {
 
     #[test]
     fn test_lt_op() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(lt 1 2 3 4 5)", env_ref.clone_ref()),
@@ -277,7 +1064,7 @@ mod tests {
 
     #[test]
     fn test_lt_eq_op() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(lte 2 2)", env_ref.clone_ref()),
@@ -295,7 +1082,7 @@ mod tests {
 
     #[test]
     fn test_gt_op() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(gt 5 4 3 2 1)", env_ref.clone_ref()),
@@ -309,7 +1096,7 @@ mod tests {
 
     #[test]
     fn test_gt_eq_op() {
-        let env_ref = Env::with_core_module().into_ref();
+        let env_ref = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(gte 2 2)", env_ref.clone_ref()),
@@ -327,7 +1114,7 @@ mod tests {
 
     #[test]
     fn test_get() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(get {:hello 3} :hello)", env.clone_ref()),
@@ -343,11 +1130,30 @@ mod tests {
             Ok(Expr::number(3.))
         );
         assert_eq!(eval("(get [1 2 3] 3)", env.clone_ref()), Ok(Expr::nil()));
+        assert_eq!(
+            eval("(get [1 2 3] -1)", env.clone_ref()),
+            Ok(Expr::number(3.))
+        );
+        assert_eq!(eval("(get [1 2 3] -4)", env.clone_ref()), Ok(Expr::nil()));
+
+        eval("(set! *check-bounds* true)", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(get [1 2 3] -4)", env.clone_ref()),
+            Err(HError::IndexOutOfBounds(
+                "get".to_string(),
+                Expr::number(-4.),
+                3
+            ))
+        );
+        assert_eq!(
+            eval("(get {:hello 3} :world)", env.clone_ref()),
+            Ok(Expr::nil())
+        );
     }
 
     #[test]
     fn test_range() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(range 0 3)", env.clone_ref()),
@@ -359,9 +1165,446 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pr_str() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(pr-str [1 2] {:a 3})", env.clone_ref()),
+            Ok(Expr::string("[1 2] {:a 3}"))
+        );
+
+        // Printed strings read back as the same string.
+        let original = eval(r#"(str "say \"hi\"\n\t" "\\ \#{x}")"#, env.clone_ref()).unwrap();
+        let printed = eval(
+            r#"(pr-str (str "say \"hi\"\n\t" "\\ \#{x}"))"#,
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(printed, Expr::string(r#""say \"hi\"\n\t\\ \#{x}""#));
+        assert_eq!(eval(&printed.to_string(), env), Ok(original));
+    }
+
+    #[test]
+    fn test_print_and_pr_str_formats() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let interpreter = Interpreter::with_options(Options {
+            output: Some(output.clone()),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval("(pr-str \"a\nb\" [\"c\"])"),
+            Ok(Expr::string("\"a\\nb\" [\"c\"]"))
+        );
+
+        interpreter
+            .eval(
+                "
+                (print \"a\" [\"b\"] 1.25)
+                (set! *print-readably* true)
+                (set! *print-precision* 1)
+                (print \" \" \"a\" 1.25)",
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            "a[b]1.25\" \"\"a\"1.2"
+        );
+    }
+
+    #[test]
+    fn test_pr_str_honors_print_limits() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(set! *print-length* 2)", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(pr-str (range 0 1000))", env.clone_ref()),
+            Ok(Expr::string("[0 1 ...]"))
+        );
+
+        eval("(set! *print-length* nil)", env.clone_ref()).unwrap();
+        eval("(set! *print-depth* 2)", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(pr-str [1 [2 [3 [4]]]])", env.clone_ref()),
+            Ok(Expr::string("[1 [2 #]]"))
+        );
+    }
+
+    #[test]
+    fn test_atoms() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var a (atom 1))", env.clone_ref()).unwrap();
+        assert_eq!(eval("(deref a)", env.clone_ref()), Ok(Expr::number(1.)));
+        assert_eq!(eval("(reset! a 5)", env.clone_ref()), Ok(Expr::number(5.)));
+        assert_eq!(
+            eval("(swap! a + 2 3)", env.clone_ref()),
+            Ok(Expr::number(10.))
+        );
+        assert_eq!(eval("(deref a)", env.clone_ref()), Ok(Expr::number(10.)));
+    }
+
+    #[test]
+    fn test_prints_cyclic_atoms() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var a (atom nil)) (reset! a [1 a])", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(pr-str a)", env.clone_ref()),
+            Ok(Expr::string("#atom[[1 #cycle]]"))
+        );
+    }
+
+    #[test]
+    fn test_try_call_returns_pairs() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(try-call + 1 2)", env.clone_ref()),
+            eval("(values true 3)", env.clone_ref())
+        );
+        eval("(var [ok message] (try-call get 1 2))", env.clone_ref()).unwrap();
+        assert_eq!(eval("ok", env.clone_ref()), Ok(Expr::boolean(false)));
+        assert!(matches!(
+            eval("message", env.clone_ref()),
+            Ok(Expr::String(message)) if message.contains("Type does not support `get`")
+        ));
+        assert_eq!(
+            eval("(error-code message)", env.clone_ref()),
+            Ok(Expr::string("E004"))
+        );
+        assert_eq!(eval("(error-code \"oops\")", env), Ok(Expr::Nil));
+    }
+
+    #[test]
+    fn test_qualified_keywords() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "[(namespace :user/name) (name :user/name) (namespace :id) (name :id)]",
+                env.clone_ref()
+            ),
+            eval("[\"user\" \"name\" nil \"id\"]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(get {:user/id 1 :id 2} :user/id)", env.clone_ref()),
+            Ok(Expr::number(1.))
+        );
+        assert_eq!(
+            eval("(name 1)", env),
+            Err(HError::InvalidType("name".to_string(), Expr::number(1.)))
+        );
+    }
+
+    #[test]
+    fn test_vector_slicing() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v [1 2 3 4])", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval(
+                "[(slice v 1 3) (take 2 v) (drop 3 v) (slice v 4 4)]",
+                env.clone_ref()
+            ),
+            eval("[[2 3] [1 2] [4] []]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("[(insert-at v 4 5) (remove-at v 0) v]", env.clone_ref()),
+            eval("[[1 2 3 4 5] [2 3 4] [1 2 3 4]]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(slice v 2 5)", env.clone_ref()),
+            Err(HError::IndexOutOfBounds(
+                "slice".to_string(),
+                Expr::number(5.),
+                4
+            ))
+        );
+        assert_eq!(
+            eval("(remove-at v 4)", env.clone_ref()),
+            Err(HError::IndexOutOfBounds(
+                "remove-at".to_string(),
+                Expr::number(4.),
+                4
+            ))
+        );
+        assert!(eval("(slice v 3 1)", env.clone_ref()).is_err());
+        assert_eq!(
+            eval(
+                "[(slice v -3 -1) (slice v 1 -1) (slice v -4 4)]",
+                env.clone_ref()
+            ),
+            eval("[[2 3] [2 3] [1 2 3 4]]", env.clone_ref())
+        );
+        assert!(eval("(slice v -5 2)", env.clone_ref()).is_err());
+        assert_eq!(
+            eval("(take 1.5 v)", env),
+            Err(HError::InvalidType("take".to_string(), Expr::number(1.5)))
+        );
+    }
+
+    #[test]
+    fn test_vector_manipulation() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v [3 1 2])", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval(
+                "[(conj v 4 5) (push v 4) (pop v) (concat v [] [4]) v]",
+                env.clone_ref()
+            ),
+            eval(
+                "[[3 1 2 4 5] [3 1 2 4] [3 1] [3 1 2 4] [3 1 2]]",
+                env.clone_ref()
+            )
+        );
+        assert_eq!(
+            eval(
+                "[(first v) (rest v) (last v) (first []) (rest []) (last [])]",
+                env.clone_ref()
+            ),
+            eval("[3 [1 2] 2 nil [] nil]", env.clone_ref())
+        );
+        assert_eq!(
+            eval(
+                "[(nth v 1) (nth v 3 :none) (nth v -1 :none)]",
+                env.clone_ref()
+            ),
+            eval("[1 :none :none]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(nth v 3)", env.clone_ref()),
+            Err(HError::IndexOutOfBounds(
+                "nth".to_string(),
+                Expr::number(3.),
+                3
+            ))
+        );
+        assert_eq!(
+            eval("(pop [])", env.clone_ref()),
+            Err(HError::EmptyCollection("pop".to_string()))
+        );
+        assert!(matches!(
+            eval("(first \"abc\")", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_map_manipulation() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var m {:a 1 :b 2})", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval(
+                "[(assoc m :c 3 :a 0) (dissoc m :a :x) (merge m {:b 3} {:d 4}) (merge) m]",
+                env.clone_ref()
+            ),
+            eval(
+                "[{:a 0 :b 2 :c 3} {:b 2} {:a 1 :b 3 :d 4} {} {:a 1 :b 2}]",
+                env.clone_ref()
+            )
+        );
+        assert_eq!(
+            eval(
+                "[(keys m) (vals m) (contains-key? m :a) (contains-key? m :z)]",
+                env.clone_ref()
+            ),
+            eval("[[:a :b] [1 2] true false]", env.clone_ref())
+        );
+        assert_eq!(
+            eval(
+                "[(update m :a + 10) (update m :n (fn [n] (if n n 0))) (select-keys m [:b :z])]",
+                env.clone_ref()
+            ),
+            eval("[{:a 11 :b 2} {:a 1 :b 2 :n 0} {:b 2}]", env.clone_ref())
+        );
+        assert!(matches!(
+            eval("(assoc m :c)", env.clone_ref()),
+            Err(HError::InvalidArity(..))
+        ));
+        assert!(matches!(
+            eval("(assoc m :c 1 :d)", env.clone_ref()),
+            Err(HError::UnexpectedForm(..))
+        ));
+        assert!(matches!(
+            eval("(keys [1])", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_vector_ordering_and_search() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "[(reverse [1 2 3]) (sort [3 1 2]) (sort [\"b\" \"a\"])]",
+                env.clone_ref()
+            ),
+            eval("[[3 2 1] [1 2 3] [\"a\" \"b\"]]", env.clone_ref())
+        );
+        assert_eq!(
+            eval(
+                "(sort-by (fn [p] (get p :age)) [{:age 3 :n 1} {:age 1} {:age 3 :n 2}])",
+                env.clone_ref()
+            ),
+            eval("[{:age 1} {:age 3 :n 1} {:age 3 :n 2}]", env.clone_ref())
+        );
+        assert_eq!(
+            eval(
+                "[(contains? [1 :a] :a) (contains? [1] 2) (contains? \"huck\" \"uc\")]",
+                env.clone_ref()
+            ),
+            eval("[true false true]", env.clone_ref())
+        );
+        assert!(matches!(
+            eval("(contains? \"huck\" 1)", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_prints_fns() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(defn add [a b] (+ a b))", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval(
+                "(pr-str add (fn [a & rest] a) (fn f [n] n) range (partial add 1))",
+                env.clone_ref()
+            ),
+            Ok(Expr::string(
+                "#fn[add 2 args] #fn[1+ args] #fn[f 1 arg] #fn[range 2 args] #fn[partial 0+ args]"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_arity() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(arity (fn [a b] a))", env.clone_ref()),
+            eval("{:min 2 :max 2}", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(arity (fn [a & rest] a))", env.clone_ref()),
+            eval("{:min 1 :max nil}", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(arity range)", env.clone_ref()),
+            eval("{:min 2 :max 2}", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(arity 1)", env),
+            Err(HError::InvalidType("arity".to_string(), Expr::number(1.)))
+        );
+    }
+
+    #[test]
+    fn test_partial() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var add-3 (partial + 1 2))
+            (var double <(partial *) partial: 2>)
+            (var add-3-method <+ partial: 1 2>)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(add-3 4 5)", env.clone_ref()), Ok(Expr::number(12.)));
+        assert_eq!(eval("(double 5)", env.clone_ref()), Ok(Expr::number(10.)));
+        assert_eq!(
+            eval("(add-3-method 4 5)", env.clone_ref()),
+            Ok(Expr::number(12.))
+        );
+        assert_eq!(eval("(fn? add-3)", env), Ok(Expr::boolean(true)));
+    }
+
+    #[test]
+    fn test_apply() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(apply + [1 2 3])", env.clone_ref()),
+            Ok(Expr::number(6.))
+        );
+        assert_eq!(
+            eval("(apply + 1 2 [3])", env.clone_ref()),
+            Ok(Expr::number(6.))
+        );
+        assert_eq!(
+            eval("(apply + 1)", env),
+            Err(HError::InvalidType("apply".to_string(), Expr::number(1.)))
+        );
+    }
+
+    #[test]
+    fn test_comp() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var double-sum (comp (partial * 2) +))
+            (var dec-double (comp (fn [n] (- n 1)) (partial * 2)))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(double-sum 1 2)", env.clone_ref()),
+            Ok(Expr::number(6.))
+        );
+        assert_eq!(eval("(dec-double 5)", env), Ok(Expr::number(9.)));
+    }
+
+    #[test]
+    fn test_curry() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var pair (curry (fn [a b] [a b])))
+            (var pair-1 (pair 1))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(pair-1 2)", env.clone_ref()),
+            Ok(Expr::vector(&[Expr::number(1.), Expr::number(2.)]))
+        );
+        assert_eq!(
+            eval("(curry number?)", env.clone_ref()),
+            eval("number?", env.clone_ref())
+        );
+        assert!(eval("(curry println)", env).is_err());
+    }
+
+    #[test]
+    fn test_params() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(params (fn [a & rest] a))", env.clone_ref()),
+            Ok(Expr::vector(&[
+                Expr::symbol("a"),
+                Expr::ampersand(),
+                Expr::symbol("rest")
+            ]))
+        );
+        assert_eq!(eval("(params println)", env), Ok(Expr::Nil));
+    }
+
     #[test]
     fn test_number_q() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("[(number? 3.2) (number? \"a\")]", env.clone_ref()),
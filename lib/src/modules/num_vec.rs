@@ -0,0 +1,160 @@
+use ordered_float::OrderedFloat;
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::check_collection_len,
+    modules::utils::check_num,
+};
+
+/// Num-vecs hold plain numbers rather than values, so bulk math on them
+/// doesn't box each element. `+`, `-`, `*`, and `/` work on them element by
+/// element. `dot` and the statistics module take them too.
+pub fn num_vec_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "num-vec",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = numbers("num-vec", &resolve_args(args, env)?[0])?;
+            check_collection_len(values.len())?;
+            Ok(Expr::NumVec(values))
+        },
+    );
+
+    env.defn(
+        "to-vector",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::NumVec(values) => Ok(Expr::Vector(
+                    values.iter().map(|value| Expr::Number(*value)).collect(),
+                )),
+                invalid => Err(HError::InvalidType(
+                    "to-vector".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "dot",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let a = numbers("dot", &resolved[0])?;
+            let b = numbers("dot", &resolved[1])?;
+            if a.len() != b.len() {
+                return Err(HError::InvalidType("dot".to_string(), resolved[1].clone()));
+            }
+            Ok(Expr::number(a.iter().zip(&b).map(|(a, b)| **a * **b).sum()))
+        },
+    );
+
+    env
+}
+
+/// The elements of a num-vec, or of a vector holding only numbers.
+pub(crate) fn numbers(name: &str, expr: &Expr) -> Result<Vec<OrderedFloat<f64>>, HError> {
+    match expr {
+        Expr::NumVec(values) => Ok(values.clone()),
+        Expr::Vector(values) => values
+            .iter()
+            .map(|value| check_num(value, name).map(OrderedFloat))
+            .collect(),
+        invalid => Err(HError::InvalidType(name.to_string(), invalid.clone())),
+    }
+}
+
+/// Applies `op` element by element to `operands`, which are num-vecs of one
+/// length or numbers applied to every element, as in `(* 2 v)`.
+pub(crate) fn element_wise(
+    name: &str,
+    operands: &[Expr],
+    op: fn(f64, f64) -> f64,
+) -> Result<Expr, HError> {
+    let len = operands
+        .iter()
+        .find_map(|operand| match operand {
+            Expr::NumVec(values) => Some(values.len()),
+            _ => None,
+        })
+        .unwrap_or(1);
+    let element = |operand: &Expr, i: usize| -> Result<f64, HError> {
+        match operand {
+            Expr::NumVec(values) if values.len() == len => Ok(*values[i]),
+            Expr::Number(value) => Ok(**value),
+            invalid => Err(HError::InvalidType(name.to_string(), invalid.clone())),
+        }
+    };
+    let mut result = Vec::with_capacity(len);
+    for i in 0..len {
+        let mut acc = element(&operands[0], i)?;
+        for operand in &operands[1..] {
+            acc = op(acc, element(operand, i)?);
+        }
+        result.push(OrderedFloat(acc));
+    }
+    Ok(Expr::NumVec(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{env::Env, error::HError, evaluator::eval, expr::Expr};
+
+    #[test]
+    fn test_element_wise_math() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v (num-vec [1 2 3]))", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(+ v v 1)", env.clone_ref()),
+            eval("(num-vec [3 5 7])", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(to-vector (/ (* 2 v) 4))", env.clone_ref()),
+            eval("[0.5 1 1.5]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(- v)", env.clone_ref()),
+            eval("(num-vec [1 2 3])", env.clone_ref())
+        );
+        assert!(matches!(
+            eval("(+ v (num-vec [1 2]))", env.clone_ref()),
+            Err(HError::InvalidType(..))
+        ));
+        assert!(matches!(
+            eval("(+ v [1 2 3])", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_dot() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v (num-vec [1 2 3 4]))", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(dot v [1 0 1 0])", env.clone_ref()),
+            Ok(Expr::number(4.))
+        );
+        assert!(matches!(
+            eval("(num-vec [1 :a])", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_displays_num_vecs() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(num-vec [1 2.5])", env).unwrap().to_string(),
+            "#num-vec[1 2.5]"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    cassette::recorded,
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::check_deterministic,
+};
+
+pub fn os_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "getenv",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match &resolved[0] {
+                Expr::String(name) => recorded("getenv", &resolved, || {
+                    check_deterministic("getenv")?;
+                    Ok(std::env::var(name)
+                        .map(|value| Expr::string(&value))
+                        .unwrap_or(Expr::Nil))
+                }),
+                invalid => Err(HError::InvalidType("getenv".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env.defn(
+        "expand",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            match &resolved[0] {
+                Expr::String(text) => recorded("expand", &resolved, || {
+                    check_deterministic("expand")?;
+                    Ok(Expr::String(expand(text, |name| std::env::var(name).ok())?))
+                }),
+                invalid => Err(HError::InvalidType("expand".to_string(), invalid.clone())),
+            }
+        },
+    );
+
+    env
+}
+
+/// Substitutes `$NAME` and `${NAME}` with values from `lookup`, or nothing
+/// when unset. `$$` is a literal `$`.
+pub fn expand(text: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, HError> {
+    let mut result = String::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let name = match chars.peek() {
+            Some((_, '$')) => {
+                chars.next();
+                result.push('$');
+                continue;
+            }
+            Some((_, '{')) => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, c)) => name.push(c),
+                        None => {
+                            return Err(HError::UnexpectedForm(
+                                "Unclosed ${ in expand".to_string(),
+                                Expr::string(text),
+                            ))
+                        }
+                    }
+                }
+                name
+            }
+            _ => {
+                let mut name = String::new();
+                while let Some((_, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || *c == '_') {
+                        break;
+                    }
+                    name.push(*c);
+                    chars.next();
+                }
+                name
+            }
+        };
+        // A lone `$` is kept as is.
+        if name.is_empty() {
+            result.push('$');
+        } else {
+            result.push_str(&lookup(&name).unwrap_or_default());
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/ada".to_string()),
+            "USER" => Some("ada".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            expand("Home is $HOME and ${USER}!", lookup),
+            Ok("Home is /home/ada and ada!".to_string())
+        );
+        assert_eq!(
+            expand("$$HOME costs $5 $ $UNSET.", lookup),
+            Ok("$HOME costs  $ .".to_string())
+        );
+        assert_eq!(
+            expand("${USER", lookup),
+            Err(HError::UnexpectedForm(
+                "Unclosed ${ in expand".to_string(),
+                Expr::string("${USER")
+            ))
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::eval_expr,
+    expr::Expr,
+};
+
+/// Evaluates the subject of a `match` form's `args`, then finds the first arm
+/// whose pattern matches it. Returns that arm's body along with a scope
+/// binding the pattern's names, or `None` if no pattern matches.
+pub(crate) fn select_arm(args: &[Expr], env: EnvRef) -> Result<Option<(&Expr, EnvRef)>, HError> {
+    if args.len().is_multiple_of(2) {
+        return Err(HError::UnexpectedForm(
+            "\"match\" requires a body for each pattern".to_string(),
+            Expr::Vector(args.to_vec()),
+        ));
+    }
+    let value = eval_expr(&args[0], env.clone_ref())?;
+    for arm in args[1..].chunks(2) {
+        let mut bindings = vec![];
+        if match_pattern(&arm[0], &value, &mut bindings)? {
+            let mut arm_env = Env::extend(env);
+            for (name, value) in bindings {
+                arm_env.def(&name, value);
+            }
+            return Ok(Some((&arm[1], arm_env.into_ref())));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `value` matches `pattern`, adding the names the pattern binds to
+/// `bindings` as it goes. Patterns are:
+/// - `_`, which matches anything, and symbols, which bind what they match.
+/// - Literals, which match equal values, and quoted forms, which match the
+///   form itself.
+/// - Vectors, which match vectors of the same length element by element, or
+///   at least as long when ending in `& rest`, which binds the rest.
+/// - Maps of keys to patterns, which match maps holding each key with a value
+///   matching its pattern.
+pub(crate) fn match_pattern(
+    pattern: &Expr,
+    value: &Expr,
+    bindings: &mut Vec<(String, Expr)>,
+) -> Result<bool, HError> {
+    match pattern {
+        Expr::Symbol(name) if name == "_" => Ok(true),
+        Expr::Symbol(name) => {
+            bindings.push((name.to_string(), value.clone()));
+            Ok(true)
+        }
+        Expr::Number(_) | Expr::String(_) | Expr::Keyword(_) | Expr::Boolean(_) | Expr::Nil => {
+            Ok(pattern == value)
+        }
+        Expr::List(list) => match list.as_slice() {
+            [Expr::Symbol(head), form] if head == "quote" => Ok(form == value),
+            _ => Err(invalid_pattern(pattern)),
+        },
+        Expr::Vector(patterns) => {
+            let values = match value {
+                Expr::Vector(values) => values,
+                _ => return Ok(false),
+            };
+            let (patterns, rest) = match patterns.iter().position(|p| p == &Expr::Ampersand) {
+                Some(i) if i + 2 == patterns.len() => (&patterns[..i], Some(&patterns[i + 1])),
+                Some(_) => return Err(invalid_pattern(pattern)),
+                None => (&patterns[..], None),
+            };
+            let fits = match rest {
+                Some(_) => values.len() >= patterns.len(),
+                None => values.len() == patterns.len(),
+            };
+            if !fits {
+                return Ok(false);
+            }
+            for (pattern, value) in patterns.iter().zip(values) {
+                if !match_pattern(pattern, value, bindings)? {
+                    return Ok(false);
+                }
+            }
+            match rest {
+                Some(rest) => {
+                    let remaining = Expr::Vector(values[patterns.len()..].to_vec());
+                    match_pattern(rest, &remaining, bindings)
+                }
+                None => Ok(true),
+            }
+        }
+        Expr::Map(fields) => {
+            let entries = match value {
+                Expr::Map(entries) => entries,
+                _ => return Ok(false),
+            };
+            for (key, pattern) in fields {
+                match entries.get(key) {
+                    Some(value) if match_pattern(pattern, value, bindings)? => (),
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        _ => Err(invalid_pattern(pattern)),
+    }
+}
+
+fn invalid_pattern(pattern: &Expr) -> HError {
+    HError::UnexpectedForm("Invalid pattern".to_string(), pattern.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn bindings(pattern: &str, value: &str) -> Option<Vec<(String, Expr)>> {
+        let pattern = parse(pattern).unwrap().remove(0);
+        let value = parse(value).unwrap().remove(0);
+        let mut bindings = vec![];
+        match_pattern(&pattern, &value, &mut bindings)
+            .unwrap()
+            .then_some(bindings)
+    }
+
+    #[test]
+    fn test_matches_literals_and_wildcards() {
+        assert_eq!(bindings("1", "1"), Some(vec![]));
+        assert_eq!(bindings(":a", ":b"), None);
+        assert_eq!(bindings("_", "[1 2]"), Some(vec![]));
+        assert_eq!(bindings("'x", "x"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_binds_vectors_and_maps() {
+        assert_eq!(
+            bindings("[x [_ y]]", "[1 [2 3]]"),
+            Some(vec![
+                ("x".to_string(), Expr::number(1.)),
+                ("y".to_string(), Expr::number(3.))
+            ])
+        );
+        assert_eq!(bindings("[x y]", "[1 2 3]"), None);
+        assert_eq!(
+            bindings("[x & rest]", "[1 2 3]"),
+            Some(vec![
+                ("x".to_string(), Expr::number(1.)),
+                (
+                    "rest".to_string(),
+                    Expr::vector(&[Expr::number(2.), Expr::number(3.)])
+                )
+            ])
+        );
+        assert_eq!(
+            bindings("{:type :circle :r r}", "{:type :circle :r 2 :x 0}"),
+            Some(vec![("r".to_string(), Expr::number(2.))])
+        );
+        assert_eq!(bindings("{:r r}", "{:x 0}"), None);
+    }
+
+    #[test]
+    fn test_rejects_invalid_patterns() {
+        let mut bindings = vec![];
+        let pattern = parse("(f x)").unwrap().remove(0);
+
+        assert!(matches!(
+            match_pattern(&pattern, &Expr::Nil, &mut bindings),
+            Err(HError::UnexpectedForm(..))
+        ));
+    }
+}
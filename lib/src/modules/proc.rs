@@ -0,0 +1,277 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdout, Command, Stdio},
+    rc::Rc,
+    thread,
+};
+
+use crate::{
+    cassette::recorded,
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::{check_deterministic, check_string_len},
+};
+
+pub(crate) struct Proc {
+    child: Child,
+    stdout: Option<BufReader<ChildStdout>>,
+}
+
+/// The processes an interpreter has spawned and not yet waited on.
+pub(crate) type Procs = Rc<RefCell<BTreeMap<usize, Proc>>>;
+
+thread_local! {
+    static PROCS: RefCell<Procs> = RefCell::new(Rc::default());
+    static NEXT_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Processes are referred to by numeric handles. Their stdin and stdout are
+/// piped to the script, and stderr is shared with the interpreter.
+pub fn proc_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "proc/spawn",
+        Arity::Range(1, 2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let program = string_arg("proc/spawn", &resolved[0])?;
+            let args = match resolved.get(1) {
+                Some(Expr::Vector(args)) => args
+                    .iter()
+                    .map(|arg| string_arg("proc/spawn", arg))
+                    .collect::<Result<Vec<_>, _>>()?,
+                Some(invalid) => {
+                    return Err(HError::InvalidType(
+                        "proc/spawn".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+                None => vec![],
+            };
+
+            recorded("proc/spawn", &resolved, || {
+                check_deterministic("proc/spawn")?;
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .map_err(io_error)?;
+                let stdout = child.stdout.take().map(BufReader::new);
+                let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+                procs().borrow_mut().insert(id, Proc { child, stdout });
+                Ok(Expr::number(id as f64))
+            })
+        },
+    );
+
+    env.defn(
+        "proc/stdin-write",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let text = string_arg("proc/stdin-write", &resolved[1])?;
+            recorded("proc/stdin-write", &resolved, || {
+                with_proc(&resolved[0], |proc| {
+                    let stdin = proc.child.stdin.as_mut().ok_or_else(|| {
+                        HError::IoError("The process's stdin is closed".to_string())
+                    })?;
+                    stdin.write_all(text.as_bytes()).map_err(io_error)?;
+                    stdin.flush().map_err(io_error)
+                })?;
+                Ok(Expr::Nil)
+            })
+        },
+    );
+
+    env.defn(
+        "proc/stdin-close",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            recorded("proc/stdin-close", &resolved, || {
+                with_proc(&resolved[0], |proc| {
+                    proc.child.stdin.take();
+                    Ok(Expr::Nil)
+                })
+            })
+        },
+    );
+
+    env.defn(
+        "proc/read-line",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            recorded("proc/read-line", &resolved, || {
+                with_proc(&resolved[0], read_line)
+            })
+        },
+    );
+
+    env.defn(
+        "proc/wait",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            recorded("proc/wait", &resolved, || {
+                let status = with_proc(&resolved[0], |proc| {
+                    // Close stdin so processes reading until end of input can finish.
+                    proc.child.stdin.take();
+                    proc.child.wait().map_err(io_error)
+                })?;
+                // The process is reaped, so its handle is released.
+                procs().borrow_mut().remove(&proc_id(&resolved[0])?);
+                Ok(status
+                    .code()
+                    .map(|code| Expr::number(code as f64))
+                    .unwrap_or(Expr::Nil))
+            })
+        },
+    );
+    env.defn(
+        "proc/pipe",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            recorded("proc/pipe", &resolved, || {
+                let stdout = with_proc(&resolved[0], |proc| {
+                    proc.stdout.take().ok_or_else(|| {
+                        HError::IoError("The process's stdout is already piped".to_string())
+                    })
+                })?;
+                let stdin = with_proc(&resolved[1], |proc| {
+                    proc.child
+                        .stdin
+                        .take()
+                        .ok_or_else(|| HError::IoError("The process's stdin is closed".to_string()))
+                })?;
+                // Pipes are plain OS handles, so a helper thread can copy between them.
+                thread::spawn(move || {
+                    let (mut stdout, mut stdin) = (stdout, stdin);
+                    let _ = io::copy(&mut stdout, &mut stdin);
+                });
+                Ok(resolved[1].clone())
+            })
+        },
+    );
+
+    env
+}
+
+/// Reads a line of the process's output, without the line ending.
+fn read_line(proc: &mut Proc) -> Result<Expr, HError> {
+    let stdout = match proc.stdout.as_mut() {
+        Some(stdout) => stdout,
+        None => return Ok(Expr::Nil),
+    };
+    let mut line = String::new();
+    if stdout.read_line(&mut line).map_err(io_error)? == 0 {
+        return Ok(Expr::Nil);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    check_string_len(line.len())?;
+    Ok(Expr::String(line))
+}
+
+fn procs() -> Procs {
+    PROCS.with(|procs| Rc::clone(&procs.borrow()))
+}
+
+/// Installs `procs` as the active interpreter's, returning the previous ones.
+pub(crate) fn replace_procs(procs: Procs) -> Procs {
+    PROCS.with(|active| active.replace(procs))
+}
+
+fn proc_id(handle: &Expr) -> Result<usize, HError> {
+    match handle {
+        Expr::Number(id) => Ok(**id as usize),
+        invalid => Err(HError::InvalidType("proc".to_string(), invalid.clone())),
+    }
+}
+
+fn with_proc<T>(
+    handle: &Expr,
+    f: impl FnOnce(&mut Proc) -> Result<T, HError>,
+) -> Result<T, HError> {
+    let id = proc_id(handle)?;
+    match procs().borrow_mut().get_mut(&id) {
+        Some(proc) => f(proc),
+        None => Err(HError::UnexpectedForm(
+            "Unknown process".to_string(),
+            handle.clone(),
+        )),
+    }
+}
+
+fn string_arg(fn_name: &str, expr: &Expr) -> Result<String, HError> {
+    match expr {
+        Expr::String(text) => Ok(text.to_string()),
+        invalid => Err(HError::InvalidType(fn_name.to_string(), invalid.clone())),
+    }
+}
+
+fn io_error(err: io::Error) -> HError {
+    HError::IoError(err.to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_spawn_write_and_read() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var p (proc/spawn \"cat\"))", env.clone_ref()).unwrap();
+        eval("(proc/stdin-write p \"hello\n\")", env.clone_ref()).unwrap();
+
+        assert_eq!(
+            eval("(proc/read-line p)", env.clone_ref()),
+            Ok(Expr::string("hello"))
+        );
+        assert_eq!(eval("(proc/wait p)", env.clone_ref()), Ok(Expr::number(0.)));
+        // Waiting releases the handle.
+        let handle = env.get("p").unwrap();
+        assert_eq!(
+            eval("(proc/read-line p)", env),
+            Err(HError::UnexpectedForm(
+                "Unknown process".to_string(),
+                handle
+            ))
+        );
+    }
+
+    #[test]
+    fn test_pipe() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var source (proc/spawn \"printf\" [\"b\na\n\"]))
+            (var sorted (proc/pipe source (proc/spawn \"sort\")))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval(
+                "[(proc/read-line sorted) (proc/read-line sorted)]",
+                env.clone_ref()
+            ),
+            Ok(Expr::vector(&[Expr::string("a"), Expr::string("b")]))
+        );
+        assert_eq!(eval("(proc/wait sorted)", env), Ok(Expr::number(0.)));
+    }
+}
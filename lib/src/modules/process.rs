@@ -0,0 +1,214 @@
+use std::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::check_deterministic,
+};
+
+/// Signals scripts can trap with `on-signal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+}
+
+impl Signal {
+    const ALL: &'static [Signal] = &[Signal::Interrupt, Signal::Terminate];
+
+    fn keyword(&self) -> &'static str {
+        match self {
+            Signal::Interrupt => ":interrupt",
+            Signal::Terminate => ":terminate",
+        }
+    }
+
+    fn pending(&self) -> &'static AtomicBool {
+        match self {
+            Signal::Interrupt => &INTERRUPT,
+            Signal::Terminate => &TERMINATE,
+        }
+    }
+
+    fn trapped(&self) -> &'static AtomicBool {
+        match self {
+            Signal::Interrupt => &INTERRUPT_TRAPPED,
+            Signal::Terminate => &TERMINATE_TRAPPED,
+        }
+    }
+}
+
+// Read and set from signal handlers, so they are plain atomics shared by all
+// threads.
+static INTERRUPT: AtomicBool = AtomicBool::new(false);
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+static INTERRUPT_TRAPPED: AtomicBool = AtomicBool::new(false);
+static TERMINATE_TRAPPED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LISTENING: Cell<bool> = const { Cell::new(false) };
+    // Handlers run in the env they were registered from.
+    static EXIT_HANDLERS: RefCell<Vec<(Expr, EnvRef)>> = const { RefCell::new(Vec::new()) };
+    static SIGNAL_HANDLERS: RefCell<Vec<(Signal, Expr, EnvRef)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+pub fn process_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "on-exit",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let handler = resolve_args(args, env.clone_ref())?.remove(0);
+            EXIT_HANDLERS.with(|handlers| handlers.borrow_mut().push((handler, env)));
+            Ok(Expr::Nil)
+        },
+    );
+
+    env.defn(
+        "on-signal",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            check_deterministic("on-signal")?;
+            let resolved = resolve_args(args, env.clone_ref())?;
+            let signal = Signal::ALL
+                .iter()
+                .find(|signal| Expr::keyword(signal.keyword()) == resolved[0])
+                .ok_or_else(|| {
+                    HError::UnexpectedForm("Unsupported signal".to_string(), resolved[0].clone())
+                })?;
+            SIGNAL_HANDLERS.with(|handlers| {
+                handlers
+                    .borrow_mut()
+                    .push((*signal, resolved[1].clone(), env))
+            });
+            signal.trapped().store(true, Ordering::SeqCst);
+            Ok(Expr::Nil)
+        },
+    );
+
+    env
+}
+
+/// Makes this thread handle raised signals. Only one thread should listen,
+/// since each signal is handled once.
+pub fn listen() {
+    LISTENING.with(|listening| listening.set(true));
+}
+
+/// Whether a script has trapped `signal` with `on-signal`. Only reads an
+/// atomic, so a signal handler can use it to fall back to the default action.
+pub fn is_trapped(signal: Signal) -> bool {
+    signal.trapped().load(Ordering::SeqCst)
+}
+
+/// Records that `signal` arrived. Only touches an atomic, so it is safe to
+/// call from a signal handler.
+pub fn raise(signal: Signal) {
+    signal.pending().store(true, Ordering::SeqCst);
+}
+
+/// Runs the handlers for any signal raised since the last check. A signal
+/// without handlers stops evaluation with `HError::Interrupted`.
+pub(crate) fn handle_signals() -> Result<(), HError> {
+    if !LISTENING.with(|listening| listening.get()) {
+        return Ok(());
+    }
+    for signal in Signal::ALL {
+        if !signal.pending().swap(false, Ordering::SeqCst) {
+            continue;
+        }
+        let handlers: Vec<(Expr, EnvRef)> = SIGNAL_HANDLERS.with(|handlers| {
+            handlers
+                .borrow()
+                .iter()
+                .filter(|(trapped, ..)| trapped == signal)
+                .map(|(_, handler, env)| (handler.clone(), env.clone_ref()))
+                .collect()
+        });
+        if handlers.is_empty() {
+            return Err(HError::Interrupted);
+        }
+        for (handler, env) in handlers {
+            call(&handler, &[], env)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs and clears the handlers registered with `on-exit`, most recent first.
+pub fn run_exit_handlers() -> Result<(), HError> {
+    let handlers = EXIT_HANDLERS.with(|handlers| handlers.take());
+    for (handler, env) in handlers.into_iter().rev() {
+        call(&handler, &[], env)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_exit_handlers_run_once_in_reverse_order() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var log (atom []))
+            (on-exit (fn [] (swap! log (fn [l] [l :first]))))
+            (on-exit (fn [] (swap! log (fn [l] [l :second]))))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        run_exit_handlers().unwrap();
+        run_exit_handlers().unwrap();
+
+        assert_eq!(
+            eval("(deref log)", env),
+            eval(
+                "[[[] :second] :first]",
+                Env::with_core_module().unwrap().into_ref()
+            )
+        );
+    }
+
+    #[test]
+    fn test_signals() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        listen();
+
+        raise(Signal::Terminate);
+        assert_eq!(eval("(+ 1 2)", env.clone_ref()), Err(HError::Interrupted));
+
+        eval(
+            "
+            (var caught (atom 0))
+            (on-signal :terminate (fn [] (swap! caught (fn [n] (+ n 1)))))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert!(is_trapped(Signal::Terminate));
+        raise(Signal::Terminate);
+        assert_eq!(eval("(+ 1 2)", env.clone_ref()), Ok(Expr::number(3.)));
+        assert_eq!(
+            eval("(deref caught)", env.clone_ref()),
+            Ok(Expr::number(1.))
+        );
+
+        assert_eq!(
+            eval("(on-signal :hangup (fn [] nil))", env),
+            Err(HError::UnexpectedForm(
+                "Unsupported signal".to_string(),
+                Expr::keyword(":hangup")
+            ))
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use crate::{
+    coverage,
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{eval_source, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::module_loader,
+    manifest::MODULES_DIR,
+};
+
+/// Resolves the names passed to `require` to source code, so scripts can be
+/// stored somewhere other than the filesystem.
+pub trait ModuleLoader {
+    fn load(&self, name: &str) -> Result<String, HError>;
+
+    /// When the module last changed, used to decide what `reload_changed`
+    /// reloads. Modules without a modification time are never reloaded.
+    fn modified(&self, _name: &str) -> Option<SystemTime> {
+        None
+    }
+
+    /// Directories searched for modules, as reported by `huck-path`.
+    fn search_path(&self) -> Vec<PathBuf> {
+        vec![]
+    }
+
+    /// The file a module is read from, if it has one.
+    fn path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Loads modules from files, relative to `root` and then to each directory
+/// in `search_path`. A name without an extension also matches `<name>.huck`.
+#[derive(Debug, Default, Clone)]
+pub struct FileLoader {
+    pub root: PathBuf,
+    pub search_path: Vec<PathBuf>,
+}
+
+impl FileLoader {
+    /// A loader relative to `root` that also searches the directories in
+    /// `HUCK_PATH`, separated like `PATH`, and then the dependencies fetched
+    /// into `root` by `huck deps`. A leading `~` is the home directory.
+    pub fn from_env(root: PathBuf) -> FileLoader {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let mut search_path: Vec<PathBuf> = env::var_os("HUCK_PATH")
+            .map(|paths| {
+                env::split_paths(&paths)
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .map(|path| match (path.strip_prefix("~"), &home) {
+                        (Ok(rest), Some(home)) => home.join(rest),
+                        _ => path,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let modules = root.join(MODULES_DIR);
+        if modules.is_dir() {
+            search_path.push(modules);
+        }
+        FileLoader { root, search_path }
+    }
+
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let mut names = vec![PathBuf::from(name)];
+        if Path::new(name).extension().is_none() {
+            names.push(PathBuf::from(format!("{}.huck", name)));
+        }
+        std::iter::once(&self.root)
+            .chain(&self.search_path)
+            .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+            .find(|path| path.is_file())
+    }
+}
+
+impl ModuleLoader for FileLoader {
+    fn load(&self, name: &str) -> Result<String, HError> {
+        self.resolve(name)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .ok_or_else(|| HError::ModuleNotFound(name.to_string()))
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        fs::metadata(self.resolve(name)?)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+
+    fn search_path(&self) -> Vec<PathBuf> {
+        self.search_path.clone()
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.resolve(name)
+    }
+}
+
+/// Serves modules from memory, keyed by name.
+impl ModuleLoader for BTreeMap<String, String> {
+    fn load(&self, name: &str) -> Result<String, HError> {
+        self.get(name)
+            .cloned()
+            .ok_or_else(|| HError::ModuleNotFound(name.to_string()))
+    }
+}
+
+/// The vars a module being loaded makes available to whoever requires it.
+#[derive(Default)]
+struct Exports {
+    module: String,
+    // Set by `export`, in which case only these are exported.
+    listed: Option<BTreeSet<String>>,
+    // Defined with `defn-`.
+    private: BTreeSet<String>,
+}
+
+impl Exports {
+    fn allows(&self, name: &str) -> bool {
+        match &self.listed {
+            Some(listed) => listed.contains(name),
+            None => !self.private.contains(name),
+        }
+    }
+}
+
+/// A module that finished loading, kept so requiring it again only imports
+/// its bindings rather than evaluating it again.
+pub(crate) struct LoadedModule {
+    modified: Option<SystemTime>,
+    env: EnvRef,
+    exports: Exports,
+}
+
+/// The modules loaded by an interpreter, by name.
+pub(crate) type LoadedModules = Rc<RefCell<BTreeMap<String, LoadedModule>>>;
+
+thread_local! {
+    // The active interpreter's modules, swapped in by `Interpreter`.
+    static LOADED: RefCell<LoadedModules> = RefCell::new(Rc::default());
+    // Exports of the modules currently being loaded, innermost last.
+    static LOADING: RefCell<Vec<Exports>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn require_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "require",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = name_arg("require", args, env.clone_ref())?;
+            require(&name, env)
+        },
+    );
+
+    env.defn(
+        "reload",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = name_arg("reload", args, env.clone_ref())?;
+            load(&name, env)
+        },
+    );
+
+    env.defn(
+        "export",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            let mut names = BTreeSet::new();
+            for arg in args {
+                match arg {
+                    Expr::Symbol(name) => names.insert(name.to_string()),
+                    invalid => {
+                        return Err(HError::UnexpectedForm(
+                            "\"export\" requires symbols".to_string(),
+                            invalid.clone(),
+                        ))
+                    }
+                };
+            }
+            // Outside of a required module everything stays in scope anyway.
+            LOADING.with(|loading| {
+                if let Some(exports) = loading.borrow_mut().last_mut() {
+                    exports
+                        .listed
+                        .get_or_insert_with(BTreeSet::new)
+                        .extend(names);
+                }
+            });
+            Ok(Expr::Nil)
+        },
+    );
+
+    env.defn(
+        "huck-path",
+        Arity::Count(0),
+        |_: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            let dirs: Vec<Expr> = module_loader()
+                .search_path()
+                .iter()
+                .map(|dir| Expr::string(&dir.to_string_lossy()))
+                .collect();
+            Ok(Expr::vector(&dirs))
+        },
+    );
+
+    env
+}
+
+fn name_arg(fn_name: &str, args: &[Expr], env: EnvRef) -> Result<String, HError> {
+    match &resolve_args(args, env)?[0] {
+        Expr::String(name) => Ok(name.to_string()),
+        invalid => Err(HError::InvalidType(fn_name.to_string(), invalid.clone())),
+    }
+}
+
+/// Makes the module `name` available in `env`, loading it unless it was
+/// already loaded. A module's code runs at most once, however many times or
+/// places it is required.
+pub fn require(name: &str, env: EnvRef) -> Result<Expr, HError> {
+    let loaded = loaded_modules();
+    let loaded = loaded.borrow();
+    match loaded.get(name) {
+        Some(module) => {
+            env.import(name, &module.env, |var| module.exports.allows(var))?;
+            Ok(Expr::Nil)
+        }
+        None => {
+            drop(loaded);
+            load(name, env)
+        }
+    }
+}
+
+/// Evaluates the module `name` in its own env on top of `env`, using the
+/// active loader, then imports its exported vars and its methods into `env`.
+/// Definitions from an earlier load are overwritten, and everything else in
+/// `env` is left as is.
+pub fn load(name: &str, env: EnvRef) -> Result<Expr, HError> {
+    check_cycle(name)?;
+    let loader = module_loader();
+    let source = loader.load(name)?;
+    let modified = loader.modified(name);
+    let path = loader.path(name);
+    coverage::record_source(
+        &path.map_or(name.to_string(), |path| path.to_string_lossy().to_string()),
+        &source,
+    );
+
+    let module = Env::extend(env.clone_ref()).into_ref();
+    LOADING.with(|loading| {
+        loading.borrow_mut().push(Exports {
+            module: name.to_string(),
+            ..Exports::default()
+        })
+    });
+    let result = eval_source(&source, name, module.clone_ref());
+    let exports = LOADING
+        .with(|loading| loading.borrow_mut().pop())
+        .unwrap_or_default();
+    let value = result?;
+    env.import(name, &module, |var| exports.allows(var))?;
+    loaded_modules().borrow_mut().insert(
+        name.to_string(),
+        LoadedModule {
+            modified,
+            env: module,
+            exports,
+        },
+    );
+    Ok(value)
+}
+
+fn loaded_modules() -> LoadedModules {
+    LOADED.with(|loaded| Rc::clone(&loaded.borrow()))
+}
+
+/// Installs `modules` as the active interpreter's, returning the previous ones.
+pub(crate) fn replace_loaded_modules(modules: LoadedModules) -> LoadedModules {
+    LOADED.with(|loaded| loaded.replace(modules))
+}
+
+/// Fails if `name` is already being loaded, i.e. it ends up requiring itself.
+fn check_cycle(name: &str) -> Result<(), HError> {
+    LOADING.with(|loading| {
+        let loading = loading.borrow();
+        match loading.iter().position(|exports| exports.module == name) {
+            Some(start) => {
+                let mut cycle: Vec<String> = loading[start..]
+                    .iter()
+                    .map(|exports| exports.module.to_string())
+                    .collect();
+                cycle.push(name.to_string());
+                Err(HError::CyclicRequire(cycle))
+            }
+            None => Ok(()),
+        }
+    })
+}
+
+/// Keeps `name` from being exported by the module currently being loaded.
+pub fn mark_private(name: &str) {
+    LOADING.with(|loading| {
+        if let Some(exports) = loading.borrow_mut().last_mut() {
+            exports.private.insert(name.to_string());
+        }
+    });
+}
+
+/// Reloads every module modified since it was last loaded, returning
+/// their names.
+pub fn reload_changed(env: EnvRef) -> Result<Vec<String>, HError> {
+    let loader = module_loader();
+    let changed: Vec<String> = loaded_modules()
+        .borrow()
+        .iter()
+        .filter(|(name, module)| {
+            module.modified.is_some() && loader.modified(name) != module.modified
+        })
+        .map(|(name, _)| name.to_string())
+        .collect();
+    for name in &changed {
+        load(name, env.clone_ref())?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evaluator::eval,
+        interpreter::{Interpreter, Options},
+    };
+    use std::{rc::Rc, time::Duration};
+
+    fn write_module(name: &str, source: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, source).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_require() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let path = write_module("huck_test_require.huck", "(defn double [n] (* n 2))");
+
+        eval(&format!("(require {:?})", path), env.clone_ref()).unwrap();
+
+        assert_eq!(eval("(double 2)", env), Ok(Expr::number(4.)));
+    }
+
+    #[test]
+    fn test_require_missing_file() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(require \"missing/module.huck\")", env),
+            Err(HError::ModuleNotFound("missing/module.huck".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_require_exports() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let private = write_module(
+            "huck_test_private.huck",
+            "(defn- helper [n] (* n 2)) (defn double [n] (helper n))",
+        );
+        let exported = write_module(
+            "huck_test_export.huck",
+            "(export quadruple) (defn twice [n] (* n 2)) (defn quadruple [n] (twice (twice n)))",
+        );
+
+        load(&private, env.clone_ref()).unwrap();
+        load(&exported, env.clone_ref()).unwrap();
+
+        assert_eq!(eval("(double 2)", env.clone_ref()), Ok(Expr::number(4.)));
+        assert_eq!(
+            eval("(helper 2)", env.clone_ref()),
+            Err(HError::PrivateVar("helper".to_string(), private))
+        );
+        assert_eq!(eval("(quadruple 2)", env.clone_ref()), Ok(Expr::number(8.)));
+        assert_eq!(
+            eval("twice", env),
+            Err(HError::PrivateVar("twice".to_string(), exported))
+        );
+    }
+
+    fn interpreter_with_modules(modules: &[(&str, &str)]) -> Interpreter {
+        let sources: BTreeMap<String, String> = modules
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect();
+        Interpreter::with_options(Options {
+            loader: Some(Rc::new(sources)),
+            ..Options::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_require_diamond() {
+        let interpreter = interpreter_with_modules(&[
+            ("a", "(require \"b\") (require \"c\") (defn a [] [(b) (c)])"),
+            ("b", "(require \"d\") (defn b [] (d :b))"),
+            ("c", "(require \"d\") (defn c [] (d :c))"),
+            ("d", "(defn d [from] [:d from])"),
+        ]);
+
+        interpreter.eval("(require \"a\")").unwrap();
+
+        assert_eq!(
+            interpreter.eval("(a)"),
+            interpreter.eval("[[:d :b] [:d :c]]")
+        );
+    }
+
+    #[test]
+    fn test_require_runs_modules_once() {
+        let interpreter = interpreter_with_modules(&[
+            (
+                "counter",
+                "(swap! loads (fn [n] (+ n 1))) (defn loaded [] (deref loads))",
+            ),
+            ("user", "(require \"counter\")"),
+        ]);
+        interpreter.eval("(var loads (atom 0))").unwrap();
+
+        interpreter
+            .eval("(require \"counter\") (require \"user\") (require \"counter\")")
+            .unwrap();
+        assert_eq!(interpreter.eval("(loaded)"), Ok(Expr::number(1.)));
+
+        interpreter.eval("(reload \"counter\")").unwrap();
+        assert_eq!(interpreter.eval("(loaded)"), Ok(Expr::number(2.)));
+    }
+
+    #[test]
+    fn test_require_cycle() {
+        let interpreter = interpreter_with_modules(&[
+            ("a", "(require \"b\")"),
+            ("b", "(require \"c\")"),
+            ("c", "(require \"a\")"),
+        ]);
+
+        assert_eq!(
+            interpreter.eval("(require \"b\")"),
+            Err(HError::CyclicRequire(vec![
+                "b".to_string(),
+                "c".to_string(),
+                "a".to_string(),
+                "b".to_string()
+            ]))
+        );
+        // Failed loads are unwound, so the next cycle is reported from its own start.
+        assert_eq!(
+            interpreter.eval("(require \"c\")"),
+            Err(HError::CyclicRequire(vec![
+                "c".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_file_loader_search_path() {
+        let dir = std::env::temp_dir().join("huck_test_search_path");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("utils.huck"), "(defn triple [n] (* n 3))").unwrap();
+        let loader = FileLoader {
+            root: PathBuf::from("missing"),
+            search_path: vec![dir.clone()],
+        };
+
+        assert_eq!(
+            loader.load("utils"),
+            Ok("(defn triple [n] (* n 3))".to_string())
+        );
+        assert_eq!(
+            loader.load("utils.txt"),
+            Err(HError::ModuleNotFound("utils.txt".to_string()))
+        );
+        assert_eq!(loader.search_path(), vec![dir]);
+    }
+
+    #[test]
+    fn test_reload_changed_preserves_session_state() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let path = write_module(
+            "huck_test_reload.huck",
+            "(defm number? [describe] :before) (defn greeting [] \"hi\")",
+        );
+
+        load(&path, env.clone_ref()).unwrap();
+        eval("(var counter 3)", env.clone_ref()).unwrap();
+
+        assert_eq!(reload_changed(env.clone_ref()), Ok(vec![]));
+
+        fs::write(&path, "(defm number? [describe] :after)").unwrap();
+        // Make sure the change is visible on filesystems with coarse timestamps.
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(reload_changed(env.clone_ref()), Ok(vec![path]));
+        assert_eq!(
+            eval("<1 describe>", env.clone_ref()),
+            Ok(Expr::keyword(":after"))
+        );
+        assert_eq!(eval("(greeting)", env.clone_ref()), Ok(Expr::string("hi")));
+        assert_eq!(eval("counter", env), Ok(Expr::number(3.)));
+    }
+}
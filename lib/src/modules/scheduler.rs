@@ -0,0 +1,202 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::{break_on_error, check_deterministic},
+    modules::{process::handle_signals, time::parse_time, units::parse_duration},
+};
+
+/// Longest sleep between checks for signals while waiting on a timer.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub(crate) struct Timer {
+    id: usize,
+    due: Instant,
+    interval: Option<Duration>,
+    callback: Expr,
+}
+
+/// The timers an interpreter has registered.
+pub(crate) type Timers = Rc<RefCell<Vec<Timer>>>;
+
+// Values can't be sent between threads, so timers run on the interpreter
+// thread whenever the scheduler is run.
+thread_local! {
+    static TIMERS: RefCell<Timers> = RefCell::new(Rc::default());
+    static NEXT_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn scheduler_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "every",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            check_deterministic("every")?;
+            let resolved = resolve_args(args, env)?;
+            let interval = match &resolved[0] {
+                Expr::String(text) => parse_duration(text)?,
+                Expr::Number(ms) => **ms,
+                invalid => return Err(HError::InvalidType("every".to_string(), invalid.clone())),
+            };
+            let interval = Duration::from_secs_f64(interval.max(0.) / 1_000.);
+            let id = schedule(Instant::now() + interval, Some(interval), &resolved[1]);
+            Ok(Expr::number(id as f64))
+        },
+    );
+
+    env.defn(
+        "at",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            check_deterministic("at")?;
+            let resolved = resolve_args(args, env)?;
+            let time = match &resolved[0] {
+                Expr::String(text) => parse_time(text)? as f64,
+                Expr::Number(ms) => **ms,
+                invalid => return Err(HError::InvalidType("at".to_string(), invalid.clone())),
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_millis() as f64)
+                .unwrap_or(0.);
+            let wait = Duration::from_secs_f64((time - now).max(0.) / 1_000.);
+            let id = schedule(Instant::now() + wait, None, &resolved[1]);
+            Ok(Expr::number(id as f64))
+        },
+    );
+
+    env.defn(
+        "cancel",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let id = match &resolve_args(args, env)?[0] {
+                Expr::Number(id) => **id as usize,
+                invalid => return Err(HError::InvalidType("cancel".to_string(), invalid.clone())),
+            };
+            let timers = timers();
+            let mut timers = timers.borrow_mut();
+            let before = timers.len();
+            timers.retain(|timer| timer.id != id);
+            let cancelled = before != timers.len();
+            Ok(Expr::boolean(cancelled))
+        },
+    );
+
+    env.defn(
+        "run-scheduled",
+        Arity::Count(0),
+        |_: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            run_scheduled(env)?;
+            Ok(Expr::Nil)
+        },
+    );
+
+    env
+}
+
+pub(crate) fn schedule(due: Instant, interval: Option<Duration>, callback: &Expr) -> usize {
+    let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+    timers().borrow_mut().push(Timer {
+        id,
+        due,
+        interval,
+        callback: callback.clone(),
+    });
+    id
+}
+
+fn timers() -> Timers {
+    TIMERS.with(|timers| Rc::clone(&timers.borrow()))
+}
+
+/// Installs `timers` as the active interpreter's, returning the previous ones.
+pub(crate) fn replace_timers(timers: Timers) -> Timers {
+    TIMERS.with(|active| active.replace(timers))
+}
+
+/// Runs timers as they come due until none are left. Repeating timers keep
+/// the loop going until they are cancelled.
+pub fn run_scheduled(env: EnvRef) -> Result<(), HError> {
+    loop {
+        let next = timers()
+            .borrow()
+            .iter()
+            .min_by_key(|timer| (timer.due, timer.id))
+            .map(|timer| (timer.id, timer.due));
+        let (id, due) = match next {
+            Some(next) => next,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        if due > now {
+            thread::sleep((due - now).min(POLL_INTERVAL));
+            handle_signals()?;
+            continue;
+        }
+
+        // Reschedule before calling, so the callback can cancel its own timer.
+        let callback = {
+            let timers = timers();
+            let mut timers = timers.borrow_mut();
+            timers.iter().position(|timer| timer.id == id).map(|index| {
+                match timers[index].interval {
+                    Some(interval) => {
+                        timers[index].due = due + interval;
+                        timers[index].callback.clone()
+                    }
+                    None => timers.remove(index).callback,
+                }
+            })
+        };
+        // A failing callback can be resumed, which carries on with the timers.
+        if let Some(callback) = callback {
+            call(&callback, &[], env.clone_ref())
+                .or_else(|err| break_on_error(err, env.clone_ref()))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_every_and_at() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var log (atom []))
+            (var ticks (atom 0))
+            (var timer nil)
+            (set! timer (every \"1ms\" (fn []
+                (swap! ticks (fn [n] (+ n 1)))
+                (if (= (deref ticks) 3) (cancel timer)))))
+            (at \"2000-01-01T00:00\" (fn [] (swap! log (fn [l] [l :past]))))
+            (var never (at 0 (fn [] (swap! log (fn [l] [l :cancelled])))))
+            (cancel never)
+            (run-scheduled)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(deref ticks)", env.clone_ref()), Ok(Expr::number(3.)));
+        assert_eq!(
+            eval("(deref log)", env.clone_ref()),
+            eval("[[] :past]", env.clone_ref())
+        );
+        assert_eq!(eval("(cancel timer)", env), Ok(Expr::boolean(false)));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    modules::utils::hint_matches,
+};
+
+/// Schemas are plain data: a type keyword such as `:string` (any type hint
+/// name, or `:any`), a map of keys to schemas, or a vector holding the
+/// schema of every element.
+pub fn schema_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "schema",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let schema = resolve_args(args, env)?.remove(0);
+            check_schema(&schema)?;
+            Ok(schema)
+        },
+    );
+
+    env.defn(
+        "validate",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            check_schema(&resolved[0])?;
+            let mut errors = vec![];
+            validate(&resolved[0], &resolved[1], &mut vec![], &mut errors);
+            Ok(if errors.is_empty() {
+                Expr::Nil
+            } else {
+                Expr::Vector(errors)
+            })
+        },
+    );
+
+    env
+}
+
+fn check_schema(schema: &Expr) -> Result<(), HError> {
+    match schema {
+        Expr::Keyword(name) if name == ":any" || hint_matches(&name[1..], &Expr::Nil).is_some() => {
+            Ok(())
+        }
+        Expr::Map(fields) => fields.values().try_for_each(check_schema),
+        Expr::Vector(items) if items.len() == 1 => check_schema(&items[0]),
+        invalid => Err(HError::UnexpectedForm(
+            "Invalid schema".to_string(),
+            invalid.clone(),
+        )),
+    }
+}
+
+/// Adds an error to `errors` for each part of `value` that doesn't match
+/// `schema`. `path` holds the keys and indices leading to `value`.
+fn validate(schema: &Expr, value: &Expr, path: &mut Vec<Expr>, errors: &mut Vec<Expr>) {
+    match (schema, value) {
+        (Expr::Keyword(name), _) if name == ":any" => (),
+        (Expr::Keyword(name), _) if hint_matches(&name[1..], value) == Some(true) => (),
+        (Expr::Map(fields), Expr::Map(map)) => {
+            for (key, field) in fields {
+                path.push(key.clone());
+                validate(field, map.get(key).unwrap_or(&Expr::Nil), path, errors);
+                path.pop();
+            }
+        }
+        (Expr::Vector(item), Expr::Vector(items)) => {
+            for (i, value) in items.iter().enumerate() {
+                path.push(Expr::number(i as f64));
+                validate(&item[0], value, path, errors);
+                path.pop();
+            }
+        }
+        _ => errors.push(Expr::map(&[
+            (Expr::keyword(":path"), Expr::vector(path)),
+            (Expr::keyword(":expected"), expected(schema)),
+        ])),
+    }
+}
+
+/// What a mismatched value should have been: the type keyword, or `:map`
+/// or `:vector` for structured schemas.
+fn expected(schema: &Expr) -> Expr {
+    match schema {
+        Expr::Map(_) => Expr::keyword(":map"),
+        Expr::Vector(_) => Expr::keyword(":vector"),
+        keyword => keyword.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_validates_nested_values() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(var person (schema {:name :string :age :number :tags [:keyword]}))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(
+            eval(
+                "(validate person {:name \"Ada\" :age 36 :tags [:math]})",
+                env.clone_ref()
+            ),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval(
+                "(validate person {:name \"Ada\" :tags [:math \"poet\"]})",
+                env.clone_ref()
+            ),
+            eval(
+                "[{:path [:age] :expected :number} {:path [:tags 1] :expected :keyword}]",
+                env.clone_ref()
+            )
+        );
+        assert_eq!(
+            eval("(validate [person] [1])", env.clone_ref()),
+            eval("[{:path [0] :expected :map}]", env.clone_ref())
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_schemas() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(schema {:a :text})", env.clone_ref()),
+            Err(HError::UnexpectedForm(
+                "Invalid schema".to_string(),
+                Expr::keyword(":text")
+            ))
+        );
+        assert!(eval("(validate [:number :string] [])", env).is_err());
+    }
+}
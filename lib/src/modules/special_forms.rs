@@ -1,22 +1,97 @@
+use std::{cell::Cell, collections::BTreeMap};
+
 use crate::{
     env::{Env, EnvRef},
     error::HError,
-    evaluator::{eval_expr, eval_exprs},
+    evaluator::{apply, current_source, eval_expr, eval_exprs, resolve_args},
     expr::{Arity, Expr, Fn, Method},
+    interpreter::{allow_redefine, catching},
+    stats::{Kind, Live},
+    trace,
+};
+
+use super::{
+    native::curry,
+    pattern::select_arm,
+    require::mark_private,
+    utils::{is_truthy, method_signature, split_hints},
 };
 
-use super::utils::{is_truthy, method_args, method_id};
+/// Names of the special forms, which scripts can't redefine unless the
+/// interpreter allows it.
+pub const RESERVED: &[&str] = &[
+    "var",
+    "defonce",
+    "if",
+    "if-let",
+    "when-let",
+    "some->",
+    "or-else",
+    "and",
+    "or",
+    "set!",
+    "swap-var!",
+    "fn",
+    "defn",
+    "defn-",
+    "defn-curried",
+    "declare",
+    "when-available",
+    "letfn",
+    "let",
+    "defm",
+    "for-each",
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+    "defmacro",
+    "match",
+    "try",
+];
+
+thread_local! {
+    // Numbers the names made by `gensym`.
+    static NEXT_GENSYM: Cell<usize> = const { Cell::new(0) };
+}
 
 pub fn special_forms_module() -> Env {
     let mut env = Env::new();
 
     env.defn(
         "var",
-        Arity::Range(1, 2),
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &args[0] {
+                Expr::Symbol(value) => {
+                    check_reserved(value)?;
+                    env.define(value, eval_expr(&args[1], env.clone_ref())?)?;
+                    Ok(Expr::Nil)
+                }
+                Expr::Vector(_) | Expr::Map(_) => {
+                    destructure(&args[0], eval_expr(&args[1], env.clone_ref())?, &env)?;
+                    Ok(Expr::Nil)
+                }
+                invalid => Err(HError::UnexpectedForm(
+                    "Only symbols can be defined".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "defonce",
+        Arity::Count(2),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
             match &args[0] {
+                // The value is only evaluated when it will be used, so reloading
+                // a file doesn't repeat its side effects.
                 Expr::Symbol(value) => {
-                    env.def(&value, eval_expr(&args[1], env.clone_ref())?);
+                    check_reserved(value)?;
+                    if env.get(value).is_err() {
+                        env.define(value, eval_expr(&args[1], env.clone_ref())?)?;
+                    }
                     Ok(Expr::Nil)
                 }
                 invalid => Err(HError::UnexpectedForm(
@@ -42,13 +117,118 @@ pub fn special_forms_module() -> Env {
         },
     );
 
+    env.defn(
+        "if-let",
+        Arity::Range(2, 3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let (name, value) = let_binding("if-let", &args[0])?;
+            let value = eval_expr(value, env.clone_ref())?;
+            if is_truthy(&value) {
+                let let_env = Env::extend(env).into_ref();
+                let_env.def(name, value)?;
+                eval_expr(&args[1], let_env)
+            } else if args.len() == 3 {
+                eval_expr(&args[2], env)
+            } else {
+                Ok(Expr::Nil)
+            }
+        },
+    );
+
+    env.defn(
+        "when-let",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let (name, value) = let_binding("when-let", &args[0])?;
+            let value = eval_expr(value, env.clone_ref())?;
+            if !is_truthy(&value) {
+                return Ok(Expr::Nil);
+            }
+            let let_env = Env::extend(env).into_ref();
+            let_env.def(name, value)?;
+            eval_exprs(&args[1..], let_env)
+        },
+    );
+
+    env.defn(
+        "some->",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut value = eval_expr(&args[0], env.clone_ref())?;
+            for form in &args[1..] {
+                if value == Expr::Nil {
+                    break;
+                }
+                // The value becomes the first argument of each call.
+                let call = match form {
+                    Expr::List(list) if !list.is_empty() => {
+                        let mut call = vec![list[0].clone(), value];
+                        call.extend_from_slice(&list[1..]);
+                        call
+                    }
+                    Expr::Symbol(_) => vec![form.clone(), value],
+                    invalid => {
+                        return Err(HError::UnexpectedForm(
+                            "\"some->\" requires calls or function names".to_string(),
+                            invalid.clone(),
+                        ))
+                    }
+                };
+                value = eval_expr(&Expr::List(call), env.clone_ref())?;
+            }
+            Ok(value)
+        },
+    );
+
+    env.defn(
+        "or-else",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match eval_expr(&args[0], env.clone_ref())? {
+                Expr::Nil => eval_expr(&args[1], env),
+                value => Ok(value),
+            }
+        },
+    );
+
+    env.defn(
+        "and",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut value = Expr::Boolean(true);
+            for arg in args {
+                value = eval_expr(arg, env.clone_ref())?;
+                if !is_truthy(&value) {
+                    break;
+                }
+            }
+            Ok(value)
+        },
+    );
+
+    env.defn(
+        "or",
+        Arity::Range(0, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let mut value = Expr::Nil;
+            for arg in args {
+                value = eval_expr(arg, env.clone_ref())?;
+                if is_truthy(&value) {
+                    break;
+                }
+            }
+            Ok(value)
+        },
+    );
+
     env.defn(
         "set!",
         Arity::Count(2),
         |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
             match &args[0] {
                 Expr::Symbol(value) => {
-                    env.set(&value, eval_expr(&args[1], env.clone_ref())?)?;
+                    check_reserved(value)?;
+                    env.set(value, eval_expr(&args[1], env.clone_ref())?)?;
                     Ok(Expr::Nil)
                 }
                 invalid => Err(HError::UnexpectedForm(
@@ -59,6 +239,29 @@ pub fn special_forms_module() -> Env {
         },
     );
 
+    env.defn(
+        "swap-var!",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = match &args[0] {
+                Expr::Symbol(name) => name,
+                invalid => {
+                    return Err(HError::UnexpectedForm(
+                        "Only symbols can be swapped".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+            check_reserved(name)?;
+            let resolved = resolve_args(&args[1..], env.clone_ref())?;
+            let mut fn_args = vec![env.get(name)?];
+            fn_args.extend_from_slice(&resolved[1..]);
+            let value = apply(&resolved[0], &fn_args, env.clone_ref())?;
+            env.set(name, value.clone())?;
+            Ok(value)
+        },
+    );
+
     env.defn(
         "fn",
         Arity::Range(1, usize::MAX),
@@ -80,13 +283,164 @@ pub fn special_forms_module() -> Env {
                 }
             };
 
-            let fun_expr = function(&args[1..], env.clone_ref())?;
-            env.def(name, fun_expr);
+            check_reserved(name)?;
+            let fun_expr = named_function(name, &args[1..], env.clone_ref())?;
+            env.define(name, fun_expr)?;
+
+            Ok(Expr::nil())
+        },
+    );
+
+    env.defn(
+        "defn-",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = match &args[0] {
+                Expr::Symbol(value) => value,
+                invalid => {
+                    return Err(HError::UnexpectedForm(
+                        "\"defn-\" requires a symbol for name".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+
+            check_reserved(name)?;
+            let fun_expr = named_function(name, &args[1..], env.clone_ref())?;
+            env.define(name, fun_expr)?;
+            mark_private(name);
+
+            Ok(Expr::nil())
+        },
+    );
+
+    env.defn(
+        "defn-curried",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = match &args[0] {
+                Expr::Symbol(value) => value,
+                invalid => {
+                    return Err(HError::UnexpectedForm(
+                        "\"defn-curried\" requires a symbol for name".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+
+            check_reserved(name)?;
+            let fun_expr = curry(function(&args[1..], env.clone_ref())?)?;
+            env.define(name, fun_expr)?;
 
             Ok(Expr::nil())
         },
     );
 
+    env.defn(
+        "declare",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            for arg in args {
+                match arg {
+                    // Existing definitions are left untouched.
+                    Expr::Symbol(name) => {
+                        check_reserved(name)?;
+                        if env.get(name).is_err() {
+                            env.define(name, Expr::Nil)?;
+                        }
+                    }
+                    invalid => {
+                        return Err(HError::UnexpectedForm(
+                            "Only symbols can be declared".to_string(),
+                            invalid.clone(),
+                        ))
+                    }
+                }
+            }
+            Ok(Expr::Nil)
+        },
+    );
+
+    env.defn(
+        "when-available",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let names = match &args[0] {
+                Expr::Vector(names) => names.clone(),
+                name => vec![name.clone()],
+            };
+            // Lets shared scripts skip code that needs a left out module.
+            for name in &names {
+                match name {
+                    Expr::Symbol(name) => {
+                        if env.get(name).is_err() {
+                            return Ok(Expr::Nil);
+                        }
+                    }
+                    invalid => {
+                        return Err(HError::UnexpectedForm(
+                            "\"when-available\" requires symbols".to_string(),
+                            invalid.clone(),
+                        ))
+                    }
+                }
+            }
+            eval_exprs(&args[1..], env)
+        },
+    );
+
+    env.defn(
+        "letfn",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let bindings = match &args[0] {
+                Expr::Vector(values) => values,
+                invalid => {
+                    return Err(HError::UnexpectedForm(
+                        "\"letfn\" requires a vector of function definitions".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+
+            // Every function closes over the same scope, so each can call the others.
+            let letfn_env = Env::extend(env).into_ref();
+            for binding in bindings {
+                match binding {
+                    Expr::List(list) if list.len() >= 2 => match &list[0] {
+                        Expr::Symbol(name) => {
+                            check_reserved(name)?;
+                            let fun = named_function(name, &list[1..], letfn_env.clone_ref())?;
+                            letfn_env.def(name, fun)?;
+                        }
+                        invalid => {
+                            return Err(HError::UnexpectedForm(
+                                "\"letfn\" requires a symbol for name".to_string(),
+                                invalid.clone(),
+                            ))
+                        }
+                    },
+                    invalid => {
+                        return Err(HError::UnexpectedForm(
+                            "Expected (name [args] body) in \"letfn\"".to_string(),
+                            invalid.clone(),
+                        ))
+                    }
+                }
+            }
+
+            eval_exprs(&args[1..], letfn_env)
+        },
+    );
+
+    env.defn(
+        "let",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            eval_exprs(&args[1..], let_bindings(&args[0], env)?)
+        },
+    );
+
     env.defn(
         "defm",
         Arity::Range(2, usize::MAX),
@@ -111,20 +465,30 @@ pub fn special_forms_module() -> Env {
                 ));
             }
 
-            if arg_len > 1 && arg_len % 2 == 1 {
+            let (name, filtered_args) = method_signature(raw_args);
+            let variadic = filtered_args.contains(&Expr::Ampersand);
+            if arg_len > 1 && arg_len % 2 == 1 && !variadic {
                 return Err(HError::UnexpectedForm(
                     "Expected an even number of arguments".to_string(),
                     args[1].clone(),
                 ));
             }
 
-            let name = method_id(raw_args);
-            let filtered_args: Vec<Expr> = method_args(raw_args);
-            let arity = Arity::Count(filtered_args.len());
+            let arity = match variadic {
+                true => Arity::Range(filtered_args.len() - 2, usize::MAX),
+                false => Arity::Count(filtered_args.len()),
+            };
 
+            // A leading string is a docstring only when more body forms follow it.
+            let mut doc = None;
             let mut code: &[Expr] = &[Expr::Nil];
-            if args.len() > 2 {
-                code = &args[2..];
+            match &args[2..] {
+                [Expr::String(value), rest @ ..] if !rest.is_empty() => {
+                    doc = Some(value.to_string());
+                    code = rest;
+                }
+                [] => (),
+                rest => code = rest,
             }
             env.defm(
                 &name,
@@ -135,8 +499,10 @@ pub fn special_forms_module() -> Env {
                     args: filtered_args.clone(),
                     function: code.into(),
                     closure: env.clone_ref(),
+                    doc,
+                    source: current_source(),
                 },
-            );
+            )?;
 
             Ok(Expr::nil())
         },
@@ -149,29 +515,26 @@ pub fn special_forms_module() -> Env {
             let var = &args[0];
             let collection = eval_expr(&args[1], env.clone_ref())?;
 
-            let var_name = match var {
-                Expr::Symbol(name) => name,
-                _ => {
-                    return Err(HError::UnexpectedForm(
-                        "Invalid variable name in for-each".to_string(),
-                        var.clone(),
-                    ))
-                }
-            };
+            if !matches!(var, Expr::Symbol(_) | Expr::Vector(_) | Expr::Map(_)) {
+                return Err(HError::UnexpectedForm(
+                    "Invalid variable name in for-each".to_string(),
+                    var.clone(),
+                ));
+            }
 
             match collection {
                 Expr::Vector(vec) => {
                     for expr in vec {
                         let new_env = Env::extend(env.clone_ref()).into_ref();
-                        new_env.def(var_name, expr);
-                        eval_exprs(&args[2..].into(), new_env)?;
+                        destructure(var, expr, &new_env)?;
+                        eval_exprs(&args[2..], new_env)?;
                     }
                 }
                 Expr::Map(map) => {
                     for (key, value) in map {
                         let new_env = Env::extend(env.clone_ref()).into_ref();
-                        new_env.def(var_name, Expr::vector(&[key, value]));
-                        eval_exprs(&args[2..].into(), new_env)?;
+                        destructure(var, Expr::vector(&[key, value]), &new_env)?;
+                        eval_exprs(&args[2..], new_env)?;
                     }
                 }
                 _ => {
@@ -186,62 +549,418 @@ pub fn special_forms_module() -> Env {
         },
     );
 
+    env.defn(
+        "quote",
+        Arity::Count(1),
+        |args: &[Expr], _: EnvRef| -> Result<Expr, HError> { Ok(args[0].clone()) },
+    );
+
+    env.defn(
+        "quasiquote",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            quasiquote(&args[0], &env, &mut BTreeMap::new())
+        },
+    );
+
+    env.defn(
+        "unquote",
+        Arity::Count(1),
+        |args: &[Expr], _: EnvRef| -> Result<Expr, HError> { outside_quasiquote("unquote", args) },
+    );
+
+    env.defn(
+        "unquote-splicing",
+        Arity::Count(1),
+        |args: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            outside_quasiquote("unquote-splicing", args)
+        },
+    );
+
+    env.defn(
+        "match",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match select_arm(args, env)? {
+                Some((body, arm_env)) => eval_expr(body, arm_env),
+                None => Ok(Expr::Nil),
+            }
+        },
+    );
+
+    env.defn(
+        "try",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let (body, name, handler) = match args.split_last() {
+                Some((Expr::List(clause), body)) => match clause.as_slice() {
+                    [Expr::Symbol(head), name, handler @ ..] if head == "catch" => {
+                        (body, name, handler)
+                    }
+                    _ => return Err(missing_catch(args)),
+                },
+                _ => return Err(missing_catch(args)),
+            };
+            match catching(|| eval_exprs(body, env.clone_ref())) {
+                Ok(value) => Ok(value),
+                // Running out of budget or being interrupted should still stop the script.
+                Err(err @ (HError::ResourceLimit(_) | HError::Interrupted)) => Err(err),
+                Err(err) => {
+                    trace::clear();
+                    let catch_env = Env::extend(env).into_ref();
+                    destructure(name, err.value(), &catch_env)?;
+                    eval_exprs(handler, catch_env)
+                }
+            }
+        },
+    );
+
+    env.defn(
+        "defmacro",
+        Arity::Range(2, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let name = match &args[0] {
+                Expr::Symbol(value) => value,
+                invalid => {
+                    return Err(HError::UnexpectedForm(
+                        "\"defmacro\" requires a symbol for name".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+
+            check_reserved(name)?;
+            // Calls only expand a macro when its name doesn't resolve, so a
+            // macro named after a var or builtin would be silently ignored.
+            if env.get(name).is_ok() {
+                return Err(HError::UnexpectedForm(
+                    "A var of the same name hides the macro".to_string(),
+                    args[0].clone(),
+                ));
+            }
+            let expander = named_function(name, &args[1..], env.clone_ref())?;
+            env.defmacro(name, expander)?;
+
+            Ok(Expr::nil())
+        },
+    );
+
     env
 }
 
-fn function(args: &[Expr], env: EnvRef) -> Result<Expr, HError> {
-    let fn_args = match &args[0] {
-        Expr::Vector(values) => values,
-        value => {
-            return Err(HError::UnexpectedForm(
-                "Expected an argument vector".to_string(),
-                value.clone(),
-            ))
+/// A symbol name starting with `prefix` that no other call returns.
+pub(crate) fn gensym(prefix: &str) -> String {
+    let id = NEXT_GENSYM.with(|next| next.replace(next.get() + 1));
+    format!("{}__{}__auto", prefix, id)
+}
+
+/// Copies `template`, replacing each `unquote`d form with its value and
+/// splicing in the elements of each `unquote-splicing`d one. Each symbol
+/// ending in `#`, such as `x#`, is replaced by the same fresh name throughout,
+/// which `names` remembers.
+fn quasiquote(
+    template: &Expr,
+    env: &EnvRef,
+    names: &mut BTreeMap<String, String>,
+) -> Result<Expr, HError> {
+    match template {
+        Expr::List(list) => match list.as_slice() {
+            [Expr::Symbol(head), form] if head == "unquote" => eval_expr(form, env.clone_ref()),
+            _ => Ok(Expr::List(quasiquote_all(list, env, names)?)),
+        },
+        Expr::MethodList(list) => Ok(Expr::MethodList(quasiquote_all(list, env, names)?)),
+        Expr::Vector(list) => Ok(Expr::Vector(quasiquote_all(list, env, names)?)),
+        Expr::Map(map) => map
+            .iter()
+            .map(|(key, value)| Ok((quasiquote(key, env, names)?, quasiquote(value, env, names)?)))
+            .collect::<Result<_, _>>()
+            .map(Expr::Map),
+        Expr::Symbol(symbol) if symbol.len() > 1 && symbol.ends_with('#') => {
+            let name = names
+                .entry(symbol.to_string())
+                .or_insert_with(|| gensym(&symbol[..symbol.len() - 1]));
+            Ok(Expr::symbol(name))
         }
-    };
+        _ => Ok(template.clone()),
+    }
+}
 
-    let mut arity = Arity::Count(fn_args.len());
-    for (i, arg) in fn_args.iter().enumerate() {
-        match arg {
-            Expr::Ampersand => {
-                arity = Arity::Range(i, usize::MAX);
-                if fn_args.len() != i + 2 {
-                    return Err(HError::UnexpectedForm(
-                        "In fn declaration, & can only proceed one symbol".to_string(),
-                        arg.clone(),
-                    ));
+fn quasiquote_all(
+    templates: &[Expr],
+    env: &EnvRef,
+    names: &mut BTreeMap<String, String>,
+) -> Result<Vec<Expr>, HError> {
+    let mut result = Vec::with_capacity(templates.len());
+    for template in templates {
+        match template {
+            Expr::List(list) if matches!(list.as_slice(), [Expr::Symbol(head), _] if head == "unquote-splicing") => {
+                match eval_expr(&list[1], env.clone_ref())? {
+                    Expr::List(values) | Expr::Vector(values) => result.extend(values),
+                    Expr::Nil => (),
+                    invalid => {
+                        return Err(HError::InvalidType("unquote-splicing".to_string(), invalid))
+                    }
                 }
-                break;
             }
-            _ => (),
+            template => result.push(quasiquote(template, env, names)?),
         }
     }
+    Ok(result)
+}
 
-    let mut code: &[Expr] = &[Expr::Nil];
-    if args.len() > 1 {
-        code = &args[1..];
-    }
+fn missing_catch(args: &[Expr]) -> HError {
+    let mut list = vec![Expr::symbol("try")];
+    list.extend_from_slice(args);
+    HError::UnexpectedForm(
+        "\"try\" must end with a (catch name body...) clause".to_string(),
+        Expr::List(list),
+    )
+}
 
-    Ok(Expr::Fn(Fn {
-        id: format!("{:?}_{:?}", fn_args, code),
-        arity,
-        args: fn_args.clone(),
-        function: code.into(),
-        closure: env.clone_ref(),
-    }))
+fn outside_quasiquote(form: &str, args: &[Expr]) -> Result<Expr, HError> {
+    let mut list = vec![Expr::symbol(form)];
+    list.extend_from_slice(args);
+    Err(HError::UnexpectedForm(
+        format!("\"{}\" is only valid inside a quasiquote", form),
+        Expr::List(list),
+    ))
 }
 
-#[cfg(test)]
-mod tests {
+/// Defines the symbols in `pattern` from the matching parts of `value`, as
+/// described by `bind_pattern`.
+pub(crate) fn destructure(pattern: &Expr, value: Expr, env: &EnvRef) -> Result<(), HError> {
+    let mut bindings = vec![];
+    bind_pattern(pattern, value, &mut bindings)?;
+    for (name, value) in bindings {
+        env.define(&name, value)?;
+    }
+    Ok(())
+}
+
+/// Adds the symbols in `pattern` to `bindings` with the matching parts of
+/// `value`. A vector pattern takes a vector apart, binding `nil` to names
+/// past its end, or collecting the rest after `&`. A map pattern, such as
+/// `{:name n}`, binds the value under each key, or `nil` if it is missing.
+/// Either takes `nil` apart as if it were empty.
+pub(crate) fn bind_pattern(
+    pattern: &Expr,
+    value: Expr,
+    bindings: &mut Vec<(String, Expr)>,
+) -> Result<(), HError> {
+    match (pattern, value) {
+        (Expr::Symbol(name), value) => {
+            check_reserved(name)?;
+            bindings.push((name.to_string(), value));
+            Ok(())
+        }
+        (Expr::Vector(_), Expr::Nil) => bind_pattern(pattern, Expr::Vector(vec![]), bindings),
+        (Expr::Map(_), Expr::Nil) => bind_pattern(pattern, Expr::Map(BTreeMap::new()), bindings),
+        (Expr::Vector(patterns), Expr::Vector(values)) => {
+            let (patterns, rest) = match patterns.iter().position(|p| p == &Expr::Ampersand) {
+                Some(i) if i + 2 == patterns.len() => (&patterns[..i], Some(&patterns[i + 1])),
+                Some(i) => {
+                    return Err(HError::UnexpectedForm(
+                        "& must be followed by one pattern".to_string(),
+                        patterns[i].clone(),
+                    ))
+                }
+                None => (&patterns[..], None),
+            };
+            let mut values = values.into_iter();
+            for pattern in patterns {
+                bind_pattern(pattern, values.next().unwrap_or(Expr::Nil), bindings)?;
+            }
+            if let Some(rest) = rest {
+                bind_pattern(rest, Expr::Vector(values.collect()), bindings)?;
+            }
+            Ok(())
+        }
+        (Expr::Map(fields), Expr::Map(mut entries)) => {
+            for (key, pattern) in fields {
+                bind_pattern(pattern, entries.remove(key).unwrap_or(Expr::Nil), bindings)?;
+            }
+            Ok(())
+        }
+        (Expr::Vector(_) | Expr::Map(_), invalid) => {
+            Err(HError::InvalidType("destructure".to_string(), invalid))
+        }
+        (invalid, _) => Err(HError::UnexpectedForm(
+            "Only symbols, vectors, and maps can be bound".to_string(),
+            invalid.clone(),
+        )),
+    }
+}
+
+/// Creates the scope of a `let`, evaluating each `[pattern value]` pair of
+/// `bindings` in order so later values can use earlier names.
+pub(crate) fn let_bindings(bindings: &Expr, env: EnvRef) -> Result<EnvRef, HError> {
+    let pairs = match bindings {
+        Expr::Vector(pairs) if pairs.len() % 2 == 0 => pairs,
+        invalid => {
+            return Err(HError::UnexpectedForm(
+                "\"let\" requires a vector of name and value pairs".to_string(),
+                invalid.clone(),
+            ))
+        }
+    };
+    let let_env = Env::extend(env).into_ref();
+    for pair in pairs.chunks(2) {
+        let value = eval_expr(&pair[1], let_env.clone_ref())?;
+        destructure(&pair[0], value, &let_env)?;
+    }
+    Ok(let_env)
+}
+
+/// Fails if `name` is a special form and redefining them isn't allowed.
+fn check_reserved(name: &str) -> Result<(), HError> {
+    if RESERVED.contains(&name) && !allow_redefine() {
+        return Err(HError::ReservedName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Splits the `[name value]` binding of `form`.
+pub(crate) fn let_binding<'a>(
+    form: &str,
+    binding: &'a Expr,
+) -> Result<(&'a str, &'a Expr), HError> {
+    match binding {
+        Expr::Vector(pair) => match pair.as_slice() {
+            [Expr::Symbol(name), value] => Ok((name, value)),
+            _ => Err(HError::UnexpectedForm(
+                format!("\"{}\" requires a [name value] binding", form),
+                binding.clone(),
+            )),
+        },
+        invalid => Err(HError::UnexpectedForm(
+            format!("\"{}\" requires a [name value] binding", form),
+            invalid.clone(),
+        )),
+    }
+}
+
+/// Builds a function that displays as `name`.
+fn named_function(name: &str, args: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    let mut fun = function(args, env)?;
+    if let Expr::Fn(fun) = &mut fun {
+        fun.name = Some(name.to_string());
+    }
+    Ok(fun)
+}
+
+fn function(args: &[Expr], env: EnvRef) -> Result<Expr, HError> {
+    // A named fn binds its own name in a scope visible only to its body.
+    if let [Expr::Symbol(name), rest @ ..] = args {
+        if let Some(Expr::Vector(_)) = rest.first() {
+            let fn_env = Env::extend(env).into_ref();
+            let fun = named_function(name, rest, fn_env.clone_ref())?;
+            fn_env.def(name, fun.clone())?;
+            return Ok(fun);
+        }
+    }
+
+    let params = match &args[0] {
+        Expr::Vector(values) => values,
+        value => {
+            return Err(HError::UnexpectedForm(
+                "Expected an argument vector".to_string(),
+                value.clone(),
+            ))
+        }
+    };
+
+    let (fn_args, hints) = split_hints(params)?;
+    let mut arity = Arity::Count(fn_args.len());
+    for (i, arg) in fn_args.iter().enumerate() {
+        if arg == &Expr::Ampersand {
+            arity = Arity::Range(i, usize::MAX);
+            if fn_args.len() != i + 2 {
+                return Err(HError::UnexpectedForm(
+                    "In fn declaration, & can only proceed one symbol".to_string(),
+                    arg.clone(),
+                ));
+            }
+            break;
+        }
+    }
+
+    let mut code: &[Expr] = &[Expr::Nil];
+    if args.len() > 1 {
+        code = &args[1..];
+    }
+
+    Ok(Expr::Fn(Fn {
+        id: format!("{:?}_{:?}", params, code).into(),
+        name: None,
+        arity,
+        args: fn_args,
+        hints,
+        function: code.into(),
+        closure: env.clone_ref(),
+        source: current_source(),
+        live: Live::new(Kind::Fn),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
     use crate::{
         env::Env,
         evaluator::{eval, get_first_method_matching},
     };
 
+    #[test]
+    fn test_defn_curried() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defn-curried add [a b c] (+ a b c))
+            (var add-1 (add 1))
+            (var add-3 (add-1 2))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(add-3 3)", env.clone_ref()), Ok(Expr::number(6.)));
+        assert_eq!(
+            eval("(add-1 1 2)", env),
+            Err(HError::InvalidArity(
+                "curry".to_string(),
+                Arity::Count(1),
+                2
+            ))
+        );
+    }
+
+    #[test]
+    fn test_when_available() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(when-available println 1 2)", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+        assert_eq!(
+            eval(
+                "(when-available [println http-get] (http-get))",
+                env.clone_ref()
+            ),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval("(when-available 1 2)", env),
+            Err(HError::UnexpectedForm(
+                "\"when-available\" requires symbols".to_string(),
+                Expr::number(1.)
+            ))
+        );
+    }
+
     #[test]
     fn test_defm() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval(
             "(defm (fn [i] i) [to: n do: f] (println num))",
@@ -262,25 +981,86 @@ mod tests {
         assert_eq!(
             method.selector,
             Box::new(Expr::Fn(Fn {
-                id: "[Symbol(\"i\")]_[Symbol(\"i\")]".to_string(),
+                id: "[Symbol(\"i\")]_[Symbol(\"i\")]".into(),
+                name: None,
                 arity: Arity::Count(1),
                 args: vec![Expr::Symbol("i".to_string())],
+                hints: vec![None],
                 function: vec![Expr::Symbol("i".to_string())].into(),
                 closure: env.clone_ref(),
+                source: None,
+                live: Live::new(Kind::Fn),
             }))
         );
         assert_eq!(
-            method.function,
-            vec![Expr::list(&[
+            *method.function,
+            [Expr::list(&[
                 Expr::Symbol("println".to_string()),
                 Expr::Symbol("num".to_string())
             ])]
         );
     }
 
+    #[test]
+    fn test_defm_rest_args() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(defm number? [add: n & more] [this n more])
+             (defm number? [sum: & all] all)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("<1 add: 2 3 4>", env.clone_ref()),
+            eval("[1 2 [3 4]]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("<1 add: 2>", env.clone_ref()),
+            eval("[1 2 []]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("<1 sum:>", env.clone_ref()),
+            eval("[]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("<1 add:>", env),
+            Err(HError::InvalidArity(
+                "add &".to_string(),
+                Arity::Range(1, usize::MAX),
+                0
+            ))
+        );
+    }
+
+    #[test]
+    fn test_defm_docstring() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defm number? [twice] \"Doubles a number.\" (* this 2))
+            (defm number? [greeting] \"hello\")",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        let twice = &env.get_methods("twice").unwrap()[0];
+        assert_eq!(twice.doc, Some("Doubles a number.".to_string()));
+        assert_eq!(eval("<2 twice>", env.clone_ref()), Ok(Expr::number(4.)));
+
+        let greeting = &env.get_methods("greeting").unwrap()[0];
+        assert_eq!(greeting.doc, None);
+        assert_eq!(
+            eval("<2 greeting>", env.clone_ref()),
+            Ok(Expr::string("hello"))
+        );
+    }
+
     #[test]
     fn test_defn() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(defn add [a b] (+ a b))", env.clone_ref()).unwrap();
 
@@ -292,8 +1072,8 @@ mod tests {
                     vec![Expr::Symbol("a".to_string()), Expr::Symbol("b".to_string())]
                 );
                 assert_eq!(
-                    fun.function,
-                    vec![Expr::list(&[
+                    *fun.function,
+                    [Expr::list(&[
                         Expr::Symbol("+".to_string()),
                         Expr::Symbol("a".to_string()),
                         Expr::Symbol("b".to_string())
@@ -304,9 +1084,104 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_defn_forward_references() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (defn is-even? [n] (if (= n 0) true (is-odd? (- n 1))))
+            (defn is-odd? [n] (if (= n 0) false (is-even? (- n 1))))
+            (defn a [] (b))
+            (defn b [] 1)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(is-even? 10)", env.clone_ref()),
+            Ok(Expr::boolean(true))
+        );
+        assert_eq!(eval("(a)", env.clone_ref()), Ok(Expr::number(1.)));
+
+        // Bodies resolve symbols when called, so redefinitions are picked up.
+        eval("(defn b [] 2)", env.clone_ref()).unwrap();
+        assert_eq!(eval("(a)", env.clone_ref()), Ok(Expr::number(2.)));
+    }
+
+    #[test]
+    fn test_declare() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var b 5) (declare a b)", env.clone_ref()).unwrap();
+
+        assert_eq!(env.get("a"), Ok(Expr::Nil));
+        assert_eq!(env.get("b"), Ok(Expr::number(5.)));
+
+        eval("(set! a 1)", env.clone_ref()).unwrap();
+        assert_eq!(env.get("a"), Ok(Expr::number(1.)));
+    }
+
+    #[test]
+    fn test_type_hints() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(defn add [^number a ^number b] (+ a b))
+             (defn count-all [& ^vector xs] xs)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        // Hints are only enforced once type checking is turned on.
+        assert_eq!(
+            eval("(add 1 \"2\")", env.clone_ref()),
+            Err(HError::InvalidType("+".to_string(), Expr::string("2")))
+        );
+        eval("(set! *check-types* true)", env.clone_ref()).unwrap();
+        assert_eq!(eval("(add 1 2)", env.clone_ref()), Ok(Expr::number(3.)));
+        assert_eq!(
+            eval("(add 1 \"2\")", env.clone_ref()),
+            Err(HError::TypeMismatch(
+                "b".to_string(),
+                "number".to_string(),
+                Expr::string("2")
+            ))
+        );
+        assert_eq!(
+            eval("(count-all 1 2)", env.clone_ref()),
+            eval("[1 2]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(fn [^integer n] n)", env),
+            Err(HError::UnexpectedForm(
+                "Invalid type hint".to_string(),
+                Expr::symbol("^integer")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_defonce() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "
+            (var opened (atom 0))
+            (defn open [] (swap! opened (fn [n] (+ n 1))))
+            (defonce conn (open))
+            (defonce conn (open))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(env.get("conn"), Ok(Expr::number(1.)));
+        assert_eq!(eval("(deref opened)", env), Ok(Expr::number(1.)));
+    }
+
     #[test]
     fn test_def() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         let result = eval("(var a 2) (+ a 1)", env);
 
@@ -315,16 +1190,27 @@ mod tests {
 
     #[test]
     fn test_def_overwrite() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var a 2) (var a (+ a 1))", env.clone_ref()).unwrap();
 
         assert_eq!(env.get("a"), Ok(Expr::number(3.)));
     }
 
+    #[test]
+    fn test_def_without_value() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(var a)", env.clone_ref()),
+            Err(HError::InvalidArity("var".to_string(), Arity::Count(2), 1))
+        );
+        assert_eq!(env.get("a"), Err(HError::UnboundVar("a".to_string())));
+    }
+
     #[test]
     fn test_if() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         assert_eq!(
             eval("(if (lt 1 2) 1 2)", env.clone_ref()),
@@ -347,16 +1233,42 @@ mod tests {
 
     #[test]
     fn test_set() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var a 2) (set! a 1) a", env.clone_ref()).unwrap();
 
         assert_eq!(env.get("a"), Ok(Expr::number(1.)));
     }
 
+    #[test]
+    fn test_special_forms_are_reserved() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        for form in [
+            "(var if 5)",
+            "(defn fn [] 1)",
+            "(set! defm 1)",
+            "(var [a for-each] [1 2])",
+        ] {
+            assert!(matches!(
+                eval(form, env.clone_ref()),
+                Err(HError::ReservedName(_))
+            ));
+        }
+        assert_eq!(
+            eval("(letfn [(var [] 1)] 2)", env.clone_ref()),
+            Err(HError::ReservedName("var".to_string()))
+        );
+        let forms = special_forms_module().into_ref();
+        for name in RESERVED {
+            assert!(forms.get(name).is_ok(), "{} is not a special form", name);
+        }
+        assert_eq!(eval("(if true 1 2)", env), Ok(Expr::number(1.)));
+    }
+
     #[test]
     fn test_set_error_on_unset() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         let result = eval("(set! a 1)", env.clone_ref());
 
@@ -365,7 +1277,7 @@ mod tests {
 
     #[test]
     fn test_creates_lambdas() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var f (fn [a b] (+ a b)))", env.clone_ref()).unwrap();
 
@@ -374,7 +1286,7 @@ mod tests {
 
     #[test]
     fn test_creates_variadic_lambdas() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var f (fn [a &b] (println a b)))", env.clone_ref()).unwrap();
 
@@ -404,9 +1316,374 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_named_lambdas_can_recurse() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(var fact (fn f [n] (if (lte n 1) 1 (* n (f (- n 1))))))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(fact 5)", env.clone_ref()), Ok(Expr::number(120.)));
+        assert_eq!(
+            eval("f", env.clone_ref()),
+            Err(HError::UnboundVar("f".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_var_destructures_vectors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var [ok [a b] c] [true [1 2]])", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("[ok a b c]", env.clone_ref()),
+            eval("[true 1 2 nil]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(var [x] 1)", env.clone_ref()),
+            Err(HError::InvalidType(
+                "destructure".to_string(),
+                Expr::number(1.)
+            ))
+        );
+        assert!(matches!(
+            eval("(var [:x] [1])", env.clone_ref()),
+            Err(HError::UnexpectedForm(_, _))
+        ));
+        assert_eq!(
+            eval("(var [a b])", env.clone_ref()),
+            Err(HError::InvalidArity("var".to_string(), Arity::Count(2), 1))
+        );
+
+        eval(
+            "(var {:a a [:b] [b & more]} {:a 1 [:b] [2 3 4]})",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(
+            eval("[a b more]", env.clone_ref()),
+            eval("[1 2 [3 4]]", env.clone_ref())
+        );
+        assert_eq!(
+            eval(
+                "(var total 0) (for-each [k v] {:x 1 :y 2} (set! total (+ total v))) total",
+                env
+            ),
+            Ok(Expr::number(3.))
+        );
+    }
+
+    #[test]
+    fn test_if_let_and_when_let() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var m {:a 1 :b false})", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(if-let [x (get m :a)] (+ x 1) :none)", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+        assert_eq!(
+            eval("(if-let [x (get m :b)] x :none)", env.clone_ref()),
+            Ok(Expr::keyword(":none"))
+        );
+        assert_eq!(
+            eval("(if-let [x (get m :c)] x)", env.clone_ref()),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval(
+                "(when-let [x (get m :a)] (var y x) (+ x y))",
+                env.clone_ref()
+            ),
+            Ok(Expr::number(2.))
+        );
+        assert_eq!(
+            eval(
+                "(when-let [x (get m :c)] (undefined-fn x))",
+                env.clone_ref()
+            ),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval("x", env.clone_ref()),
+            Err(HError::UnboundVar("x".to_string()))
+        );
+        assert!(matches!(
+            eval("(if-let [x] x)", env),
+            Err(HError::UnexpectedForm(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_let_binds_sequentially_in_its_own_scope() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "(var b 10) (let [a 1 b (+ a 1) [c d] [b 4]] (var e 5) (+ a b c d e))",
+                env.clone_ref()
+            ),
+            Ok(Expr::number(14.))
+        );
+        assert_eq!(env.get("b"), Ok(Expr::number(10.)));
+        assert!(env.get("a").is_err());
+        assert!(env.get("e").is_err());
+        assert_eq!(eval("(let [])", env.clone_ref()), Ok(Expr::Nil));
+        assert!(matches!(
+            eval("(let [a] a)", env),
+            Err(HError::UnexpectedForm(..))
+        ));
+    }
+
+    #[test]
+    fn test_and_or_short_circuit() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(var calls (atom 0)) (defn touch [v] (swap! calls (fn [n] (+ n 1))) v)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(eval("(and)", env.clone_ref()), Ok(Expr::boolean(true)));
+        assert_eq!(eval("(or)", env.clone_ref()), Ok(Expr::Nil));
+        assert_eq!(
+            eval("(and 1 :ok)", env.clone_ref()),
+            Ok(Expr::keyword(":ok"))
+        );
+        assert_eq!(
+            eval("(and 1 nil (touch 2))", env.clone_ref()),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval("(or false 2 (touch 3))", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+        assert_eq!(
+            eval("(or nil false)", env.clone_ref()),
+            Ok(Expr::boolean(false))
+        );
+        assert_eq!(eval("(deref calls)", env), Ok(Expr::number(0.)));
+    }
+
+    #[test]
+    fn test_quote_and_quasiquote() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("'(f x)", env.clone_ref()),
+            Ok(Expr::list(&[Expr::symbol("f"), Expr::symbol("x")]))
+        );
+        assert_eq!(eval("(quote a)", env.clone_ref()), Ok(Expr::symbol("a")));
+        assert_eq!(
+            eval(
+                "(var n 2) (var xs [3 4]) `(+ 1 ~n ~@xs {:n ~n} [~@nil])",
+                env.clone_ref()
+            ),
+            eval("'(+ 1 2 3 4 {:n 2} [])", env.clone_ref())
+        );
+        // Quoted values passed back into functions aren't evaluated again.
+        assert_eq!(
+            eval(
+                "(var code (atom 'x)) (swap! code (fn [c] `(inc ~c))) (deref code)",
+                env.clone_ref()
+            ),
+            Ok(Expr::list(&[Expr::symbol("inc"), Expr::symbol("x")]))
+        );
+        assert!(matches!(
+            eval("`(~@1)", env.clone_ref()),
+            Err(HError::InvalidType(..))
+        ));
+        assert!(matches!(
+            eval("(unquote n)", env),
+            Err(HError::UnexpectedForm(..))
+        ));
+    }
+
+    #[test]
+    fn test_match() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(defn area [shape]
+               (match shape
+                 {:type :circle :r r} (* 3 r r)
+                 {:type :rect :w w :h h} (* w h)
+                 [w h] (* w h)
+                 _ :unknown))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(area {:type :circle :r 2})", env.clone_ref()),
+            Ok(Expr::number(12.))
+        );
+        assert_eq!(
+            eval("(area {:type :rect :w 2 :h 5})", env.clone_ref()),
+            Ok(Expr::number(10.))
+        );
+        assert_eq!(eval("(area [3 4])", env.clone_ref()), Ok(Expr::number(12.)));
+        assert_eq!(
+            eval("(area \"square\")", env.clone_ref()),
+            Ok(Expr::keyword(":unknown"))
+        );
+        // Bindings only last for their arm.
+        assert_eq!(
+            eval("(var x 1) (match [2] [x] x) x", env.clone_ref()),
+            Ok(Expr::number(1.))
+        );
+        assert_eq!(eval("(match 3 1 :one)", env.clone_ref()), Ok(Expr::Nil));
+        // Arms are in tail position.
+        assert_eq!(
+            eval(
+                "(defn total [xs acc] (match xs [] acc [x & rest] (total rest (+ acc x))))
+                 (total (range 0 1000) 0)",
+                env.clone_ref()
+            ),
+            Ok(Expr::number(499500.))
+        );
+        assert!(matches!(
+            eval("(match 1 _)", env),
+            Err(HError::UnexpectedForm(..))
+        ));
+    }
+
+    #[test]
+    fn test_defmacro() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(defmacro unless [test & body] `(if ~test nil (let [] ~@body)))
+             (defmacro swap-pair! [a b] `(let [tmp# ~a] (set! ~a ~b) (set! ~b tmp#)))
+             (var calls 0)",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        // Arguments aren't evaluated unless the expansion runs them.
+        assert_eq!(
+            eval("(unless true (set! calls 1)) calls", env.clone_ref()),
+            Ok(Expr::number(0.))
+        );
+        assert_eq!(
+            eval("(unless false (set! calls 2) calls)", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+        // The generated name can't capture a user's `tmp`.
+        assert_eq!(
+            eval(
+                "(var tmp 1) (var x 2) (swap-pair! tmp x) [tmp x]",
+                env.clone_ref()
+            ),
+            eval("[2 1]", env.clone_ref())
+        );
+        // Expansions in tail position reuse the caller's frame.
+        assert_eq!(
+            eval(
+                "(defn count-down [n] (unless (lte n 0) (count-down (- n 1))))
+                 (count-down 10000)",
+                env.clone_ref()
+            ),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval("(macroexpand '(unless a b))", env.clone_ref()),
+            eval("'(if a nil (let [] b))", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(macroexpand-1 '(f a))", env.clone_ref()),
+            eval("'(f a)", env.clone_ref())
+        );
+        // A var of the same name hides the macro.
+        assert_eq!(
+            eval("(defn unless [x] :fn) (unless true)", env.clone_ref()),
+            Ok(Expr::keyword(":fn"))
+        );
+        assert_eq!(
+            eval("(defmacro defn [] nil)", env.clone_ref()),
+            Err(HError::ReservedName("defn".to_string()))
+        );
+        assert_eq!(
+            eval("(defmacro first [x] `(quote macro))", env.clone_ref()),
+            Err(HError::UnexpectedForm(
+                "A var of the same name hides the macro".to_string(),
+                Expr::symbol("first")
+            ))
+        );
+        assert_eq!(eval("(first [5 6])", env), Ok(Expr::number(5.)));
+    }
+
+    #[test]
+    fn test_some_thread_and_or_else() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var m {:a {:b [10 20]}})", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(some-> m (get :a) (get :b) (get 1))", env.clone_ref()),
+            Ok(Expr::number(20.))
+        );
+        assert_eq!(
+            eval("(some-> m (get :x) (get :b) (get 1))", env.clone_ref()),
+            Ok(Expr::Nil)
+        );
+        assert_eq!(
+            eval("(some-> 2 (+ 1) pr-str)", env.clone_ref()),
+            Ok(Expr::string("3"))
+        );
+        assert_eq!(
+            eval("(or-else (some-> m (get :x)) :default)", env.clone_ref()),
+            Ok(Expr::keyword(":default"))
+        );
+        assert_eq!(
+            eval("(or-else false (undefined-fn))", env),
+            Ok(Expr::boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_letfn_mutual_recursion() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "
+                (letfn [(even? [n] (if (= n 0) true (odd? (- n 1))))
+                        (odd? [n] (if (= n 0) false (even? (- n 1))))]
+                    [(even? 10) (odd? 7) (even? 3)])",
+                env.clone_ref()
+            ),
+            Ok(Expr::vector(&[
+                Expr::boolean(true),
+                Expr::boolean(true),
+                Expr::boolean(false)
+            ]))
+        );
+        assert_eq!(
+            eval("even?", env.clone_ref()),
+            Err(HError::UnboundVar("even?".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_letfn_rejects_invalid_bindings() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(letfn [(1 [n] n)] nil)", env.clone_ref()),
+            Err(HError::UnexpectedForm(
+                "\"letfn\" requires a symbol for name".to_string(),
+                Expr::number(1.)
+            ))
+        );
+    }
+
     #[test]
     fn test_empty_lambda_returns_nil() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
 
         eval("(var f (fn []))", env.clone_ref()).unwrap();
 
@@ -415,7 +1692,7 @@ mod tests {
 
     #[test]
     fn test_for_each() {
-        let env = Env::with_core_module().into_ref();
+        let env = Env::with_core_module().unwrap().into_ref();
         eval(
             "
             (var a 1)
@@ -425,5 +1702,101 @@ mod tests {
         )
         .unwrap();
         assert_eq!(env.get("a"), Ok(Expr::number(16.)));
+
+        // Each iteration has its own scope, so vars defined in the body and
+        // the loop variable captured by closures are not shared.
+        eval(
+            "
+            (var fns [])
+            (var total 0)
+            (for-each i [1 2 3]
+                (var doubled (* i 2))
+                (set! total (+ total doubled))
+                (set! fns [(fn [] i) fns]))",
+            env.clone_ref(),
+        )
+        .unwrap();
+        assert_eq!(env.get("total"), Ok(Expr::number(12.)));
+        assert_eq!(
+            eval(
+                "(var last (get fns 0)) (var previous (get (get fns 1) 0)) [(last) (previous)]",
+                env.clone_ref()
+            ),
+            eval("[3 2]", env.clone_ref())
+        );
+        assert_eq!(
+            env.get("doubled"),
+            Err(HError::UnboundVar("doubled".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_swap_var() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval(
+                "
+                (var count 0)
+                (defn bump [n] (swap-var! count + n))
+                (for-each i [1 2 3] (bump i))
+                (swap-var! count * 2)",
+                env.clone_ref()
+            ),
+            Ok(Expr::number(12.))
+        );
+        assert_eq!(env.get("count"), Ok(Expr::number(12.)));
+        assert_eq!(
+            eval("(swap-var! missing + 1)", env),
+            Err(HError::UnboundVar("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_try_catches_thrown_values() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(try (throw :oops) 1 (catch e e))", env.clone_ref()),
+            Ok(Expr::keyword(":oops"))
+        );
+        assert_eq!(
+            eval(
+                "(try (throw {:reason \"bad\"}) (catch {:reason r} r))",
+                env.clone_ref()
+            ),
+            Ok(Expr::string("bad"))
+        );
+        assert_eq!(
+            eval("(try (+ 1 2) (catch e :unused))", env.clone_ref()),
+            Ok(Expr::number(3.))
+        );
+        assert_eq!(
+            eval("(throw :uncaught)", env),
+            Err(HError::UserError(Expr::keyword(":uncaught")))
+        );
+    }
+
+    #[test]
+    fn test_try_catches_runtime_errors() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(defn one [x] x)", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(try (one 1 2) (catch e (error-code e)))", env.clone_ref()),
+            Ok(Expr::string("E010"))
+        );
+        assert_eq!(
+            eval("(try (/ 1 :a) (catch e (get e :code)))", env.clone_ref()),
+            Ok(Expr::string("E011"))
+        );
+        assert!(matches!(
+            eval("(try (throw 1))", env.clone_ref()),
+            Err(HError::UnexpectedForm(..))
+        ));
+        assert!(matches!(
+            eval("(try (throw 1) (handle e e))", env),
+            Err(HError::UnexpectedForm(..))
+        ));
     }
 }
@@ -0,0 +1,166 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    modules::{num_vec::numbers, utils::check_num},
+};
+
+/// Each function takes a vector of numbers or a num-vec. Everything but
+/// `sum` fails with an `EmptyCollection` error when given no numbers.
+pub fn statistics_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "sum",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = sample("sum", args, env)?;
+            Ok(Expr::number(values.iter().sum()))
+        },
+    );
+
+    env.defn(
+        "mean",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = non_empty("mean", sample("mean", args, env)?)?;
+            Ok(Expr::number(mean(&values)))
+        },
+    );
+
+    env.defn(
+        "median",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = non_empty("median", sorted(sample("median", args, env)?))?;
+            Ok(Expr::number(percentile(&values, 50.)))
+        },
+    );
+
+    env.defn(
+        "stddev",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = non_empty("stddev", sample("stddev", args, env)?)?;
+            let mean = mean(&values);
+            let variance = values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                / values.len() as f64;
+            Ok(Expr::number(variance.sqrt()))
+        },
+    );
+
+    env.defn(
+        "percentile",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = non_empty(
+                "percentile",
+                sorted(sample("percentile", args, env.clone_ref())?),
+            )?;
+            let p = resolve_args(&args[1..], env)?.remove(0);
+            match check_num(&p, "percentile")? {
+                rank if (0. ..=100.).contains(&rank) => Ok(Expr::number(percentile(&values, rank))),
+                _ => Err(HError::InvalidType("percentile".to_string(), p)),
+            }
+        },
+    );
+
+    env
+}
+
+/// The numbers in the collection passed as the first of `args`.
+fn sample(name: &str, args: &[Expr], env: EnvRef) -> Result<Vec<f64>, HError> {
+    let collection = resolve_args(&args[..1], env)?.remove(0);
+    Ok(numbers(name, &collection)?
+        .into_iter()
+        .map(|value| *value)
+        .collect())
+}
+
+fn non_empty(name: &str, values: Vec<f64>) -> Result<Vec<f64>, HError> {
+    match values.is_empty() {
+        true => Err(HError::EmptyCollection(name.to_string())),
+        false => Ok(values),
+    }
+}
+
+fn sorted(mut values: Vec<f64>) -> Vec<f64> {
+    values.sort_by(f64::total_cmp);
+    values
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The `rank`th percentile of sorted `values`, interpolating linearly
+/// between the two nearest values.
+fn percentile(values: &[f64], rank: f64) -> f64 {
+    let position = rank / 100. * (values.len() - 1) as f64;
+    let (low, high) = (position.floor() as usize, position.ceil() as usize);
+    values[low] + (values[high] - values[low]) * (position - low as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{env::Env, error::HError, evaluator::eval, expr::Expr};
+
+    #[test]
+    fn test_summarizes_samples() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v [2 4 4 4 5 5 7 9])", env.clone_ref()).unwrap();
+        assert_eq!(eval("(sum v)", env.clone_ref()), Ok(Expr::number(40.)));
+        assert_eq!(eval("(mean v)", env.clone_ref()), Ok(Expr::number(5.)));
+        assert_eq!(eval("(median v)", env.clone_ref()), Ok(Expr::number(4.5)));
+        assert_eq!(eval("(stddev v)", env.clone_ref()), Ok(Expr::number(2.)));
+        assert_eq!(
+            eval("(median (num-vec [3 1 2]))", env.clone_ref()),
+            Ok(Expr::number(2.))
+        );
+    }
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var v [10 20 30 40 50])", env.clone_ref()).unwrap();
+        assert_eq!(
+            eval("(percentile v 0)", env.clone_ref()),
+            Ok(Expr::number(10.))
+        );
+        assert_eq!(
+            eval("(percentile v 90)", env.clone_ref()),
+            Ok(Expr::number(46.))
+        );
+        assert_eq!(
+            eval("(percentile v 100)", env.clone_ref()),
+            Ok(Expr::number(50.))
+        );
+        assert!(matches!(
+            eval("(percentile v 101)", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_empty_samples() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(eval("(sum [])", env.clone_ref()), Ok(Expr::number(0.)));
+        for name in ["mean", "median", "stddev"] {
+            assert_eq!(
+                eval(&format!("({} [])", name), env.clone_ref()),
+                Err(HError::EmptyCollection(name.to_string()))
+            );
+        }
+        assert_eq!(
+            eval("(percentile (num-vec []) 50)", env),
+            Err(HError::EmptyCollection("percentile".to_string()))
+        );
+    }
+}
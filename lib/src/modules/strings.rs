@@ -0,0 +1,275 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr, PrintLimits},
+    interpreter::{check_collection_len, check_string_len},
+    modules::native::check_index,
+};
+
+/// Positions and lengths count characters rather than bytes. `str`, which
+/// interpolated strings are read as, and `contains?`, which also searches
+/// vectors, are natives so they are always available.
+pub fn strings_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "len",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let s = check_string(&resolved[0], "len")?;
+            Ok(Expr::number(s.chars().count() as f64))
+        },
+    );
+
+    env.defn(
+        "substring",
+        Arity::Range(2, 3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let chars: Vec<char> = check_string(&resolved[0], "substring")?.chars().collect();
+            let start = check_index(&resolved[1], chars.len(), "substring")?;
+            let end = match resolved.get(2) {
+                Some(end) => check_index(end, chars.len(), "substring")?,
+                None => chars.len(),
+            };
+            if end < start {
+                return Err(HError::IndexOutOfBounds(
+                    "substring".to_string(),
+                    resolved[2].clone(),
+                    chars.len(),
+                ));
+            }
+            Ok(Expr::String(chars[start..end].iter().collect()))
+        },
+    );
+
+    env.defn(
+        "char-at",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let chars: Vec<char> = check_string(&resolved[0], "char-at")?.chars().collect();
+            let index = check_index(&resolved[1], chars.len(), "char-at")?;
+            match chars.get(index) {
+                Some(c) => Ok(Expr::String(c.to_string())),
+                // The end of the string isn't a character.
+                None => Err(HError::IndexOutOfBounds(
+                    "char-at".to_string(),
+                    resolved[1].clone(),
+                    chars.len(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "split",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let s = check_string(&resolved[0], "split")?;
+            let parts: Vec<Expr> = match check_string(&resolved[1], "split")? {
+                // Splitting on nothing separates each character.
+                "" => s.chars().map(|c| Expr::String(c.to_string())).collect(),
+                separator => s.split(separator).map(Expr::string).collect(),
+            };
+            check_collection_len(parts.len())?;
+            Ok(Expr::Vector(parts))
+        },
+    );
+
+    env.defn(
+        "join",
+        Arity::Range(1, 2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let limits = PrintLimits::from_env(&env);
+            let resolved = resolve_args(args, env)?;
+            let parts = match &resolved[0] {
+                Expr::Vector(parts) => parts,
+                invalid => return Err(HError::InvalidType("join".to_string(), invalid.clone())),
+            };
+            let separator = match resolved.get(1) {
+                Some(separator) => check_string(separator, "join")?,
+                None => "",
+            };
+            let text = limits.scope(|| {
+                parts
+                    .iter()
+                    .map(|part| part.to_string())
+                    .collect::<Vec<String>>()
+                    .join(separator)
+            });
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
+        },
+    );
+
+    env.defn(
+        "upper",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            Ok(Expr::String(
+                check_string(&resolved[0], "upper")?.to_uppercase(),
+            ))
+        },
+    );
+
+    env.defn(
+        "lower",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            Ok(Expr::String(
+                check_string(&resolved[0], "lower")?.to_lowercase(),
+            ))
+        },
+    );
+
+    env.defn(
+        "trim",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            Ok(Expr::string(check_string(&resolved[0], "trim")?.trim()))
+        },
+    );
+
+    env.defn(
+        "replace",
+        Arity::Count(3),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let s = check_string(&resolved[0], "replace")?;
+            let from = check_string(&resolved[1], "replace")?;
+            let to = check_string(&resolved[2], "replace")?;
+            if from.is_empty() {
+                return Err(HError::InvalidType(
+                    "replace".to_string(),
+                    resolved[1].clone(),
+                ));
+            }
+            let text = s.replace(from, to);
+            check_string_len(text.len())?;
+            Ok(Expr::String(text))
+        },
+    );
+
+    env.defn(
+        "starts-with?",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let s = check_string(&resolved[0], "starts-with?")?;
+            let prefix = check_string(&resolved[1], "starts-with?")?;
+            Ok(Expr::boolean(s.starts_with(prefix)))
+        },
+    );
+
+    env.defn(
+        "ends-with?",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let s = check_string(&resolved[0], "ends-with?")?;
+            let suffix = check_string(&resolved[1], "ends-with?")?;
+            Ok(Expr::boolean(s.ends_with(suffix)))
+        },
+    );
+
+    env
+}
+
+fn check_string<'a>(expr: &'a Expr, fun_name: &str) -> Result<&'a str, HError> {
+    match expr {
+        Expr::String(s) => Ok(s),
+        _ => Err(HError::InvalidType(fun_name.to_string(), expr.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{env::Env, error::HError, evaluator::eval, expr::Expr};
+
+    #[test]
+    fn test_measures_and_slices_by_character() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval("(var s \"héllo\")", env.clone_ref()).unwrap();
+        assert_eq!(eval("(len s)", env.clone_ref()), Ok(Expr::number(5.)));
+        assert_eq!(
+            eval("(substring s 1 3)", env.clone_ref()),
+            Ok(Expr::string("él"))
+        );
+        assert_eq!(
+            eval("(substring s 3)", env.clone_ref()),
+            Ok(Expr::string("lo"))
+        );
+        assert_eq!(
+            eval("(substring \"héllo\" 1 3)", env.clone_ref()),
+            Ok(Expr::string("él"))
+        );
+        assert_eq!(
+            eval("(char-at s 1)", env.clone_ref()),
+            Ok(Expr::string("é"))
+        );
+        assert!(matches!(
+            eval("(char-at s 5)", env.clone_ref()),
+            Err(HError::IndexOutOfBounds(..))
+        ));
+        assert!(matches!(
+            eval("(substring s 3 1)", env),
+            Err(HError::IndexOutOfBounds(..))
+        ));
+    }
+
+    #[test]
+    fn test_splits_and_joins() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(split \"a,b,,c\" \",\")", env.clone_ref()),
+            eval("[\"a\" \"b\" \"\" \"c\"]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(split \"ab\" \"\")", env.clone_ref()),
+            eval("[\"a\" \"b\"]", env.clone_ref())
+        );
+        assert_eq!(
+            eval("(join [\"a\" 1 :b] \", \")", env.clone_ref()),
+            Ok(Expr::string("a, 1, :b"))
+        );
+        assert_eq!(eval("(join [\"a\" \"b\"])", env), Ok(Expr::string("ab")));
+    }
+
+    #[test]
+    fn test_transforms_and_searches() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(upper (trim \"  hi \"))", env.clone_ref()),
+            Ok(Expr::string("HI"))
+        );
+        assert_eq!(
+            eval("(lower \"Hi\")", env.clone_ref()),
+            Ok(Expr::string("hi"))
+        );
+        assert_eq!(
+            eval("(replace \"a-b-c\" \"-\" \"+\")", env.clone_ref()),
+            Ok(Expr::string("a+b+c"))
+        );
+        assert_eq!(
+            eval(
+                "[(starts-with? \"huck\" \"hu\") (ends-with? \"huck\" \"k\") (contains? \"huck\" \"x\")]",
+                env.clone_ref()
+            ),
+            eval("[true true false]", env.clone_ref())
+        );
+        assert!(matches!(
+            eval("(upper 1)", env),
+            Err(HError::InvalidType(..))
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    cell::RefCell,
+    future::{poll_fn, Future},
+    pin::Pin,
+    rc::Rc,
+    sync::{Mutex, MutexGuard, PoisonError},
+    task::Poll,
+};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{apply, eval, resolve_args},
+    expr::{Arity, Expr, HuckInspect},
+};
+
+/// The work behind an async native function.
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<Expr, HError>>>>;
+
+/// The script's handle to a running `HostFuture`.
+#[derive(Default)]
+struct Task {
+    result: Mutex<Option<Expr>>,
+    callbacks: Mutex<Vec<Expr>>,
+}
+
+impl Task {
+    fn result(&self) -> Option<Expr> {
+        lock(&self.result).clone()
+    }
+}
+
+// Tasks are only updated by whole-value writes, so a poisoned lock still
+// holds a valid value.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl HuckInspect for Task {
+    fn type_name(&self) -> String {
+        "task".to_string()
+    }
+
+    fn summary(&self) -> String {
+        match self.result() {
+            Some(value) => format!("done {}", value),
+            None => "pending".to_string(),
+        }
+    }
+}
+
+/// The tasks an interpreter has started, with the futures behind them.
+pub(crate) type Pending = Rc<RefCell<Vec<(Expr, HostFuture)>>>;
+
+// Futures hold values, which can't leave the interpreter thread, so they are
+// polled there by `eval_async`.
+thread_local! {
+    static PENDING: RefCell<Pending> = RefCell::new(Rc::default());
+}
+
+pub fn task_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "then",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            let task = as_task("then", &resolved[0])?;
+            let result = task.result();
+            match result {
+                Some(value) => apply(&resolved[1], &[value], env)?,
+                None => {
+                    lock(&task.callbacks).push(resolved[1].clone());
+                    Expr::Nil
+                }
+            };
+            Ok(resolved[0].clone())
+        },
+    );
+
+    // Reads a finished task rather than waiting on it: evaluation is
+    // synchronous, and the futures are driven by `eval_async` after it returns.
+    env.defn(
+        "task-value",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let result = as_task("task-value", &resolved[0])?.result();
+            result.ok_or_else(|| HError::TaskPending(resolved[0].clone()))
+        },
+    );
+
+    env.defn(
+        "done?",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let done = as_task("done?", &resolved[0])?.result().is_some();
+            Ok(Expr::boolean(done))
+        },
+    );
+
+    env
+}
+
+fn as_task<'a>(name: &str, value: &'a Expr) -> Result<&'a Task, HError> {
+    match value {
+        Expr::Host(host) => host.downcast::<Task>(),
+        _ => None,
+    }
+    .ok_or_else(|| HError::InvalidType(name.to_string(), value.clone()))
+}
+
+/// Wraps an async host function as a value scripts can call. A call starts
+/// the future and returns a task; pass it to `then` to use the result.
+pub fn async_fn(
+    name: &str,
+    arity: Arity,
+    function: impl std::ops::Fn(&[Expr]) -> HostFuture + Send + Sync + 'static,
+) -> Expr {
+    Expr::closure(name, arity, move |args: &[Expr], _: EnvRef| {
+        let task = Expr::host(Task::default());
+        let future = function(args);
+        pending().borrow_mut().push((task.clone(), future));
+        Ok(task)
+    })
+}
+
+/// Evaluates `input`, then awaits the tasks it started, running their
+/// callbacks as they finish. Tasks started by callbacks are awaited too.
+pub async fn eval_async(input: &str, env: EnvRef) -> Result<Expr, HError> {
+    let result = eval(input, env.clone_ref());
+    await_tasks(&pending(), result, |task, value| {
+        finish(task, value, env.clone_ref())
+    })
+    .await
+}
+
+/// Awaits the `pending` tasks of an evaluation that returned `result`,
+/// passing each one that finishes to `finish`. If anything fails, the tasks
+/// still running are dropped, so they don't carry over to the next one.
+pub(crate) async fn await_tasks(
+    pending: &Pending,
+    result: Result<Expr, HError>,
+    finish: impl Fn(&Expr, Expr) -> Result<(), HError>,
+) -> Result<Expr, HError> {
+    let result = async {
+        let result = result?;
+        while let Some(finished) = next_finished(pending).await {
+            for (task, value) in finished {
+                finish(&task, value?)?;
+            }
+        }
+        Ok(result)
+    }
+    .await;
+    if result.is_err() {
+        pending.borrow_mut().clear();
+    }
+    result
+}
+
+fn pending() -> Pending {
+    PENDING.with(|pending| Rc::clone(&pending.borrow()))
+}
+
+/// Installs `pending` as the active interpreter's, returning the previous ones.
+pub(crate) fn replace_pending(pending: Pending) -> Pending {
+    PENDING.with(|active| active.replace(pending))
+}
+
+/// Waits until at least one of the `pending` tasks finishes, or returns
+/// `None` if no tasks are running.
+async fn next_finished(pending: &Pending) -> Option<Vec<(Expr, Result<Expr, HError>)>> {
+    let mut running = pending.take();
+    if running.is_empty() {
+        return None;
+    }
+    let finished = poll_fn(|cx| {
+        let mut finished = vec![];
+        let mut i = 0;
+        while i < running.len() {
+            match running[i].1.as_mut().poll(cx) {
+                Poll::Ready(value) => finished.push((running.remove(i).0, value)),
+                Poll::Pending => i += 1,
+            }
+        }
+        if finished.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(finished)
+        }
+    })
+    .await;
+    // Keep tasks started while these were polled behind the ones still running.
+    let started = pending.replace(running);
+    pending.borrow_mut().extend(started);
+    Some(finished)
+}
+
+/// Records a finished task's value and runs the callbacks waiting on it.
+pub(crate) fn finish(task: &Expr, value: Expr, env: EnvRef) -> Result<(), HError> {
+    let task = as_task("finish", task)?;
+    *lock(&task.result) = Some(value.clone());
+    let callbacks = std::mem::take(&mut *lock(&task.callbacks));
+    for callback in callbacks {
+        apply(&callback, std::slice::from_ref(&value), env.clone_ref())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        future::Future,
+        task::{Context, Waker},
+    };
+
+    /// Polls `future` to completion without an async runtime.
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let mut future = Box::pin(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+        }
+    }
+
+    /// A future that is pending for `polls` polls before returning `value`.
+    fn after(polls: usize, value: Expr) -> HostFuture {
+        let mut remaining = polls;
+        Box::pin(poll_fn(move |_| {
+            if remaining == 0 {
+                Poll::Ready(Ok(value.clone()))
+            } else {
+                remaining -= 1;
+                Poll::Pending
+            }
+        }))
+    }
+
+    fn env_with_fetch() -> EnvRef {
+        let env = Env::with_core_module().unwrap().into_ref();
+        env.def(
+            "fetch",
+            async_fn("fetch", Arity::Count(2), |args: &[Expr]| match &args[1] {
+                Expr::Number(polls) => after(**polls as usize, args[0].clone()),
+                _ => Box::pin(async { Err(HError::IoError("refused".to_string())) }),
+            }),
+        )
+        .unwrap();
+        env
+    }
+
+    #[test]
+    fn test_runs_callbacks_when_tasks_finish() {
+        let env = env_with_fetch();
+
+        // Each callback appends its digit to `order`.
+        let result = block_on(eval_async(
+            "(var order (atom 0))
+             (defn record [v] (swap! order (fn [n] (+ (* n 10) v))))
+             (then (fetch 3 3) record)
+             (then (fetch 1 1) (fn [v] (record v) (then (fetch 2 0) record)))
+             :started",
+            env.clone_ref(),
+        ));
+
+        assert_eq!(result, Ok(Expr::keyword(":started")));
+        assert_eq!(eval("(deref order)", env), Ok(Expr::number(123.)));
+    }
+
+    #[test]
+    fn test_reads_finished_tasks() {
+        let env = env_with_fetch();
+
+        block_on(eval_async("(var task (fetch 42 2))", env.clone_ref())).unwrap();
+
+        assert_eq!(
+            eval("(done? task)", env.clone_ref()),
+            Ok(Expr::boolean(true))
+        );
+        assert_eq!(
+            eval("(task-value task)", env.clone_ref()),
+            Ok(Expr::number(42.))
+        );
+        assert!(matches!(
+            block_on(eval_async("(task-value (fetch 1 1))", env.clone_ref())),
+            Err(HError::TaskPending(_))
+        ));
+        assert!(pending().borrow().is_empty());
+        assert_eq!(
+            block_on(eval_async("(fetch 1 :fail) (fetch 2 5)", env)),
+            Err(HError::IoError("refused".to_string()))
+        );
+        assert!(pending().borrow().is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, eval_exprs, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::{check_deterministic, write_output, writes_stdout},
+    modules::{num_vec::numbers, utils::check_num},
+};
+
+/// Width reported by `term-width` when it can't be determined.
+const DEFAULT_WIDTH: usize = 80;
+/// Number of cells in a progress bar.
+const PROGRESS_WIDTH: usize = 30;
+const SPINNER_FRAMES: &[char] = &['|', '/', '-', '\\'];
+/// Number of cells in the longest bar of a bar chart.
+const BAR_CHART_WIDTH: usize = 40;
+/// Blocks from lowest to highest, for sparklines.
+const SPARK_BLOCKS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Blocks from one to seven eighths of a cell wide, for partial bars.
+const BAR_EIGHTHS: &[char] = &['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+pub fn term_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "color",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let code = match &resolved[0] {
+                Expr::Keyword(name) => color_code(name).ok_or_else(|| {
+                    HError::UnexpectedForm("Unknown color".to_string(), resolved[0].clone())
+                })?,
+                invalid => return Err(HError::InvalidType("color".to_string(), invalid.clone())),
+            };
+            Ok(styled(&resolved[1], code))
+        },
+    );
+
+    env.defn(
+        "bold",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            Ok(styled(&resolve_args(args, env)?[0], 1))
+        },
+    );
+
+    env.defn(
+        "clear-screen",
+        Arity::Count(0),
+        |_: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            if styling_enabled() {
+                write_output("\x1b[2J\x1b[H");
+            }
+            Ok(Expr::Nil)
+        },
+    );
+
+    env.defn(
+        "term-width",
+        Arity::Count(0),
+        |_: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            check_deterministic("term-width")?;
+            Ok(Expr::number(term_width() as f64))
+        },
+    );
+
+    env.defn(
+        "with-progress",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env.clone_ref())?;
+            let total = match &resolved[0] {
+                Expr::Number(total) if **total >= 0. => **total as usize,
+                invalid => {
+                    return Err(HError::InvalidType(
+                        "with-progress".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+            let draw = stderr_is_tty();
+            let done = Arc::new(AtomicUsize::new(0));
+            let ticks = Arc::clone(&done);
+            let tick = Expr::closure("tick", Arity::Count(0), move |_: &[Expr], _: EnvRef| {
+                let ticked = ticks.fetch_add(1, Ordering::SeqCst) + 1;
+                if draw {
+                    eprint!("\r{}", progress_bar(ticked, total, PROGRESS_WIDTH));
+                }
+                Ok(Expr::Nil)
+            });
+
+            let result = call(&resolved[1], &[tick], env);
+            if draw && done.load(Ordering::SeqCst) > 0 {
+                eprintln!();
+            }
+            result
+        },
+    );
+
+    env.defn(
+        "sparkline",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let values = numbers("sparkline", &resolve_args(args, env)?[0])?;
+            Ok(Expr::String(sparkline(
+                &values.iter().map(|value| **value).collect::<Vec<_>>(),
+            )))
+        },
+    );
+
+    env.defn(
+        "bar-chart",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let rows = match &resolve_args(args, env)?[0] {
+                Expr::Map(map) => map
+                    .iter()
+                    .map(|(label, value)| match check_num(value, "bar-chart")? {
+                        value if value >= 0. => Ok((label.to_string(), value)),
+                        _ => Err(HError::InvalidType("bar-chart".to_string(), value.clone())),
+                    })
+                    .collect::<Result<Vec<_>, HError>>()?,
+                invalid => {
+                    return Err(HError::InvalidType(
+                        "bar-chart".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+            Ok(Expr::String(bar_chart(&rows, BAR_CHART_WIDTH)))
+        },
+    );
+
+    env.defn(
+        "spinner",
+        Arity::Range(1, usize::MAX),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let message = match &resolve_args(&args[..1], env.clone_ref())?[0] {
+                Expr::String(message) => message.to_string(),
+                invalid => return Err(HError::InvalidType("spinner".to_string(), invalid.clone())),
+            };
+            if !stderr_is_tty() {
+                return eval_exprs(&args[1..], env);
+            }
+
+            // Values can't leave this thread, so only the animation runs on another.
+            let running = Arc::new(AtomicBool::new(true));
+            let animation = {
+                let running = Arc::clone(&running);
+                let message = message.clone();
+                thread::spawn(move || {
+                    let mut frames = SPINNER_FRAMES.iter().cycle();
+                    while running.load(Ordering::Relaxed) {
+                        eprint!("\r{} {}", frames.next().unwrap(), message);
+                        let _ = std::io::stderr().flush();
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                })
+            };
+            let result = eval_exprs(&args[1..], env);
+            running.store(false, Ordering::Relaxed);
+            let _ = animation.join();
+            eprintln!("\r{} done", message);
+            result
+        },
+    );
+
+    env
+}
+
+/// Renders e.g. `[#####     ] 5/10`. Ticks past `total` show as full.
+fn progress_bar(done: usize, total: usize, width: usize) -> String {
+    let filled = (done.min(total) * width)
+        .checked_div(total)
+        .unwrap_or(width);
+    format!(
+        "[{}{}] {}/{}",
+        "#".repeat(filled),
+        " ".repeat(width - filled),
+        done,
+        total
+    )
+}
+
+/// Renders each value as a block as tall as its place between the smallest
+/// and largest values. Equal values all show as the middle block.
+fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let top = (SPARK_BLOCKS.len() - 1) as f64;
+    values
+        .iter()
+        .map(|value| {
+            let level = match max > min {
+                true => ((value - min) / (max - min) * top).round(),
+                false => (top / 2.).floor(),
+            };
+            SPARK_BLOCKS[level as usize]
+        })
+        .collect()
+}
+
+/// Renders a line per `(label, value)` with a bar `width` cells long for the
+/// largest value, drawn to an eighth of a cell, followed by the value.
+fn bar_chart(rows: &[(String, f64)], width: usize) -> String {
+    let max = rows.iter().map(|(_, value)| *value).fold(0., f64::max);
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|(label, value)| {
+            let eighths = match max > 0. {
+                true => (value / max * (width * 8) as f64).round() as usize,
+                false => 0,
+            };
+            let mut bar = "█".repeat(eighths / 8);
+            if eighths % 8 > 0 {
+                bar.push(BAR_EIGHTHS[eighths % 8 - 1]);
+            }
+            let padding = label_width - label.chars().count();
+            format!(
+                "{}{} {} {}",
+                label,
+                " ".repeat(padding),
+                bar,
+                Expr::number(*value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn color_code(name: &str) -> Option<u8> {
+    let code = match name {
+        ":black" => 30,
+        ":red" => 31,
+        ":green" => 32,
+        ":yellow" => 33,
+        ":blue" => 34,
+        ":magenta" => 35,
+        ":cyan" => 36,
+        ":white" => 37,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Wraps the displayed value in an SGR escape, or leaves it plain when
+/// output isn't a terminal.
+fn styled(value: &Expr, code: u8) -> Expr {
+    if styling_enabled() {
+        Expr::String(format!("\x1b[{}m{}\x1b[0m", code, value))
+    } else {
+        Expr::String(value.to_string())
+    }
+}
+
+/// Styling is only used on a terminal, and can be turned off with `NO_COLOR`.
+pub(crate) fn styling_enabled() -> bool {
+    writes_stdout() && std::env::var_os("NO_COLOR").is_none() && stdout_is_tty()
+}
+
+#[cfg(unix)]
+fn stdout_is_tty() -> bool {
+    // SAFETY: isatty only inspects the file descriptor.
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_tty() -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn stderr_is_tty() -> bool {
+    // SAFETY: isatty only inspects the file descriptor.
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stderr_is_tty() -> bool {
+    false
+}
+
+fn term_width() -> usize {
+    if let Some(width) = tty_width() {
+        return width;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    // SAFETY: winsize is plain data filled in by the ioctl.
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+            Some(size.ws_col as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::interpreter::{Interpreter, Options};
+
+    use super::*;
+
+    #[test]
+    fn test_styles_degrade_without_a_terminal() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let interpreter = Interpreter::with_options(Options {
+            output: Some(output.clone()),
+            ..Options::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            interpreter.eval("(color :red (bold \"alert\"))"),
+            Ok(Expr::string("alert"))
+        );
+        assert_eq!(interpreter.eval("(clear-screen)"), Ok(Expr::Nil));
+        assert!(output.borrow().is_empty());
+        assert_eq!(
+            interpreter.eval("(color :mauve \"x\")"),
+            Err(HError::UnexpectedForm(
+                "Unknown color".to_string(),
+                Expr::keyword(":mauve")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0, 4, 8), "[        ] 0/4");
+        assert_eq!(progress_bar(1, 4, 8), "[##      ] 1/4");
+        assert_eq!(progress_bar(5, 4, 8), "[########] 5/4");
+        assert_eq!(progress_bar(0, 0, 4), "[####] 0/0");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[1., 5., 2., 8.]), "▁▅▂█");
+        assert_eq!(sparkline(&[3., 3.]), "▄▄");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let rows = [(":a".to_string(), 3.), (":bb".to_string(), 8.)];
+        assert_eq!(bar_chart(&rows, 4), ":a  █▌ 3\n:bb ████ 8");
+
+        let interpreter = Interpreter::new().unwrap();
+        assert_eq!(
+            interpreter.eval("(sparkline (num-vec [0 1]))"),
+            Ok(Expr::string("▁█"))
+        );
+        assert_eq!(
+            interpreter.eval("(bar-chart {:a 0})"),
+            Ok(Expr::string(":a  0"))
+        );
+        assert!(matches!(
+            interpreter.eval("(bar-chart {:a -1})"),
+            Err(HError::InvalidType(..))
+        ));
+    }
+
+    #[test]
+    fn test_with_progress_and_spinner_return_body_results() {
+        let interpreter = Interpreter::new().unwrap();
+
+        assert_eq!(
+            interpreter.eval(
+                "
+                (var count 0)
+                (with-progress 3 (fn [tick]
+                    (for-each i (range 0 3) (tick) (set! count (+ count 1)))
+                    count))"
+            ),
+            Ok(Expr::number(3.))
+        );
+        assert_eq!(
+            interpreter.eval("(spinner \"Working\" 1 2)"),
+            Ok(Expr::number(2.))
+        );
+    }
+
+    #[test]
+    fn test_term_width() {
+        let interpreter = Interpreter::new().unwrap();
+
+        assert!(matches!(
+            interpreter.eval("(term-width)"),
+            Ok(Expr::Number(width)) if *width > 0.
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    interpreter::frozen_clock,
+    modules::utils::check_num,
+};
+
+const MS_PER_DAY: i64 = 86_400_000;
+const WEEKDAYS: &[&str] = &[
+    ":thursday",
+    ":friday",
+    ":saturday",
+    ":sunday",
+    ":monday",
+    ":tuesday",
+    ":wednesday",
+];
+
+/// Times are numbers of milliseconds since the Unix epoch, in UTC.
+pub fn time_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "now-ms",
+        Arity::Count(0),
+        |_: &[Expr], _: EnvRef| -> Result<Expr, HError> {
+            if let Some(clock) = frozen_clock() {
+                return Ok(Expr::number(clock.get()));
+            }
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis())
+                .unwrap_or(0);
+            Ok(Expr::number(now as f64))
+        },
+    );
+
+    env.defn(
+        "set-clock!",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let ms = check_num(&resolve_args(args, env)?[0], "set-clock!")?;
+            match frozen_clock() {
+                Some(clock) => {
+                    clock.set(ms);
+                    Ok(Expr::Nil)
+                }
+                None => Err(HError::UnexpectedForm(
+                    "The clock can only be set in deterministic mode".to_string(),
+                    Expr::number(ms),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "parse-time",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            match &resolve_args(args, env)?[0] {
+                Expr::String(text) => parse_time(text).map(|ms| Expr::number(ms as f64)),
+                invalid => Err(HError::InvalidType(
+                    "parse-time".to_string(),
+                    invalid.clone(),
+                )),
+            }
+        },
+    );
+
+    env.defn(
+        "format-time",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let ms = check_num(&resolve_args(args, env)?[0], "format-time")?;
+            Ok(Expr::String(format_time(ms as i64)))
+        },
+    );
+
+    env.defn(
+        "plus-days",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let time = check_num(&resolved[0], "plus-days")?;
+            let days = check_num(&resolved[1], "plus-days")?;
+            Ok(Expr::number(time + days * MS_PER_DAY as f64))
+        },
+    );
+
+    env.defn(
+        "diff-ms",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let resolved = resolve_args(args, env)?;
+            let a = check_num(&resolved[0], "diff-ms")?;
+            let b = check_num(&resolved[1], "diff-ms")?;
+            Ok(Expr::number(a - b))
+        },
+    );
+
+    env.defn(
+        "day-of-week",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let ms = check_num(&resolve_args(args, env)?[0], "day-of-week")? as i64;
+            // The epoch was a Thursday.
+            let day = ms.div_euclid(MS_PER_DAY).rem_euclid(7) as usize;
+            Ok(Expr::keyword(WEEKDAYS[day]))
+        },
+    );
+
+    env
+}
+
+/// Parses an RFC 3339 timestamp such as `2024-05-01T10:00:00Z` or
+/// `2024-05-01T10:00:00.250+02:00`. Seconds and the offset may be left out,
+/// as in `2024-05-01T10:00`; a missing offset means UTC.
+pub fn parse_time(text: &str) -> Result<i64, HError> {
+    let invalid = || HError::UnexpectedForm("Invalid time".to_string(), Expr::string(text));
+    let field = |range: std::ops::Range<usize>| -> Result<i64, HError> {
+        let part = text.get(range).ok_or_else(invalid)?;
+        if !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        part.parse().map_err(|_| invalid())
+    };
+    let separators = [(4, b'-'), (7, b'-'), (13, b':')];
+    let bytes = text.as_bytes();
+    if bytes.len() < 16
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || separators.iter().any(|(i, c)| bytes[*i] != *c)
+    {
+        return Err(invalid());
+    }
+
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute) = (field(11..13)?, field(14..16)?);
+    let (second, mut rest) = match bytes.get(16) {
+        Some(b':') => (field(17..19)?, &text[19..]),
+        _ => (0, &text[16..]),
+    };
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(invalid());
+    }
+
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let padded = format!("{:0<3}", &fraction[..digits.min(3)]);
+        millis = padded.parse::<i64>().map_err(|_| invalid())?;
+        rest = &fraction[digits..];
+    }
+    let offset_minutes = match rest {
+        "" | "Z" | "z" => 0,
+        offset if offset.len() == 6 && offset.as_bytes()[3] == b':' => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(invalid()),
+            };
+            let hours: i64 = offset[1..3].parse().map_err(|_| invalid())?;
+            let minutes: i64 = offset[4..6].parse().map_err(|_| invalid())?;
+            sign * (hours * 60 + minutes)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second - offset_minutes * 60;
+    Ok(seconds * 1_000 + millis)
+}
+
+/// Formats epoch milliseconds as an RFC 3339 timestamp in UTC.
+pub fn format_time(ms: i64) -> String {
+    let days = ms.div_euclid(MS_PER_DAY);
+    let in_day = ms.rem_euclid(MS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute) = (in_day / 3_600_000, in_day / 60_000 % 60);
+    let (second, millis) = (in_day / 1_000 % 60, in_day % 1_000);
+    let mut text = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    );
+    if millis != 0 {
+        text.push_str(&format!(".{:03}", millis));
+    }
+    text.push('Z');
+    text
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since the epoch and proleptic Gregorian dates,
+// following Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_parse_and_format_time() {
+        assert_eq!(parse_time("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(parse_time("2024-05-01T10:00:00Z"), Ok(1_714_557_600_000));
+        assert_eq!(
+            parse_time("2024-05-01T12:00:00.25+02:00"),
+            Ok(1_714_557_600_250)
+        );
+        assert_eq!(parse_time("2024-05-01T10:00"), Ok(1_714_557_600_000));
+        assert_eq!(format_time(1_714_557_600_250), "2024-05-01T10:00:00.250Z");
+        assert_eq!(format_time(-1), "1969-12-31T23:59:59.999Z");
+        assert_eq!(
+            parse_time("2023-02-29T00:00:00Z"),
+            Err(HError::UnexpectedForm(
+                "Invalid time".to_string(),
+                Expr::string("2023-02-29T00:00:00Z")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        eval(
+            "(var t (parse-time \"2024-02-28T10:00:00Z\"))",
+            env.clone_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval("(format-time (plus-days t 3))", env.clone_ref()),
+            Ok(Expr::string("2024-03-02T10:00:00Z"))
+        );
+        assert_eq!(
+            eval("(diff-ms (plus-days t 1) t)", env.clone_ref()),
+            Ok(Expr::number(86_400_000.))
+        );
+        assert_eq!(
+            eval("(day-of-week t)", env),
+            Ok(Expr::keyword(":wednesday"))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::resolve_args,
+    expr::{Arity, Expr},
+    modules::utils::check_num,
+};
+
+/// Duration units, largest first, in milliseconds.
+const DURATION_UNITS: &[(&str, f64)] = &[
+    ("d", 86_400_000.),
+    ("h", 3_600_000.),
+    ("m", 60_000.),
+    ("s", 1_000.),
+    ("ms", 1.),
+];
+
+/// Decimal units are used when formatting; binary ones are only parsed.
+const BYTE_UNITS: &[(&str, f64)] = &[
+    ("TB", 1e12),
+    ("GB", 1e9),
+    ("MB", 1e6),
+    ("KB", 1e3),
+    ("B", 1.),
+];
+const BINARY_BYTE_UNITS: &[(&str, f64)] = &[
+    ("TIB", 1_099_511_627_776.),
+    ("GIB", 1_073_741_824.),
+    ("MIB", 1_048_576.),
+    ("KIB", 1_024.),
+];
+
+pub fn units_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "duration",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let text = string_arg("duration", args, env)?;
+            parse_duration(&text).map(Expr::number)
+        },
+    );
+
+    env.defn(
+        "bytes",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let text = string_arg("bytes", args, env)?;
+            parse_units(&text, |unit| {
+                let unit = unit.to_uppercase();
+                lookup(BYTE_UNITS, &unit).or_else(|| lookup(BINARY_BYTE_UNITS, &unit))
+            })
+            .map(Expr::number)
+        },
+    );
+
+    env.defn(
+        "format-duration",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let ms = check_num(&resolve_args(args, env)?[0], "format-duration")?;
+            Ok(Expr::String(format_duration(ms)))
+        },
+    );
+
+    env.defn(
+        "format-bytes",
+        Arity::Count(1),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            let bytes = check_num(&resolve_args(args, env)?[0], "format-bytes")?;
+            Ok(Expr::String(format_bytes(bytes)))
+        },
+    );
+
+    env
+}
+
+fn string_arg(name: &str, args: &[Expr], env: EnvRef) -> Result<String, HError> {
+    match &resolve_args(args, env)?[0] {
+        Expr::String(text) => Ok(text.to_string()),
+        invalid => Err(HError::InvalidType(name.to_string(), invalid.clone())),
+    }
+}
+
+fn lookup(units: &[(&str, f64)], unit: &str) -> Option<f64> {
+    units
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, size)| *size)
+}
+
+/// Parses a duration such as `1h30m` into milliseconds.
+pub fn parse_duration(text: &str) -> Result<f64, HError> {
+    parse_units(text, |unit| lookup(DURATION_UNITS, unit))
+}
+
+/// Sums a sequence of `<number><unit>` parts, e.g. `1h30m` or `1.5MB`.
+fn parse_units(text: &str, unit_size: impl Fn(&str) -> Option<f64>) -> Result<f64, HError> {
+    let invalid = || HError::UnexpectedForm("Invalid quantity".to_string(), Expr::string(text));
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total = 0.;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().map_err(|_| invalid())?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let size = unit_size(rest[..unit_len].trim()).ok_or_else(invalid)?;
+        rest = rest[unit_len..].trim_start();
+        total += number * size;
+    }
+    Ok(total)
+}
+
+/// Formats milliseconds with the largest units first, e.g. `1h30m`, or
+/// `-1h30m` for a negative duration.
+pub fn format_duration(ms: f64) -> String {
+    let mut remaining = ms.abs().round();
+    if remaining == 0. {
+        return "0ms".to_string();
+    }
+    let mut parts = String::new();
+    if ms < 0. {
+        parts.push('-');
+    }
+    for (unit, size) in DURATION_UNITS {
+        let count = (remaining / size).floor();
+        if count > 0. {
+            parts.push_str(&format!("{}{}", count, unit));
+            remaining -= count * size;
+        }
+    }
+    parts
+}
+
+/// Formats a byte count with the largest decimal unit, e.g. `1.5MB`.
+pub fn format_bytes(bytes: f64) -> String {
+    let (unit, size) = BYTE_UNITS
+        .iter()
+        .find(|(_, size)| bytes.abs() >= *size)
+        .unwrap_or(&("B", 1.));
+    let value = (bytes / size * 10.).round() / 10.;
+    format!("{}{}", value, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+
+    #[test]
+    fn test_duration() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(duration \"1h30m\")", env.clone_ref()),
+            Ok(Expr::number(5_400_000.))
+        );
+        assert_eq!(
+            eval("(duration \"1.5s 20ms\")", env.clone_ref()),
+            Ok(Expr::number(1_520.))
+        );
+        assert_eq!(
+            eval("(duration \"3 days\")", env),
+            Err(HError::UnexpectedForm(
+                "Invalid quantity".to_string(),
+                Expr::string("3 days")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let env = Env::with_core_module().unwrap().into_ref();
+
+        assert_eq!(
+            eval("(bytes \"10MB\")", env.clone_ref()),
+            Ok(Expr::number(10_000_000.))
+        );
+        assert_eq!(
+            eval("(bytes \"2KiB\")", env.clone_ref()),
+            Ok(Expr::number(2_048.))
+        );
+        assert_eq!(
+            eval("(bytes \"512\")", env),
+            Err(HError::UnexpectedForm(
+                "Invalid quantity".to_string(),
+                Expr::string("512")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_duration(5_400_000.), "1h30m");
+        assert_eq!(format_duration(90_061_001.), "1d1h1m1s1ms");
+        assert_eq!(format_duration(0.), "0ms");
+        assert_eq!(format_duration(0.3), "0ms");
+        assert_eq!(format_duration(-5.), "-5ms");
+        assert_eq!(format_duration(-5_400_000.), "-1h30m");
+        assert_eq!(format_bytes(10_000_000.), "10MB");
+        assert_eq!(format_bytes(1_500.), "1.5KB");
+        assert_eq!(format_bytes(12.), "12B");
+    }
+}
@@ -8,10 +8,75 @@ pub fn check_num(expr: &Expr, fun_name: &str) -> Result<f64, HError> {
 }
 
 pub fn is_truthy(expr: &Expr) -> bool {
-    match expr {
-        Expr::Nil | Expr::Boolean(false) => false,
-        _ => true,
+    !matches!(expr, Expr::Nil | Expr::Boolean(false))
+}
+
+/// Types that parameters can be hinted with, as in `[^number n]`.
+pub const TYPE_HINTS: &[&str] = &[
+    "number", "string", "keyword", "symbol", "boolean", "vector", "num-vec", "map", "fn", "atom",
+    "host", "nil",
+];
+
+/// Whether `value` is of the hinted type, or `None` if the hint is unknown.
+pub fn hint_matches(hint: &str, value: &Expr) -> Option<bool> {
+    let matches = match hint {
+        "number" => matches!(value, Expr::Number(_)),
+        "string" => matches!(value, Expr::String(_)),
+        "keyword" => matches!(value, Expr::Keyword(_)),
+        "symbol" => matches!(value, Expr::Symbol(_)),
+        "boolean" => matches!(value, Expr::Boolean(_)),
+        "vector" => matches!(value, Expr::Vector(_)),
+        "num-vec" => matches!(value, Expr::NumVec(_)),
+        "map" => matches!(value, Expr::Map(_)),
+        "fn" => matches!(
+            value,
+            Expr::NativeFn(_) | Expr::Fn(_) | Expr::Closure(_) | Expr::Method(_)
+        ),
+        "atom" => matches!(value, Expr::Atom(_)),
+        "host" => matches!(value, Expr::Host(_)),
+        "nil" => matches!(value, Expr::Nil),
+        _ => return None,
+    };
+    Some(matches)
+}
+
+/// Separates `^type` hints from a parameter vector, returning the
+/// parameters and the hint given for each one.
+pub fn split_hints(params: &[Expr]) -> Result<(Vec<Expr>, Vec<Option<String>>), HError> {
+    let mut args = vec![];
+    let mut hints = vec![];
+    let mut pending: Option<&str> = None;
+    for param in params {
+        match param {
+            Expr::Symbol(name) if name.starts_with('^') => {
+                let hint = &name[1..];
+                if pending.is_some() || !TYPE_HINTS.contains(&hint) {
+                    return Err(HError::UnexpectedForm(
+                        "Invalid type hint".to_string(),
+                        param.clone(),
+                    ));
+                }
+                pending = Some(hint);
+            }
+            Expr::Ampersand if pending.is_some() => {
+                return Err(HError::UnexpectedForm(
+                    "Type hints must come right before a parameter".to_string(),
+                    param.clone(),
+                ))
+            }
+            _ => {
+                args.push(param.clone());
+                hints.push(pending.take().map(str::to_string));
+            }
+        }
+    }
+    if pending.is_some() {
+        return Err(HError::UnexpectedForm(
+            "Type hints must come right before a parameter".to_string(),
+            Expr::vector(params),
+        ));
     }
+    Ok((args, hints))
 }
 
 pub fn method_id(args: &[Expr]) -> String {
@@ -24,5 +89,20 @@ pub fn method_id(args: &[Expr]) -> String {
 }
 
 pub fn method_args(args: &[Expr]) -> Vec<Expr> {
-    args.iter().skip(1).step_by(2).map(|a| a.clone()).collect()
+    args.iter().skip(1).step_by(2).cloned().collect()
+}
+
+/// Splits a method's argument vector into its name parts and parameters.
+/// A trailing `& rest`, as in `[partial: & args]` or `[add: n & more]`,
+/// collects the arguments left after the last name part's into `rest`, and
+/// is marked by a `&` at the end of the method's id.
+pub fn method_signature(args: &[Expr]) -> (String, Vec<Expr>) {
+    match args {
+        [named @ .., Expr::Ampersand, rest] if !named.is_empty() => {
+            let mut params = method_args(named);
+            params.extend([Expr::Ampersand, rest.clone()]);
+            (format!("{} &", method_id(named)), params)
+        }
+        _ => (method_id(args), method_args(args)),
+    }
 }
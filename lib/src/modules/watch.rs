@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    env::{Env, EnvRef},
+    error::HError,
+    evaluator::{call, resolve_args},
+    expr::{Arity, Expr},
+    interpreter::check_deterministic,
+    modules::scheduler::schedule,
+};
+
+/// How often watched paths are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Watchers poll the filesystem from the scheduler, so their callbacks run
+/// while `run-scheduled` is running, like any other timer. With the `watch`
+/// feature on Linux, the tree is only walked again after inotify reports a
+/// change below it.
+pub fn watch_module() -> Env {
+    let mut env = Env::new();
+
+    env.defn(
+        "watch-path",
+        Arity::Count(2),
+        |args: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+            check_deterministic("watch-path")?;
+            let resolved = resolve_args(args, env)?;
+            let root = match &resolved[0] {
+                Expr::String(path) => PathBuf::from(path.as_str()),
+                invalid => {
+                    return Err(HError::InvalidType(
+                        "watch-path".to_string(),
+                        invalid.clone(),
+                    ))
+                }
+            };
+            let callback = resolved[1].clone();
+            let (files, dirs) = walk(&root);
+            let mut notifier = Notifier::new();
+            notifier.watch(&root, &dirs);
+            let last = Mutex::new((files, notifier));
+
+            let poll = Expr::closure(
+                "watch-path",
+                Arity::Count(0),
+                move |_: &[Expr], env: EnvRef| -> Result<Expr, HError> {
+                    let mut last = last.lock().unwrap_or_else(PoisonError::into_inner);
+                    let (files, notifier) = &mut *last;
+                    if !notifier.changed() {
+                        return Ok(Expr::Nil);
+                    }
+                    let (current, dirs) = walk(&root);
+                    // Directories created since the last walk need watches too.
+                    notifier.watch(&root, &dirs);
+                    let events = changes(files, &current);
+                    *files = current;
+                    drop(last);
+                    for event in events {
+                        call(&callback, &[event], env.clone_ref())?;
+                    }
+                    Ok(Expr::Nil)
+                },
+            );
+            let id = schedule(Instant::now() + POLL_INTERVAL, Some(POLL_INTERVAL), &poll);
+            Ok(Expr::number(id as f64))
+        },
+    );
+
+    env
+}
+
+/// Records the modification time of `root` and, for directories, every
+/// file below it, along with the directories read. Symlinked directories
+/// are followed, but each directory is only read once, so a link back up the
+/// tree doesn't loop forever.
+fn walk(root: &Path) -> (Snapshot, BTreeSet<PathBuf>) {
+    let mut files = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        match fs::read_dir(&path) {
+            Ok(entries) => {
+                if visited.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+                    pending.extend(entries.flatten().map(|entry| entry.path()));
+                }
+            }
+            Err(_) if path.exists() => {
+                let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                files.insert(path, modified);
+            }
+            Err(_) => {}
+        }
+    }
+    (files, visited)
+}
+
+#[cfg(test)]
+fn snapshot(root: &Path) -> Snapshot {
+    walk(root).0
+}
+
+/// Tells whether anything watched may have changed since it was last asked,
+/// using an inotify instance with a watch on each directory.
+#[cfg(all(feature = "watch", target_os = "linux"))]
+struct Notifier {
+    fd: libc::c_int,
+    // Until the root itself is watched, e.g. before it is created, every
+    // poll walks the tree.
+    watching: bool,
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+impl Notifier {
+    const EVENTS: u32 = libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_DELETE_SELF
+        | libc::IN_MOVE_SELF;
+
+    fn new() -> Notifier {
+        // SAFETY: creates a new descriptor, owned by the notifier.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        Notifier {
+            fd,
+            watching: false,
+        }
+    }
+
+    /// Watches `root` and `dirs`. Adding a path already watched keeps its
+    /// watch, and the kernel drops the watches of deleted directories.
+    fn watch(&mut self, root: &Path, dirs: &BTreeSet<PathBuf>) {
+        if self.fd < 0 {
+            return;
+        }
+        self.watching = self.add(root);
+        for dir in dirs {
+            self.add(dir);
+        }
+    }
+
+    fn add(&self, path: &Path) -> bool {
+        use std::os::unix::ffi::OsStrExt;
+        let path = match std::ffi::CString::new(path.as_os_str().as_bytes()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        // SAFETY: `path` is a valid C string that outlives the call.
+        unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), Notifier::EVENTS) >= 0 }
+    }
+
+    /// Drains the pending events, returning whether there were any.
+    fn changed(&self) -> bool {
+        if self.fd < 0 || !self.watching {
+            return true;
+        }
+        let mut changed = false;
+        let mut buffer = [0u8; 4096];
+        // SAFETY: reads at most the buffer's length into it. The descriptor
+        // is non-blocking, so this stops once no events are left.
+        while unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), buffer.len()) } > 0 {
+            changed = true;
+        }
+        changed
+    }
+}
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        if self.fd >= 0 {
+            // SAFETY: closes the descriptor opened in `new`, once.
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(feature = "watch", target_os = "linux")))]
+struct Notifier;
+
+#[cfg(not(all(feature = "watch", target_os = "linux")))]
+impl Notifier {
+    fn new() -> Notifier {
+        Notifier
+    }
+
+    fn watch(&mut self, _root: &Path, _dirs: &BTreeSet<PathBuf>) {}
+
+    fn changed(&self) -> bool {
+        true
+    }
+}
+
+/// Describes the difference between two snapshots as `{:path :kind}` maps,
+/// where the kind is one of `:created`, `:modified`, or `:removed`.
+fn changes(before: &Snapshot, after: &Snapshot) -> Vec<Expr> {
+    let event = |path: &Path, kind: &str| {
+        Expr::map(&[
+            (
+                Expr::keyword(":path"),
+                Expr::string(&path.to_string_lossy()),
+            ),
+            (Expr::keyword(":kind"), Expr::keyword(kind)),
+        ])
+    };
+    let mut events = vec![];
+    for (path, modified) in after {
+        match before.get(path) {
+            None => events.push(event(path, ":created")),
+            Some(previous) if previous != modified => events.push(event(path, ":modified")),
+            _ => {}
+        }
+    }
+    for path in before.keys().filter(|path| !after.contains_key(*path)) {
+        events.push(event(path, ":removed"));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let dir = std::env::temp_dir().join("huck_test_watch");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("kept.huck"), "").unwrap();
+        fs::write(dir.join("removed.huck"), "").unwrap();
+        let before = snapshot(&dir);
+
+        fs::remove_file(dir.join("removed.huck")).unwrap();
+        fs::write(dir.join("nested/created.huck"), "").unwrap();
+        let after = snapshot(&dir);
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        assert_eq!(
+            changes(&before, &after),
+            vec![
+                Expr::map(&[
+                    (
+                        Expr::keyword(":path"),
+                        Expr::string(&path("nested/created.huck"))
+                    ),
+                    (Expr::keyword(":kind"), Expr::keyword(":created")),
+                ]),
+                Expr::map(&[
+                    (Expr::keyword(":path"), Expr::string(&path("removed.huck"))),
+                    (Expr::keyword(":kind"), Expr::keyword(":removed")),
+                ]),
+            ]
+        );
+        assert_eq!(changes(&after, &after), vec![]);
+    }
+
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    #[test]
+    fn test_notifier_reports_changes_below_root() {
+        let dir = std::env::temp_dir().join("huck_test_watch_notify");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let mut notifier = Notifier::new();
+        notifier.watch(&dir, &walk(&dir).1);
+        assert!(!notifier.changed());
+
+        fs::write(dir.join("nested/created.huck"), "").unwrap();
+        assert!(notifier.changed());
+        assert!(!notifier.changed());
+
+        let _ = fs::remove_dir_all(&dir);
+        assert!(notifier.changed());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_snapshot_survives_symlink_cycles() {
+        let dir = std::env::temp_dir().join("huck_test_watch_cycle");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("nested/file.huck"), "").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("nested/up")).unwrap();
+
+        assert_eq!(
+            snapshot(&dir).keys().collect::<Vec<_>>(),
+            vec![&dir.join("nested/file.huck")]
+        );
+    }
+}
@@ -3,13 +3,25 @@ use std::collections::BTreeMap;
 use crate::{
     error::HError,
     expr::Expr,
-    scanner::{scan, Token, TokenType},
+    scanner::{scan, Segment, Span, Token, TokenType},
 };
 
+/// A parsed expression with the span of source it was read from. Lists,
+/// vectors, and maps keep a node for each element, in source order, and a
+/// quote keeps one for the quoted form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub expr: Expr,
+    pub span: Span,
+    pub children: Vec<Node>,
+}
+
 struct Parser {
     tokens: Vec<Token>,
     current: usize,
     debug: bool,
+    // Each list parsed and the line it starts on, when requested.
+    lists: Option<Vec<(usize, Expr)>>,
 }
 
 pub fn parse(input: &str) -> Result<Vec<Expr>, HError> {
@@ -17,12 +29,32 @@ pub fn parse(input: &str) -> Result<Vec<Expr>, HError> {
     Parser::new(tokens).parse()
 }
 
+/// Parses `input`, returning every list and method list in it along with
+/// the line each one starts on.
+pub fn list_lines(input: &str) -> Result<Vec<(usize, Expr)>, HError> {
+    let mut parser = Parser::new(scan(input)?);
+    parser.lists = Some(vec![]);
+    parser.parse()?;
+    Ok(parser.lists.unwrap_or_default())
+}
+
+/// Parses `input` like `parse`, keeping the span of every expression.
+pub fn parse_with_spans(input: &str) -> Result<Vec<Node>, HError> {
+    let mut parser = Parser::new(scan(input)?);
+    let mut nodes = Vec::new();
+    while !parser.is_at_end() {
+        nodes.push(parser.parse_node()?);
+    }
+    Ok(nodes)
+}
+
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
         Parser {
             tokens: tokens.to_owned(),
             current: 0,
             debug: false,
+            lists: None,
         }
     }
 
@@ -38,12 +70,17 @@ impl Parser {
         let token = self.peek();
 
         match token.token_type {
-            TokenType::LeftParen => Ok(Expr::List(
-                self.parse_vector(TokenType::LeftParen, TokenType::RightParen)?,
-            )),
-            TokenType::LeftAngle => Ok(Expr::MethodList(
-                self.parse_vector(TokenType::LeftAngle, TokenType::RightAngle)?,
-            )),
+            TokenType::LeftParen => {
+                let list =
+                    Expr::List(self.parse_vector(TokenType::LeftParen, TokenType::RightParen)?);
+                Ok(self.record_list(token.line, list))
+            }
+            TokenType::LeftAngle => {
+                let list = Expr::MethodList(
+                    self.parse_vector(TokenType::LeftAngle, TokenType::RightAngle)?,
+                );
+                Ok(self.record_list(token.line, list))
+            }
             TokenType::LeftSquare => Ok(Expr::Vector(
                 self.parse_vector(TokenType::LeftSquare, TokenType::RightSquare)?,
             )),
@@ -60,6 +97,10 @@ impl Parser {
                 self.advance();
                 Ok(Expr::string(&value))
             }
+            TokenType::Interpolation(segments) => {
+                self.advance();
+                interpolate(segments)
+            }
             TokenType::Keyword(value) => {
                 self.advance();
                 Ok(Expr::keyword(&value))
@@ -76,10 +117,80 @@ impl Parser {
                 self.advance();
                 Ok(Expr::nil())
             }
+            TokenType::Quote
+            | TokenType::Quasiquote
+            | TokenType::Unquote
+            | TokenType::UnquoteSplicing => {
+                self.advance();
+                let form = quote_form(&token.token_type);
+                Ok(Expr::List(vec![
+                    Expr::symbol(form),
+                    self.parse_expression()?,
+                ]))
+            }
             token => Err(HError::ParseError(format!("Unexpected token {:?}", token))),
         }
     }
 
+    fn parse_node(&mut self) -> Result<Node, HError> {
+        let open = self.peek();
+        let close = match open.token_type {
+            TokenType::LeftParen => TokenType::RightParen,
+            TokenType::LeftAngle => TokenType::RightAngle,
+            TokenType::LeftSquare => TokenType::RightSquare,
+            TokenType::LeftCurly => TokenType::RightCurly,
+            TokenType::Quote
+            | TokenType::Quasiquote
+            | TokenType::Unquote
+            | TokenType::UnquoteSplicing => {
+                self.advance();
+                let quoted = self.parse_node()?;
+                return Ok(Node {
+                    expr: Expr::List(vec![
+                        Expr::symbol(quote_form(&open.token_type)),
+                        quoted.expr.clone(),
+                    ]),
+                    span: Span {
+                        start: open.span.start,
+                        end: quoted.span.end,
+                    },
+                    children: vec![quoted],
+                });
+            }
+            _ => {
+                let expr = self.parse_expression()?;
+                return Ok(Node {
+                    expr,
+                    span: self.previous().span,
+                    children: Vec::new(),
+                });
+            }
+        };
+
+        self.advance();
+        let mut children = Vec::new();
+        while !self.check(&close) {
+            children.push(self.parse_node()?);
+        }
+        let end = self.match_token(close)?;
+
+        let exprs: Vec<Expr> = children.iter().map(|node| node.expr.clone()).collect();
+        let expr = match open.token_type {
+            TokenType::LeftParen => Expr::List(exprs),
+            TokenType::LeftAngle => Expr::MethodList(exprs),
+            TokenType::LeftSquare => Expr::Vector(exprs),
+            _ => Expr::Map(pairs(exprs, open.line)?),
+        };
+        Ok(Node {
+            expr,
+            span: Span {
+                start: open.span.start,
+                end: end.span.end,
+            },
+            children,
+        })
+    }
+
     fn parse_vector(
         &mut self,
         open_token: TokenType,
@@ -104,18 +215,7 @@ impl Parser {
         }
         self.match_token(TokenType::RightCurly)?;
 
-        if expressions.len() % 2 == 0 {
-            let mut map: BTreeMap<Expr, Expr> = BTreeMap::new();
-            for pair in expressions.chunks(2) {
-                map.insert(pair[0].to_owned(), pair[1].to_owned());
-            }
-            Ok(map)
-        } else {
-            Err(HError::ParseError(format!(
-                "A map must have an even number of elements! Map opened at line {}.",
-                initial_token.line
-            )))
-        }
+        pairs(expressions, initial_token.line)
     }
 
     fn match_token(&mut self, token_type: TokenType) -> Result<Token, HError> {
@@ -134,16 +234,16 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        return self.peek().token_type == *token_type;
+        self.peek().token_type == *token_type
     }
 
     fn peek(&self) -> Token {
-        return self.tokens[self.current].to_owned();
+        self.tokens[self.current].to_owned()
     }
 
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
-            self.current = self.current + 1;
+            self.current += 1;
         }
         let consumed = self.previous();
         self.debug(format!("CONSUMED {:?}", consumed));
@@ -158,6 +258,13 @@ impl Parser {
         self.peek().token_type == TokenType::EndOfFile
     }
 
+    fn record_list(&mut self, line: i32, list: Expr) -> Expr {
+        if let Some(lists) = self.lists.as_mut() {
+            lists.push((line as usize, list.clone()));
+        }
+        list
+    }
+
     fn debug(&self, print: String) {
         if self.debug {
             println!("{}", print);
@@ -165,6 +272,55 @@ impl Parser {
     }
 }
 
+/// The special form a quote token reads as, e.g. `quote` for `'`.
+fn quote_form(token_type: &TokenType) -> &'static str {
+    match token_type {
+        TokenType::Quasiquote => "quasiquote",
+        TokenType::Unquote => "unquote",
+        TokenType::UnquoteSplicing => "unquote-splicing",
+        _ => "quote",
+    }
+}
+
+/// Reads an interpolated string as a call to `str`, so `"Hi #{name}!"` is
+/// `(str "Hi " name "!")`.
+fn interpolate(segments: Vec<Segment>) -> Result<Expr, HError> {
+    let mut call = vec![Expr::symbol("str")];
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => call.push(Expr::String(text)),
+            Segment::Form(tokens) => {
+                let line = tokens[0].line;
+                let mut forms = Parser::new(tokens).parse()?;
+                if forms.len() != 1 {
+                    return Err(HError::ParseError(format!(
+                        "#{{}} must hold one form, at line {}",
+                        line
+                    )));
+                }
+                call.append(&mut forms);
+            }
+        }
+    }
+    Ok(Expr::List(call))
+}
+
+/// Pairs up the elements of a map opened at `line`.
+fn pairs(expressions: Vec<Expr>, line: i32) -> Result<BTreeMap<Expr, Expr>, HError> {
+    if expressions.len().is_multiple_of(2) {
+        let mut map: BTreeMap<Expr, Expr> = BTreeMap::new();
+        for pair in expressions.chunks(2) {
+            map.insert(pair[0].to_owned(), pair[1].to_owned());
+        }
+        Ok(map)
+    } else {
+        Err(HError::ParseError(format!(
+            "A map must have an even number of elements! Map opened at line {}.",
+            line
+        )))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +386,110 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_parses_quotes() {
+        let quote = |form: &str, expr: Expr| Expr::list(&[Expr::symbol(form), expr]);
+
+        assert_eq!(
+            parse("'a `[b ~c ~@d]").unwrap(),
+            vec![
+                quote("quote", Expr::symbol("a")),
+                quote(
+                    "quasiquote",
+                    Expr::vector(&[
+                        Expr::symbol("b"),
+                        quote("unquote", Expr::symbol("c")),
+                        quote("unquote-splicing", Expr::symbol("d")),
+                    ])
+                ),
+            ]
+        );
+        assert!(parse("'").is_err());
+
+        let nodes = parse_with_spans(" '(f x)").unwrap();
+        assert_eq!(nodes[0].span, Span { start: 1, end: 7 });
+        assert_eq!(nodes[0].children[0].span, Span { start: 2, end: 7 });
+    }
+
+    #[test]
+    fn test_parses_interpolated_strings() {
+        assert_eq!(
+            parse("\"Hello #{name}, you have #{(count msgs)} messages\"").unwrap(),
+            vec![Expr::list(&[
+                Expr::symbol("str"),
+                Expr::string("Hello "),
+                Expr::symbol("name"),
+                Expr::string(", you have "),
+                Expr::list(&[Expr::symbol("count"), Expr::symbol("msgs")]),
+                Expr::string(" messages"),
+            ])]
+        );
+        assert!(parse("\"#{}\"").is_err());
+        assert!(parse("\"#{a b}\"").is_err());
+    }
+
+    #[test]
+    fn test_parses_deep_nesting() {
+        let result = parse(&format!("{}{}", "(f 1 ".repeat(20), ")".repeat(20))).unwrap();
+
+        let mut expr = &result[0];
+        let mut depth = 1;
+        while let Expr::List(items) = expr {
+            match items.get(2) {
+                Some(inner) => {
+                    expr = inner;
+                    depth += 1;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(depth, 20);
+        assert_eq!(*expr, Expr::list(&[Expr::symbol("f"), Expr::number(1.)]));
+    }
+
+    #[test]
+    fn test_parses_with_spans() {
+        let input = "(f [1 2])\n{:a <x y>}";
+        let nodes = parse_with_spans(input).unwrap();
+
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.expr.clone())
+                .collect::<Vec<_>>(),
+            parse(input).unwrap()
+        );
+        assert_eq!(nodes[0].span, Span { start: 0, end: 9 });
+        let vector = &nodes[0].children[1];
+        assert_eq!(vector.span, Span { start: 3, end: 8 });
+        assert_eq!(vector.children[1].span, Span { start: 6, end: 7 });
+        assert_eq!(nodes[1].children[1].span, Span { start: 14, end: 19 });
+        assert!(parse_with_spans("{:a}").is_err());
+    }
+
+    #[test]
+    fn test_spans_count_characters() {
+        let nodes = parse_with_spans("(println \"日本\" 42 7)").unwrap();
+
+        assert_eq!(
+            nodes[0].expr,
+            Expr::list(&[
+                Expr::symbol("println"),
+                Expr::string("日本"),
+                Expr::number(42.),
+                Expr::number(7.),
+            ])
+        );
+        let spans: Vec<Span> = nodes[0].children.iter().map(|node| node.span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span { start: 1, end: 8 },
+                Span { start: 9, end: 13 },
+                Span { start: 14, end: 16 },
+                Span { start: 17, end: 18 },
+            ]
+        );
+    }
 }
@@ -11,19 +11,50 @@ pub enum TokenType {
     LeftAngle,
     RightAngle,
     String(String),
+    Interpolation(Vec<Segment>), // "Hello #{name}"
     Number(f64),
     Symbol(String),
     Keyword(String),
     Boolean(bool),
     Ampersand,
+    Quote,           // 'form
+    Quasiquote,      // `form
+    Unquote,         // ~form
+    UnquoteSplicing, // ~@form
     Nil,
     EndOfFile,
 }
 
-#[derive(Debug, Clone)]
+/// A piece of a string with `#{form}`s in it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Segment {
+    Text(String),
+    Form(Vec<Token>), // Ends with an EndOfFile token
+}
+
+/// Where a token or expression sits in the source, as character offsets.
+/// `end` is exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The 1-based line and column `start` falls on in `source`.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before: Vec<char> = source.chars().take(self.start).collect();
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        (line, column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub line: i32,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -32,12 +63,12 @@ struct Scanner {
     current: usize,
     line: i32,
     tokens: Vec<Token>,
-    source: String,
-    source_length: usize,
+    // Indexed by character, so spans count characters rather than bytes.
+    source: Vec<char>,
 }
 
 pub fn scan(input: &str) -> Result<Vec<Token>, HError> {
-    Ok(Scanner::new(input).scan_tokens()?)
+    Scanner::new(input).scan_tokens()
 }
 
 impl Scanner {
@@ -47,8 +78,7 @@ impl Scanner {
             current: 0,
             line: 1,
             tokens: Vec::new(),
-            source: input.to_string(),
-            source_length: input.chars().count(),
+            source: input.chars().collect(),
         }
     }
 
@@ -61,6 +91,10 @@ impl Scanner {
         self.tokens.push(Token {
             token_type: TokenType::EndOfFile,
             line: self.line,
+            span: Span {
+                start: self.current,
+                end: self.current,
+            },
         });
 
         Ok(self.tokens.clone())
@@ -79,10 +113,30 @@ impl Scanner {
             Some('[') => self.add_token(TokenType::LeftSquare),
             Some(']') => self.add_token(TokenType::RightSquare),
             Some('&') => self.add_token(TokenType::Ampersand),
+            Some('\'') => self.add_token(TokenType::Quote),
+            Some('`') => self.add_token(TokenType::Quasiquote),
+            Some('~') if self.peek() == Some('@') => {
+                self.advance();
+                self.add_token(TokenType::UnquoteSplicing)
+            }
+            Some('~') => self.add_token(TokenType::Unquote),
             Some('"') => self.string()?,
+            Some('r') if self.peek() == Some('"') => {
+                self.advance();
+                self.raw_string()?
+            }
+            // Type hints, e.g. `^number`, are read as symbols.
+            Some('^') if Scanner::is_alpha(self.peek()) => self.symbol(),
             Some(':') => self.keyword(),
+            Some('-') if Scanner::is_digit(self.peek()) => self.number()?,
+            Some(';') => {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.advance();
+                }
+            }
+            Some('#') if self.peek() == Some('|') => self.block_comment()?,
             Some(' ') | Some('\r') | Some('\t') => (),
-            Some('\n') => self.line = self.line + 1,
+            Some('\n') => self.line += 1,
             _ => {
                 if Scanner::is_digit(c) {
                     self.number()?;
@@ -103,53 +157,195 @@ impl Scanner {
 
     fn add_token(&mut self, token_type: TokenType) {
         self.tokens.push(Token {
-            token_type: token_type,
+            token_type,
             line: self.line,
+            span: Span {
+                start: self.start,
+                end: self.current,
+            },
         });
     }
 
     fn advance(&mut self) -> Option<char> {
-        let c = self.source.chars().nth(self.current as usize);
-        self.current = self.current + 1;
+        let c = self.source.get(self.current).copied();
+        self.current += 1;
         c
     }
 
     fn peek(&self) -> Option<char> {
-        if self.is_at_end() {
-            return None;
-        }
-        self.source.chars().nth(self.current)
+        self.source.get(self.current).copied()
     }
 
     fn peek_next(&self) -> Option<char> {
-        let next = self.current + 1;
-        if next >= self.source_length {
-            return None;
-        }
-        self.source.chars().nth(next)
+        self.source.get(self.current + 1).copied()
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source_length
+        self.current >= self.source.len()
     }
 
+    /// The source between two character offsets.
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
+    }
+
+    /// Scans a string after its opening quote. Three quotes open a heredoc,
+    /// which ends at the next three.
     fn string(&mut self) -> Result<(), HError> {
-        while self.peek() != Some('"') && !self.is_at_end() {
-            if self.peek() == Some('\n') {
-                self.line = self.line + 1;
-            }
+        if self.peek() == Some('"') && self.peek_next() == Some('"') {
+            self.advance();
             self.advance();
+            let mut segments = self.segments("\"\"\"")?;
+            dedent(&mut segments);
+            self.add_string(segments);
+        } else {
+            let segments = self.segments("\"")?;
+            self.add_string(segments);
+        }
+        Ok(())
+    }
+
+    /// Scans an `r"..."` string, after its opening `r"`. Its text is read as
+    /// is, so `#{` doesn't start a form.
+    fn raw_string(&mut self) -> Result<(), HError> {
+        let mut text = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => break,
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
+                None => return Err(HError::ScannerError("Unterminated string".to_string())),
+            }
         }
+        self.add_token(TokenType::String(text));
+        Ok(())
+    }
 
-        if self.is_at_end() {
-            return Err(HError::ScannerError("Unterminated string".to_string()));
+    /// Reads the text and `#{form}`s of a string up to and including `close`.
+    fn segments(&mut self, close: &str) -> Result<Vec<Segment>, HError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        while !self.advance_past(close) {
+            match self.advance() {
+                Some('#') if self.peek() == Some('{') => {
+                    self.advance();
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Form(self.interpolation()?));
+                }
+                Some('\\') => match self.peek().and_then(unescape) {
+                    Some(c) => {
+                        self.advance();
+                        text.push(c);
+                    }
+                    None => text.push('\\'),
+                },
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
+                None => return Err(HError::ScannerError("Unterminated string".to_string())),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
         }
+        Ok(segments)
+    }
 
-        self.advance();
+    /// Adds a string token, or an interpolation if there are forms in it.
+    fn add_string(&mut self, mut segments: Vec<Segment>) {
+        match segments.as_mut_slice() {
+            [] => self.add_token(TokenType::String(String::new())),
+            [Segment::Text(text)] => self.add_token(TokenType::String(std::mem::take(text))),
+            _ => self.add_token(TokenType::Interpolation(segments)),
+        }
+    }
 
-        let value: String = self.source[self.start + 1..self.current - 1].to_string();
-        self.add_token(TokenType::String(value));
-        Ok(())
+    /// Consumes `expected` if the source continues with it.
+    fn advance_past(&mut self, expected: &str) -> bool {
+        let len = expected.chars().count();
+        let found = self
+            .source
+            .iter()
+            .skip(self.current)
+            .take(len)
+            .copied()
+            .eq(expected.chars());
+        if found {
+            self.current += len;
+        }
+        found
+    }
+
+    /// Scans the form in a `#{...}` of a string, after its opening `#{`.
+    fn interpolation(&mut self) -> Result<Vec<Token>, HError> {
+        let (start, line) = (self.current, self.line);
+        let mut code = String::new();
+        let mut depth = 0;
+        loop {
+            let c = match self.advance() {
+                Some('}') if depth == 0 => break,
+                Some(c) => c,
+                None => {
+                    return Err(HError::ScannerError(
+                        "Unterminated #{ in string".to_string(),
+                    ))
+                }
+            };
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                // Strings in the form may hold braces of their own.
+                '"' => {
+                    code.push(c);
+                    while let Some(c) = self.advance().filter(|c| *c != '"') {
+                        code.push(c);
+                        if c == '\\' {
+                            code.extend(self.advance());
+                        }
+                    }
+                }
+                '\n' => self.line += 1,
+                _ => (),
+            }
+            code.push(c);
+        }
+
+        let mut tokens = Scanner::new(&code).scan_tokens()?;
+        for token in &mut tokens {
+            token.line += line - 1;
+            token.span.start += start;
+            token.span.end += start;
+        }
+        Ok(tokens)
+    }
+
+    /// Skips a `#| ... |#` comment, after its opening `#`.
+    fn block_comment(&mut self) -> Result<(), HError> {
+        self.advance();
+        loop {
+            match self.advance() {
+                Some('|') if self.peek() == Some('#') => {
+                    self.advance();
+                    return Ok(());
+                }
+                Some('\n') => self.line += 1,
+                Some(_) => (),
+                None => {
+                    return Err(HError::ScannerError(
+                        "Unterminated block comment".to_string(),
+                    ))
+                }
+            }
+        }
     }
 
     fn number(&mut self) -> Result<(), HError> {
@@ -166,7 +362,7 @@ impl Scanner {
             }
         }
 
-        let value = self.source[self.start..self.current].parse::<f64>();
+        let value = self.text(self.start, self.current).parse::<f64>();
         match value {
             Ok(v) => self.add_token(TokenType::Number(v)),
             Err(_) => return Err(HError::ScannerError("Invalid number".to_string())),
@@ -175,15 +371,26 @@ impl Scanner {
     }
 
     fn symbol(&mut self) {
-        while Scanner::is_alpha_numeric(self.peek()) {
-            self.advance();
+        loop {
+            while Scanner::is_alpha_numeric(self.peek()) {
+                self.advance();
+            }
+            // An arrow, as in `some->`, is part of the symbol rather than the
+            // end of a method list.
+            let previous = self.source.get(self.current - 1);
+            if previous == Some(&'-') && self.peek() == Some('>') {
+                self.advance();
+            } else {
+                break;
+            }
         }
-        // Allow symbols to end in optional : for method arguments.
-        if self.peek() == Some(':') {
+        // Allow symbols to end in optional : for method arguments, or in #
+        // to be replaced by a fresh name inside a quasiquote.
+        if self.peek() == Some(':') || self.peek() == Some('#') {
             self.advance();
         }
 
-        let result = self.source[self.start..self.current].to_string();
+        let result = self.text(self.start, self.current);
 
         if result == "true" {
             self.add_token(TokenType::Boolean(true))
@@ -194,9 +401,9 @@ impl Scanner {
         } else {
             // Purge optional : to simplify argument handling.
             self.add_token(TokenType::Symbol(if result.ends_with(":") {
-                self.source[self.start..self.current - 1].to_string()
+                self.text(self.start, self.current - 1)
             } else {
-                self.source[self.start..self.current].to_string()
+                result
             }))
         }
     }
@@ -206,14 +413,12 @@ impl Scanner {
             self.advance();
         }
 
-        self.add_token(TokenType::Keyword(
-            self.source[self.start..self.current].to_string(),
-        ))
+        self.add_token(TokenType::Keyword(self.text(self.start, self.current)))
     }
 
     fn is_digit(c: Option<char>) -> bool {
         match c {
-            Some(value) => value >= '0' && value <= '9',
+            Some(value) => value.is_ascii_digit(),
             _ => false,
         }
     }
@@ -223,8 +428,8 @@ impl Scanner {
     fn is_alpha(c: Option<char>) -> bool {
         match c {
             Some(value) => {
-                (value >= 'a' && value <= 'z')
-                    || (value >= 'A' && value <= 'Z')
+                value.is_ascii_lowercase()
+                    || value.is_ascii_uppercase()
                     || value == '_'
                     || value == '-'
                     || value == '*'
@@ -243,10 +448,102 @@ impl Scanner {
     }
 }
 
+/// The character an escape in a string stands for, given the character
+/// after its backslash. Other backslashes are kept as written, so `"\d+"`
+/// still reads as a regex.
+fn unescape(c: char) -> Option<char> {
+    match c {
+        '"' | '\\' | '#' => Some(c),
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        _ => None,
+    }
+}
+
+/// Strips the layout of a heredoc from its text: the line break after the
+/// opening quotes, the line holding only the closing quotes, and the
+/// indentation its lines share.
+fn dedent(segments: &mut Vec<Segment>) {
+    if let Some(Segment::Text(text)) = segments.first_mut() {
+        if let Some(rest) = text.strip_prefix('\n') {
+            *text = rest.to_string();
+        }
+    }
+    if let Some(Segment::Text(text)) = segments.last_mut() {
+        if let Some(end) = text.rfind('\n') {
+            if text[end..].trim().is_empty() {
+                text.truncate(end);
+            }
+        }
+    }
+
+    // Whitespace seen so far on the current line, until it has content.
+    let mut current = Some(0);
+    let mut indent: Option<usize> = None;
+    let mut content = |current: &mut Option<usize>| {
+        if let Some(n) = current.take() {
+            indent = Some(indent.map_or(n, |indent| indent.min(n)));
+        }
+    };
+    for segment in segments.iter() {
+        match segment {
+            Segment::Text(text) => {
+                for c in text.chars() {
+                    match (c, current) {
+                        ('\n', _) => current = Some(0),
+                        (' ' | '\t', Some(n)) => current = Some(n + 1),
+                        ('\r', _) => (),
+                        _ => content(&mut current),
+                    }
+                }
+            }
+            Segment::Form(_) => content(&mut current),
+        }
+    }
+
+    let indent = indent.unwrap_or(0);
+    let mut column = Some(0);
+    for segment in segments.iter_mut() {
+        match segment {
+            Segment::Text(text) => {
+                let mut dedented = String::with_capacity(text.len());
+                for c in text.chars() {
+                    match (c, column) {
+                        ('\n', _) => {
+                            dedented.push(c);
+                            column = Some(0);
+                        }
+                        (' ' | '\t', Some(n)) if n < indent => column = Some(n + 1),
+                        _ => {
+                            dedented.push(c);
+                            column = None;
+                        }
+                    }
+                }
+                *text = dedented;
+            }
+            Segment::Form(_) => column = None,
+        }
+    }
+    segments.retain(|segment| !matches!(segment, Segment::Text(text) if text.is_empty()));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tokenizes_type_hints() {
+        let result = scan("[^number a]").unwrap();
+
+        assert_eq!(
+            result[1].token_type,
+            TokenType::Symbol("^number".to_string())
+        );
+        assert_eq!(result[2].token_type, TokenType::Symbol("a".to_string()));
+    }
+
     #[test]
     fn test_tokenizes_fn_call() {
         let result = scan("(+ 1 2)").unwrap();
@@ -326,6 +623,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tokenizes_qualified_keywords() {
+        let result = scan(":user/name").unwrap();
+
+        assert_eq!(
+            result[0].token_type,
+            TokenType::Keyword(":user/name".to_string())
+        );
+    }
+
     #[test]
     fn test_tokenizes_nil() {
         let result = scan("nil").unwrap();
@@ -340,6 +647,29 @@ mod tests {
         assert_eq!(result[0].token_type, TokenType::Ampersand);
     }
 
+    #[test]
+    fn test_tokenizes_quotes() {
+        let result = scan("'a `(b ~c ~@d)").unwrap();
+
+        let tokens: Vec<TokenType> = result.into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Quote,
+                TokenType::Symbol("a".to_string()),
+                TokenType::Quasiquote,
+                TokenType::LeftParen,
+                TokenType::Symbol("b".to_string()),
+                TokenType::Unquote,
+                TokenType::Symbol("c".to_string()),
+                TokenType::UnquoteSplicing,
+                TokenType::Symbol("d".to_string()),
+                TokenType::RightParen,
+                TokenType::EndOfFile,
+            ]
+        );
+    }
+
     #[test]
     fn test_tokenizes_angle_brackets() {
         let result = scan("<>").unwrap();
@@ -362,6 +692,88 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tokenizes_escapes() {
+        let result = scan(r##""say \"hi\"\n\t\\ \#{x} \d" "#{(f "\"}")}""##).unwrap();
+
+        assert_eq!(
+            result[0].token_type,
+            TokenType::String("say \"hi\"\n\t\\ #{x} \\d".to_string())
+        );
+        match &result[1].token_type {
+            TokenType::Interpolation(segments) => match &segments[0] {
+                Segment::Form(tokens) => {
+                    assert_eq!(tokens[2].token_type, TokenType::String("\"}".to_string()))
+                }
+                segment => panic!("Expected a form, got {:?}", segment),
+            },
+            token => panic!("Expected an interpolation, got {:?}", token),
+        }
+    }
+
+    #[test]
+    fn test_tokenizes_interpolations() {
+        let result = scan("\"Hi #{name}, #{(get m \"}\")}\"").unwrap();
+
+        let segments = match &result[0].token_type {
+            TokenType::Interpolation(segments) => segments,
+            token => panic!("Expected an interpolation, got {:?}", token),
+        };
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0], Segment::Text("Hi ".to_string()));
+        match &segments[1] {
+            Segment::Form(tokens) => {
+                assert_eq!(tokens[0].token_type, TokenType::Symbol("name".to_string()));
+                assert_eq!(tokens[0].span, Span { start: 6, end: 10 });
+            }
+            segment => panic!("Expected a form, got {:?}", segment),
+        }
+        assert_eq!(segments[2], Segment::Text(", ".to_string()));
+        match &segments[3] {
+            Segment::Form(tokens) => {
+                assert_eq!(tokens[3].token_type, TokenType::String("}".to_string()))
+            }
+            segment => panic!("Expected a form, got {:?}", segment),
+        }
+        assert!(scan("\"#{name\"").is_err());
+    }
+
+    #[test]
+    fn test_tokenizes_raw_strings() {
+        let result = scan("r\"\\d+ #{not-a-form}\" r\"\"").unwrap();
+
+        assert_eq!(
+            result[0].token_type,
+            TokenType::String("\\d+ #{not-a-form}".to_string())
+        );
+        assert_eq!(result[1].token_type, TokenType::String(String::new()));
+        assert_eq!(result[0].span, Span { start: 0, end: 20 });
+        assert!(scan("r\"open").is_err());
+    }
+
+    #[test]
+    fn test_tokenizes_heredocs() {
+        let result = scan(
+            "(f \"\"\"\n    Dear #{name},\n\n      \"Thanks\"\n    \"\"\")\n\"\"\"one line\"\"\"",
+        )
+        .unwrap();
+
+        let segments = match &result[2].token_type {
+            TokenType::Interpolation(segments) => segments,
+            token => panic!("Expected an interpolation, got {:?}", token),
+        };
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::Text("Dear ".to_string()));
+        assert_eq!(segments[2], Segment::Text(",\n\n  \"Thanks\"".to_string()));
+        assert_eq!(result[3].token_type, TokenType::RightParen);
+        assert_eq!(result[3].line, 5);
+        assert_eq!(
+            result[4].token_type,
+            TokenType::String("one line".to_string())
+        );
+        assert!(scan("\"\"\"open\"\"").is_err());
+    }
+
     #[test]
     fn test_tokenizes_numbers() {
         let result = scan("1 2.34 56.78").unwrap();
@@ -369,11 +781,16 @@ mod tests {
         assert_eq!(result[0].token_type, TokenType::Number(1.));
         assert_eq!(result[1].token_type, TokenType::Number(2.34));
         assert_eq!(result[2].token_type, TokenType::Number(56.78));
+
+        let result = scan("-1 - -2.5").unwrap();
+        assert_eq!(result[0].token_type, TokenType::Number(-1.));
+        assert_eq!(result[1].token_type, TokenType::Symbol("-".to_string()));
+        assert_eq!(result[2].token_type, TokenType::Number(-2.5));
     }
 
     #[test]
     fn test_tokenizes_symbols() {
-        let input = "+ - / * = ? ! is_symbol? set! hello";
+        let input = "+ - / * = ? ! is_symbol? set! hello x#";
         let tokens = scan(input).unwrap();
         let names = input.split(' ').collect::<Vec<&str>>();
 
@@ -385,6 +802,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tokenizes_arrows_in_symbols() {
+        let tokens = scan("(some-> m) <a ->b>").unwrap();
+
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::Symbol("some->".to_string())
+        );
+        assert_eq!(tokens[6].token_type, TokenType::Symbol("->b".to_string()));
+        assert_eq!(tokens[7].token_type, TokenType::RightAngle);
+    }
+
     #[test]
     fn test_tokenizes_method_args() {
         let input = "hello:";
@@ -402,6 +831,113 @@ mod tests {
         assert_eq!(result[2].token_type, TokenType::EndOfFile);
     }
 
+    #[test]
+    fn test_tracks_spans() {
+        let result = scan("(f \"a b\")").unwrap();
+
+        let spans: Vec<(usize, usize)> = result
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+        assert_eq!(spans, vec![(0, 1), (1, 2), (3, 8), (8, 9), (9, 9)]);
+    }
+
+    #[test]
+    fn test_scans_non_ascii_source() {
+        let result = scan("(f \"héllo\" 1 :ok) ; ñ\n(g \"ü\")").unwrap();
+
+        let tokens: Vec<(TokenType, usize, usize)> = result
+            .iter()
+            .map(|token| (token.token_type.clone(), token.span.start, token.span.end))
+            .collect();
+        assert_eq!(
+            tokens[..6],
+            [
+                (TokenType::LeftParen, 0, 1),
+                (TokenType::Symbol("f".to_string()), 1, 2),
+                (TokenType::String("héllo".to_string()), 3, 10),
+                (TokenType::Number(1.), 11, 12),
+                (TokenType::Keyword(":ok".to_string()), 13, 16),
+                (TokenType::RightParen, 16, 17),
+            ]
+        );
+        assert_eq!(result[8].token_type, TokenType::String("ü".to_string()));
+        assert_eq!(
+            result[8]
+                .span
+                .line_col("(f \"héllo\" 1 :ok) ; ñ\n(g \"ü\")"),
+            (2, 4)
+        );
+    }
+
+    #[test]
+    fn test_span_line_col() {
+        let source = "(f\n  (g x))";
+        let result = scan(source).unwrap();
+
+        assert_eq!(result[0].span.line_col(source), (1, 1));
+        assert_eq!(result[2].span.line_col(source), (2, 3));
+        assert_eq!(result[4].span.line_col(source), (2, 6));
+    }
+
+    #[test]
+    fn test_skips_comments() {
+        let result = scan("; a note\n(f ;; (g)\n #| (h\n |# x)").unwrap();
+
+        assert_eq!(result[0].token_type, TokenType::LeftParen);
+        assert_eq!(result[0].line, 2);
+        assert_eq!(result[1].token_type, TokenType::Symbol("f".to_string()));
+        assert_eq!(result[2].token_type, TokenType::Symbol("x".to_string()));
+        assert_eq!(result[2].line, 4);
+        assert_eq!(result[3].token_type, TokenType::RightParen);
+        assert!(scan("#| open").is_err());
+    }
+
+    // The inputs benchmarked in benches/scanner.rs, at a smaller size.
+    #[test]
+    fn test_scans_many_strings() {
+        let strings: Vec<String> = (0..50).map(|i| format!("\"item {}\"", i)).collect();
+        let result = scan(&format!("[{}]", strings.join(" "))).unwrap();
+
+        assert_eq!(result.len(), 53);
+        assert_eq!(
+            result[50].token_type,
+            TokenType::String("item 49".to_string())
+        );
+        assert_eq!(
+            result[50].span,
+            Span {
+                start: 481,
+                end: 490
+            }
+        );
+    }
+
+    #[test]
+    fn test_scans_deep_nesting() {
+        let result = scan(&format!("{}{}", "(f 1 ".repeat(20), ")".repeat(20))).unwrap();
+
+        assert_eq!(result.len(), 81);
+        assert_eq!(result[59].token_type, TokenType::Number(1.0));
+        assert_eq!(result[60].token_type, TokenType::RightParen);
+    }
+
+    #[test]
+    fn test_scans_long_symbols() {
+        let symbol = format!("{}-{}", "a".repeat(100), 7);
+        let result = scan(&format!("[{} {}]", symbol, symbol)).unwrap();
+
+        assert_eq!(result.len(), 5);
+        assert_eq!(result[2].token_type, TokenType::Symbol(symbol));
+        assert_eq!(
+            result[2].span,
+            Span {
+                start: 104,
+                end: 206
+            }
+        );
+    }
+
     #[test]
     fn test_tracks_line() {
         let result = scan("(\n \"\n\")").unwrap();
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+
+/// Counts of live interpreter objects. Envs and fns that outlive the
+/// interpreter that made them are usually kept alive by a closure cycle,
+/// e.g. a function stored in the env it closes over.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct MemoryStats {
+    pub envs: usize,
+    pub fns: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Env,
+    Fn,
+}
+
+thread_local! {
+    static LIVE: Cell<MemoryStats> = Cell::new(MemoryStats::default());
+}
+
+/// Held by each counted object, adding to the count until it is dropped.
+pub(crate) struct Live(Kind);
+
+impl Live {
+    pub(crate) fn new(kind: Kind) -> Live {
+        adjust(kind, |count| count + 1);
+        Live(kind)
+    }
+}
+
+impl Clone for Live {
+    fn clone(&self) -> Self {
+        Live::new(self.0)
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        adjust(self.0, |count| count.saturating_sub(1));
+    }
+}
+
+fn adjust(kind: Kind, f: impl FnOnce(usize) -> usize) {
+    LIVE.with(|live| {
+        let mut stats = live.get();
+        match kind {
+            Kind::Env => stats.envs = f(stats.envs),
+            Kind::Fn => stats.fns = f(stats.fns),
+        }
+        live.set(stats);
+    });
+}
+
+/// The objects currently alive on this thread.
+pub fn memory_stats() -> MemoryStats {
+    LIVE.with(|live| live.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{env::Env, evaluator::eval};
+
+    #[test]
+    fn test_counts_live_envs_and_fns() {
+        let env = Env::with_core_module().unwrap().into_ref();
+        let before = memory_stats();
+
+        eval("(var make (fn [] (fn [] 1)))", env.clone_ref()).unwrap();
+        let made = eval("(make)", env.clone_ref()).unwrap();
+        let after = memory_stats();
+
+        assert_eq!(after.fns, before.fns + 2);
+        drop(made);
+        assert_eq!(memory_stats().fns, before.fns + 1);
+        // The call env of `make` is captured by the fn it returned.
+        assert!(after.envs > before.envs);
+        assert_eq!(memory_stats().envs, before.envs);
+    }
+}
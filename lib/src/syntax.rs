@@ -0,0 +1,7 @@
+//! The reader, for tools such as formatters, linters, and editors that work
+//! on Huckleberry source without evaluating it.
+
+pub use crate::{
+    parser::{parse, parse_with_spans, Node},
+    scanner::{scan, Span, Token, TokenType},
+};
@@ -0,0 +1,179 @@
+use std::{cell::RefCell, fmt};
+
+use crate::{error::HError, evaluator::current_source, expr::Expr, parser::list_lines};
+
+/// One level of the call stack an error unwound through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub name: Option<String>, // Fn or method, or None for the top level
+    pub form: Option<Expr>,   // Innermost call that failed at this level
+    pub source: Option<String>,
+    pub line: Option<usize>,
+}
+
+// Exprs carry no source positions, so like coverage, a failing call is
+// matched to its line by value once the source it came from is known.
+thread_local! {
+    // The error being traced, and the frames it has unwound through so far,
+    // innermost first. The last frame is open until a call returns the error.
+    static TRACE: RefCell<Option<(HError, Vec<Frame>)>> = const { RefCell::new(None) };
+}
+
+/// Forgets the trace of the last error, e.g. once it has been handled.
+pub(crate) fn clear() {
+    TRACE.with(|trace| trace.replace(None));
+}
+
+/// Notes that `form` failed with `err`, unless a call inside it at the same
+/// level already did.
+#[cold]
+pub(crate) fn record_form(form: &Expr, err: HError) -> HError {
+    with_frames(&err, |frames| {
+        if frames.last().is_none_or(|frame| frame.name.is_some()) {
+            frames.push(Frame {
+                name: None,
+                form: Some(form.clone()),
+                source: current_source(),
+                line: None,
+            });
+        }
+    });
+    err
+}
+
+/// Notes that `err` unwound out of a call to `name`, defined in `source`,
+/// closing the open frame.
+#[cold]
+pub(crate) fn record_call(name: &str, source: &Option<String>, err: HError) -> HError {
+    with_frames(&err, |frames| match frames.last_mut() {
+        Some(frame) if frame.name.is_none() => {
+            frame.name = Some(name.to_string());
+            frame.source.clone_from(source);
+        }
+        _ => frames.push(Frame {
+            name: Some(name.to_string()),
+            form: None,
+            source: source.clone(),
+            line: None,
+        }),
+    });
+    err
+}
+
+/// Fills in the lines of frames that failed in `input`, the contents of the
+/// file `name`.
+#[cold]
+pub(crate) fn locate(input: &str, name: &str, err: &HError) {
+    let lines = match list_lines(input) {
+        Ok(lines) => lines,
+        Err(_) => return,
+    };
+    with_frames(err, |frames| {
+        for frame in frames {
+            if frame.line.is_none() && frame.source.as_deref() == Some(name) {
+                frame.line = frame.form.as_ref().and_then(|form| {
+                    lines
+                        .iter()
+                        .find(|(_, list)| list == form)
+                        .map(|(line, _)| *line)
+                });
+            }
+        }
+    });
+}
+
+/// The calls `err` unwound through, innermost first, ending with the top
+/// level. Empty unless `err` is the last error raised on this thread.
+pub fn backtrace(err: &HError) -> Vec<Frame> {
+    TRACE.with(|trace| match &*trace.borrow() {
+        Some((traced, frames)) if traced == err => frames.clone(),
+        _ => vec![],
+    })
+}
+
+/// Runs `f` on the frames of `err`, starting a new trace if the last one was
+/// for another error.
+fn with_frames(err: &HError, f: impl FnOnce(&mut Vec<Frame>)) {
+    TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        match &mut *trace {
+            Some((traced, frames)) if traced == err => f(frames),
+            _ => {
+                let mut frames = vec![];
+                f(&mut frames);
+                *trace = Some((err.clone(), frames));
+            }
+        }
+    });
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}", self.name.as_deref().unwrap_or("top level"))?;
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => write!(f, " ({}:{})", source, line)?,
+            (Some(source), None) => write!(f, " ({})", source)?,
+            _ => (),
+        }
+        match &self.form {
+            Some(form) => write!(f, ": {}", form),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_traces_nested_calls() {
+        let interpreter = Interpreter::new().unwrap();
+        let err = interpreter
+            .eval_source(
+                "(defn half [n]\n  (/ n :two))\n(defn outer [n]\n  (+ 1 (half n)))\n(outer 6)",
+                "half.huck",
+            )
+            .unwrap_err();
+
+        let frames: Vec<String> = backtrace(&err).iter().map(Frame::to_string).collect();
+        assert_eq!(
+            frames,
+            vec![
+                "at half (half.huck:2): (/ n :two)",
+                "at outer (half.huck:4): (half n)",
+                "at top level (half.huck:5): (outer 6)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handled_errors_leave_no_trace() {
+        let interpreter = Interpreter::new().unwrap();
+
+        interpreter
+            .eval("(defn fail [] (throw :a)) (try (fail) (catch e e))")
+            .unwrap();
+        let err = interpreter.eval("(fail)").unwrap_err();
+
+        assert_eq!(
+            backtrace(&err),
+            vec![
+                Frame {
+                    name: Some("fail".to_string()),
+                    form: Some(Expr::list(&[Expr::symbol("throw"), Expr::keyword(":a")])),
+                    source: None,
+                    line: None,
+                },
+                Frame {
+                    name: None,
+                    form: Some(Expr::list(&[Expr::symbol("fail")])),
+                    source: None,
+                    line: None,
+                },
+            ]
+        );
+        assert_eq!(backtrace(&HError::Interrupted), vec![]);
+    }
+}